@@ -0,0 +1,24 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use passgen_ui::passgen_core::storage::{Storage, TARGET_UNLOCK_TIME};
+use std::hint::black_box;
+
+fn derive_key(c: &mut Criterion) {
+    let salt = [7u8; 16];
+    for iterations in [10_000, 100_000] {
+        c.bench_function(&format!("derive_key/{}", iterations), |b| {
+            b.iter(|| Storage::derive_key(black_box("master"), &salt, &[], iterations))
+        });
+    }
+
+    // The work factor new vaults would get on this machine
+    let calibrated = Storage::calibrate_iterations(TARGET_UNLOCK_TIME);
+    let mut group = c.benchmark_group("calibrated");
+    group.sample_size(10);
+    group.bench_function(format!("derive_key/{}", calibrated), |b| {
+        b.iter(|| Storage::derive_key(black_box("master"), &salt, &[], calibrated))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, derive_key);
+criterion_main!(benches);
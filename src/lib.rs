@@ -1 +1,4 @@
 pub mod passgen_core;
+
+/// Programmatic access to a vault without the TUI
+pub use passgen_core::storage::{Cipher, PasswordEntry, SortMode, Storage, StorageError};
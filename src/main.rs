@@ -1,41 +1,217 @@
-use arboard::Clipboard;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use passgen_ui::passgen_core::{
-    app::{App, ViewMode},
-    storage::{PasswordEntry, Storage},
-    ui,
+    app::{App, ViewMode, chrono_timestamp},
+    clipboard,
+    config::{Config, DirProfile},
+    generator::generate_password,
+    logging,
+    session::{Action, ChangeStep, Phase, Session, SessionOptions},
+    storage::{Cipher, PasswordEntry, Storage, name_exists, sanitize_name},
+    ui, wordlist,
 };
-use ratatui::{Terminal, backend::CrosstermBackend};
-use std::io;
-
-/// Application phase
-enum Phase {
-    MasterPassword,
-    Main,
-    ChangeMasterPassword { step: ChangeStep },
-    ViewPasswords { mode: ViewMode },
+use ratatui::{
+    Frame, Terminal,
+    backend::CrosstermBackend,
+    layout::{Position, Rect},
+};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often the loop wakes up without input to refresh timed state
+const TICK_RATE: Duration = Duration::from_millis(250);
+
+/// Environment variable the CLI commands take the master password from
+const MASTER_PASSWORD_ENV: &str = "PASSGEN_MASTER_PASSWORD";
+
+/// Environment variable `change-master` takes the new master password from
+const NEW_MASTER_PASSWORD_ENV: &str = "PASSGEN_NEW_MASTER_PASSWORD";
+
+/// What to do once the arguments are parsed
+#[derive(PartialEq)]
+enum Command {
+    /// The interactive interface
+    Tui,
+    /// Decrypt the vault, report its health and exit
+    Verify,
+    /// List entries that are weak, reused or expired and exit
+    Audit,
+    /// Rewrite the vault without cruft and report the bytes saved
+    Compact,
+    /// Store one entry without the interface and print its generated password
+    Add,
+    /// Remove the entry with the given name
+    Delete,
+    /// Give an entry a new name
+    Rename,
+    /// Put the vault under a new master password
+    ChangeMaster,
 }
 
-enum ChangeStep {
-    EnterOld,
-    EnterNew,
-    ConfirmNew,
+/// Command-line options
+struct Args {
+    command: Command,
+    keyfile: Option<PathBuf>,
+    yubikey: bool,
+    read_only: bool,
+    cipher: Cipher,
+    wordlist: Option<PathBuf>,
+    /// Offer reproducible, insecure seeded generation
+    insecure_seeded: bool,
+    name: Option<String>,
+    username: String,
+    url: String,
+    /// Read the password from stdin instead of generating one
+    password_stdin: bool,
+    /// Add even if an entry with the same name exists
+    force: bool,
+    /// Generate with this saved preset's settings
+    preset: Option<String>,
+    /// Entry names given to `delete` and `rename`
+    names: Vec<String>,
+    /// Skip the confirmation question
+    yes: bool,
 }
 
-/// State for the password viewer
-struct ViewerState {
-    entries: Vec<PasswordEntry>,
-    selected: usize,
-    revealed: std::collections::HashSet<usize>,
-    status_message: Option<String>,
-    edit_buffer: String,
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Args {
+            command: Command::Tui,
+            keyfile: None,
+            yubikey: false,
+            read_only: false,
+            cipher: Cipher::default(),
+            wordlist: None,
+            insecure_seeded: false,
+            name: None,
+            username: String::new(),
+            url: String::new(),
+            password_stdin: false,
+            force: false,
+            preset: None,
+            names: Vec::new(),
+            yes: false,
+        };
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--keyfile" => {
+                    let path = iter.next().ok_or("--keyfile needs a path")?;
+                    args.keyfile = Some(PathBuf::from(path));
+                }
+                "--yubikey" => args.yubikey = true,
+                "--read-only" => args.read_only = true,
+                "--insecure-seeded" => args.insecure_seeded = true,
+                "--cipher" => {
+                    let name = iter.next().ok_or("--cipher needs a name")?;
+                    args.cipher = Cipher::from_name(&name)?;
+                }
+                "--wordlist" => {
+                    let path = iter.next().ok_or("--wordlist needs a path")?;
+                    args.wordlist = Some(PathBuf::from(path));
+                }
+                "verify" if args.command == Command::Tui => args.command = Command::Verify,
+                "audit" if args.command == Command::Tui => args.command = Command::Audit,
+                "compact" if args.command == Command::Tui => args.command = Command::Compact,
+                "add" if args.command == Command::Tui => args.command = Command::Add,
+                "--name" if args.command == Command::Add => {
+                    args.name = Some(iter.next().ok_or("--name needs a value")?);
+                }
+                "--username" if args.command == Command::Add => {
+                    args.username = iter.next().ok_or("--username needs a value")?;
+                }
+                "--url" if args.command == Command::Add => {
+                    args.url = iter.next().ok_or("--url needs a value")?;
+                }
+                "--password" if args.command == Command::Add => {
+                    // Only stdin: a password in argv is visible to other processes
+                    match iter.next().as_deref() {
+                        Some("-") => args.password_stdin = true,
+                        _ => return Err("--password only accepts - (read from stdin)".into()),
+                    }
+                }
+                "--force" if args.command == Command::Add => args.force = true,
+                "--preset" if args.command == Command::Add => {
+                    args.preset = Some(iter.next().ok_or("--preset needs a name")?);
+                }
+                "delete" if args.command == Command::Tui => args.command = Command::Delete,
+                "rename" if args.command == Command::Tui => args.command = Command::Rename,
+                "change-master" if args.command == Command::Tui => {
+                    args.command = Command::ChangeMaster;
+                }
+                "--yes" if matches!(args.command, Command::Delete | Command::Rename) => {
+                    args.yes = true;
+                }
+                name if matches!(args.command, Command::Delete | Command::Rename)
+                    && !name.starts_with("--") =>
+                {
+                    args.names.push(name.to_string());
+                }
+                other => return Err(format!("Unknown argument: {}", other)),
+            }
+        }
+        match args.command {
+            Command::Delete if args.names.len() != 1 => {
+                return Err("delete needs exactly one name".into());
+            }
+            Command::Rename if args.names.len() != 2 => {
+                return Err("rename needs the old and the new name".into());
+            }
+            _ => {}
+        }
+        if args.command == Command::Add && args.name.as_deref().is_none_or(|n| n.trim().is_empty())
+        {
+            return Err("add needs --name".into());
+        }
+        Ok(args)
+    }
 }
 
 fn main() -> io::Result<()> {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!(
+                "Usage: passgen_ui [verify] [--keyfile <path>] [--yubikey] [--read-only] [--cipher aes-256-gcm|chacha20-poly1305] [--wordlist <path>] [--insecure-seeded]"
+            );
+            eprintln!(
+                "       passgen_ui add --name <name> [--username <user>] [--url <url>] [--password -] [--preset <name>] [--force]"
+            );
+            eprintln!("       passgen_ui audit [--keyfile <path>] [--yubikey]");
+            eprintln!("       passgen_ui compact [--keyfile <path>] [--yubikey]");
+            eprintln!("       passgen_ui delete <name> [--yes]");
+            eprintln!("       passgen_ui rename <old> <new> [--yes]");
+            eprintln!("       passgen_ui change-master");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = logging::init() {
+        eprintln!("Warning: {}", e);
+    }
+    // Panic messages can quote whatever was being processed
+    std::panic::set_hook(Box::new(|info| {
+        eprintln!("{}", logging::redact(&info.to_string()));
+    }));
+
+    match args.command {
+        Command::Verify => std::process::exit(verify(&args)),
+        Command::Audit => std::process::exit(audit(&args)),
+        Command::Compact => std::process::exit(compact(&args)),
+        Command::Add => std::process::exit(add(&args)),
+        Command::Delete | Command::Rename => std::process::exit(manage(&args)),
+        Command::ChangeMaster => std::process::exit(change_master(&args)),
+        Command::Tui => {}
+    }
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -43,7 +219,7 @@ fn main() -> io::Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let result = run(&mut terminal);
+    let result = run(&mut terminal, &args);
 
     // Restore terminal
     disable_raw_mode()?;
@@ -54,437 +230,594 @@ fn main() -> io::Result<()> {
     )?;
     terminal.show_cursor()?;
 
-    if let Err(err) = result {
-        eprintln!("Error: {err:?}");
+    match result {
+        // Only now that the terminal is back to normal can it take the keystrokes
+        Ok(Some((text, delay))) => {
+            if let Err(e) = clipboard::type_out(&text, delay) {
+                eprintln!("Error: {}", e);
+            }
+        }
+        Ok(None) => {}
+        Err(err) => eprintln!("Error: {err:?}"),
     }
 
     Ok(())
 }
 
-fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
-    let mut app = App::new();
-    let mut phase = Phase::MasterPassword;
-    let mut master_input = String::new();
-    let mut storage: Option<Storage> = None;
+/// Where the vault lives: the configured path, else the default one
+fn vault_path(config: &Config) -> Result<PathBuf, String> {
+    config
+        .vault_path
+        .clone()
+        .map_or_else(Storage::default_path, Ok)
+}
 
-    // For password change flow
-    let mut new_password = String::new();
-    let mut confirm_password = String::new();
+/// Unlock the vault for a CLI subcommand. With `must_exist`, a missing vault is
+/// an error rather than a new one
+fn open_vault_cli(args: &Args, config: &Config, must_exist: bool) -> Result<Storage, String> {
+    let path = vault_path(config)?;
+    if must_exist && !path.exists() {
+        return Err(format!("No vault at {}", path.display()));
+    }
+    Storage::new_with_factors_at(
+        &master_password()?,
+        &path,
+        args.keyfile.as_deref(),
+        args.yubikey,
+        args.cipher,
+    )
+}
 
-    // For password viewer
-    let mut viewer_state: Option<ViewerState> = None;
+/// `passgen_ui verify`: print a health report; the exit code is nonzero on problems
+fn verify(args: &Args) -> i32 {
+    let storage = match open_vault_cli(args, &Config::load(), true) {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+    match storage.verify_integrity() {
+        Ok(stats) => {
+            println!("{}", storage.path().display());
+            println!("{}", stats);
+            i32::from(!stats.is_healthy())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
 
-    loop {
-        // Render
-        terminal.draw(|f| match &phase {
-            Phase::MasterPassword => {
-                ui::render(f, &app, true, &master_input, None);
-            }
-            Phase::Main => {
-                ui::render(f, &app, false, "", None);
-            }
-            Phase::ChangeMasterPassword { step } => {
-                let prompt = match step {
-                    ChangeStep::EnterOld => ("Enter current master password:", &master_input),
-                    ChangeStep::EnterNew => ("Enter NEW master password:", &new_password),
-                    ChangeStep::ConfirmNew => ("Confirm NEW master password:", &confirm_password),
-                };
-                ui::render(f, &app, true, prompt.1, Some(prompt.0));
+/// `passgen_ui audit`: list entries needing attention; the exit code is nonzero if any do
+fn audit(args: &Args) -> i32 {
+    let storage = match open_vault_cli(args, &Config::load(), true) {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+    match storage.audit() {
+        Ok(report) => {
+            println!("{}", storage.path().display());
+            println!("{}", report);
+            i32::from(report.issues() > 0)
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+/// `passgen_ui compact`: rewrite the vault and report the bytes saved
+fn compact(args: &Args) -> i32 {
+    match open_vault_cli(args, &Config::load(), false).and_then(|storage| storage.compact()) {
+        Ok(report) => {
+            println!("{}", report);
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+/// `passgen_ui add`: store one entry and print its password if it was generated;
+/// one given on stdin is never echoed back
+fn add(args: &Args) -> i32 {
+    match add_entry(args) {
+        Ok(password) => {
+            if !args.password_stdin {
+                println!("{}", password);
             }
-            Phase::ViewPasswords { mode } => {
-                if let Some(ref state) = viewer_state {
-                    ui::render_password_list(
-                        f,
-                        &state.entries,
-                        state.selected,
-                        &state.revealed,
-                        mode,
-                        state.status_message.as_deref(),
-                        &state.edit_buffer,
-                    );
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn add_entry(args: &Args) -> Result<String, String> {
+    let config = Config::load();
+    let name = sanitize_name(&args.name.clone().unwrap_or_default());
+    if name.is_empty() {
+        return Err("The name has no visible characters".into());
+    }
+    let (password, gen_options) = if args.password_stdin {
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read password from stdin: {}", e))?;
+        let password = line.trim_end_matches(['\r', '\n']).to_string();
+        if password.is_empty() {
+            return Err("No password on stdin".into());
+        }
+        (password, None)
+    } else {
+        // The same defaults the interface starts with, then the chosen preset
+        let mut app = App::from_config(&config);
+        apply_dir_profile(&mut app)?;
+        if let Some(ref wanted) = args.preset {
+            let preset = config
+                .presets
+                .iter()
+                .find(|p| &p.name == wanted)
+                .ok_or_else(|| format!("No preset named '{}'", wanted))?;
+            app.apply_preset(preset);
+        }
+        if let Err(e) = load_wordlist(args, &config) {
+            eprintln!("Warning: {}; using the built-in wordlist", e);
+        }
+        let opts = app.gen_options()?;
+        (generate_password(&opts)?, Some(opts))
+    };
+
+    let storage = open_vault_cli(args, &config, false)?;
+    // Load first: a wrong master password must not be mistaken for an empty vault
+    let entries = storage.load()?;
+    if !args.force && name_exists(&entries, &name, None) {
+        return Err(format!(
+            "An entry named '{}' already exists; use --force",
+            name
+        ));
+    }
+    storage.save(PasswordEntry {
+        name,
+        username: args.username.clone(),
+        url: args.url.trim().to_string(),
+        password: password.clone(),
+        created_at: chrono_timestamp(),
+        gen_options,
+        ..Default::default()
+    })?;
+    Ok(password)
+}
+
+/// `passgen_ui delete` / `passgen_ui rename`: change one entry by name
+fn manage(args: &Args) -> i32 {
+    match delete_or_rename(args) {
+        Ok(done) => {
+            println!("{}", done);
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn delete_or_rename(args: &Args) -> Result<String, String> {
+    let storage = open_vault_cli(args, &Config::load(), true)?;
+    let name = &args.names[0];
+    let index = storage.index_by_name(name)?;
+    let entries = storage.load()?;
+    let entry = &entries[index];
+
+    if args.command == Command::Delete {
+        if !args.yes && !confirm(&format!("Delete '{}'?", entry.name))? {
+            return Err("Cancelled".into());
+        }
+        storage.delete(index)?;
+        return Ok(format!("Deleted '{}'", entry.name));
+    }
+
+    let new_name = sanitize_name(&args.names[1]);
+    let new_name = new_name.as_str();
+    if new_name.is_empty() {
+        return Err("The new name cannot be empty".into());
+    }
+    if name_exists(&entries, new_name, Some(index)) {
+        return Err(format!("An entry named '{}' already exists", new_name));
+    }
+    if !args.yes && !confirm(&format!("Rename '{}' to '{}'?", entry.name, new_name))? {
+        return Err("Cancelled".into());
+    }
+    let mut renamed = entry.clone();
+    renamed.name = new_name.to_string();
+    storage.update(index, renamed)?;
+    Ok(format!("Renamed '{}' to '{}'", entry.name, new_name))
+}
+
+/// `passgen_ui change-master`: put the vault under a new master password
+fn change_master(args: &Args) -> i32 {
+    match rotate_master_password(args) {
+        Ok(count) => {
+            println!("Master password changed; {} entries re-keyed", count);
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn rotate_master_password(args: &Args) -> Result<usize, String> {
+    let storage = open_vault_cli(args, &Config::load(), true)?;
+    // Fail on a wrong old password before asking for the new one
+    storage.load()?;
+    let new_password = new_master_password()?;
+    storage.change_master_password(&new_password)?;
+    // Reopen from disk so the check covers exactly what a later unlock will read
+    let reopened = Storage::new_with_factors_at(
+        &new_password,
+        storage.path(),
+        args.keyfile.as_deref(),
+        args.yubikey,
+        args.cipher,
+    )?;
+    Ok(reopened.load()?.len())
+}
+
+/// New master password: the environment if set, else typed twice at the terminal
+fn new_master_password() -> Result<String, String> {
+    if let Some(password) = std::env::var(NEW_MASTER_PASSWORD_ENV)
+        .ok()
+        .filter(|p| !p.is_empty())
+    {
+        return Ok(password);
+    }
+    let read =
+        |prompt| read_password(prompt).map_err(|e| format!("Failed to read password: {}", e));
+    let password = read("New master password: ")?;
+    if password.is_empty() {
+        return Err("Password cannot be empty".into());
+    }
+    if read("Confirm new master password: ")? != password {
+        return Err("Passwords don't match".into());
+    }
+    Ok(password)
+}
+
+/// Ask a yes/no question, reading the answer from stdin
+fn confirm(question: &str) -> Result<bool, String> {
+    eprint!("{} [y/N] ", question);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|e| format!("Failed to read answer: {}", e))?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Master password for the CLI commands: the environment if set, else the terminal
+fn master_password() -> Result<String, String> {
+    if let Some(password) = std::env::var(MASTER_PASSWORD_ENV)
+        .ok()
+        .filter(|p| !p.is_empty())
+    {
+        return Ok(password);
+    }
+    read_password("Master password: ").map_err(|e| format!("Failed to read password: {}", e))
+}
+
+/// Read a line from the terminal without echoing it
+fn read_password(prompt: &str) -> io::Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    enable_raw_mode()?;
+    let mut password = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Backspace => {
+                    password.pop();
                 }
-            }
-        })?;
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                }
+                KeyCode::Char(c) => password.push(c),
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    disable_raw_mode()?;
+    eprintln!();
+    result.map(|_| password)
+}
+
+/// Returns text to type into the terminal once it is restored, with the pause
+/// between characters
+fn run(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    args: &Args,
+) -> io::Result<Option<(String, Duration)>> {
+    let config = Config::load();
+    let options = SessionOptions {
+        vault_path: vault_path(&config).ok(),
+        keyfile: args.keyfile.clone(),
+        yubikey: args.yubikey,
+        read_only: args.read_only,
+        cipher: args.cipher,
+        insecure_seeded: args.insecure_seeded,
+    };
+    let mut session = Session::new(config, options);
+    if let Some(notice) = Storage::home_fallback_notice() {
+        session.app.set_error(notice);
+    }
+    if let Err(e) = apply_dir_profile(&mut session.app) {
+        session.app.set_error(e);
+    }
+    if let Err(e) = load_wordlist(args, &session.config) {
+        session
+            .app
+            .set_error(format!("{}; using the built-in wordlist", e));
+    }
 
-        // Handle input
-        if let Event::Key(key) = event::read()? {
-            if key.kind != KeyEventKind::Press {
-                continue;
+    while !session.quit {
+        session.tick();
+        terminal.draw(|f| draw(f, &session))?;
+
+        // Handle input, waking up periodically so timed messages can expire
+        if !event::poll(TICK_RATE)? {
+            continue;
+        }
+        let action = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => session.map_key(key),
+            Event::Mouse(mouse) => map_mouse(&session, mouse, terminal.get_frame().area()),
+            _ => None,
+        };
+        if let Some(action) = action {
+            // Derivation blocks the loop, so show why before it starts
+            if session.derives_key(action) {
+                terminal.draw(|f| {
+                    draw(f, &session);
+                    ui::render_busy(f, "Deriving key...");
+                })?;
+            }
+            if session.contacts_network(action) {
+                terminal.draw(|f| {
+                    draw(f, &session);
+                    ui::render_busy(f, "Checking breach data...");
+                })?;
             }
+            session.apply(action);
+        }
+    }
+    session.clear_clipboard_on_exit();
+    let delay = Duration::from_millis(session.config.type_out_delay_ms);
+    Ok(session.type_on_exit.take().map(|text| (text, delay)))
+}
 
-            match &mut phase {
-                Phase::MasterPassword => match key.code {
-                    KeyCode::Esc => return Ok(()),
-                    KeyCode::Enter => {
-                        if master_input.is_empty() {
-                            continue;
-                        }
-                        match Storage::new(&master_input) {
-                            Ok(s) => {
-                                storage = Some(s);
-                                phase = Phase::Main;
-                                master_input.clear();
-                            }
-                            Err(e) => {
-                                app.error = Some(e);
-                                master_input.clear();
-                            }
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        master_input.pop();
-                    }
-                    KeyCode::Char(c) => {
-                        master_input.push(c);
-                    }
-                    _ => {}
-                },
-                Phase::Main => {
-                    match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Esc => return Ok(()),
-                        KeyCode::Char('c') => {
-                            // Start change password flow
-                            phase = Phase::ChangeMasterPassword {
-                                step: ChangeStep::EnterOld,
-                            };
-                            master_input.clear();
-                            new_password.clear();
-                            confirm_password.clear();
-                            app.error = None;
-                            app.status_message = None;
-                        }
-                        KeyCode::Char('v') => {
-                            // View saved passwords
-                            if let Some(ref store) = storage {
-                                match store.load() {
-                                    Ok(entries) => {
-                                        viewer_state = Some(ViewerState {
-                                            entries,
-                                            selected: 0,
-                                            revealed: std::collections::HashSet::new(),
-                                            status_message: None,
-                                            edit_buffer: String::new(),
-                                        });
-                                        phase = Phase::ViewPasswords { mode: ViewMode::Browse };
-                                        app.error = None;
-                                    }
-                                    Err(e) => {
-                                        app.error = Some(format!("Failed to load: {}", e));
-                                    }
-                                }
-                            }
-                        }
-                        KeyCode::Tab | KeyCode::Down => app.next_field(),
-                        KeyCode::BackTab | KeyCode::Up => app.prev_field(),
-                        KeyCode::Enter => {
-                            app.generate();
-                            // Auto-save if generation succeeded
-                            if app.generated_password.is_some()
-                                && let Some(ref store) = storage
-                                && let Some(entry) = app.get_entry()
-                            {
-                                match store.save(entry) {
-                                    Ok(_) => {
-                                        app.status_message =
-                                            Some(format!("✓ Saved to {}", store.path().display()));
-                                    }
-                                    Err(e) => {
-                                        app.error = Some(format!("Save failed: {}", e));
-                                    }
-                                }
-                            }
-                        }
-                        KeyCode::Char(' ') => {
-                            app.toggle_current();
+/// Apply the `.passgenrc` in the working directory, which sets project-specific
+/// generator defaults
+fn apply_dir_profile(app: &mut App) -> Result<(), String> {
+    let dir =
+        std::env::current_dir().map_err(|e| format!("Cannot read the working directory: {}", e))?;
+    if let Some(profile) = DirProfile::load(&dir)? {
+        app.apply_profile(&profile);
+    }
+    Ok(())
+}
+
+/// Switch passphrases to the wordlist from the arguments or the config, if any
+fn load_wordlist(args: &Args, config: &Config) -> Result<(), String> {
+    match args.wordlist.as_ref().or(config.wordlist.as_ref()) {
+        Some(path) => wordlist::load_custom(path).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+/// Clicking the viewer's column header cycles the sort
+fn map_mouse(session: &Session, mouse: MouseEvent, size: Rect) -> Option<Action> {
+    let browsing = session.phase
+        == Phase::ViewPasswords {
+            mode: ViewMode::Browse,
+        };
+    let clicked = Position::new(mouse.column, mouse.row);
+    (browsing
+        && mouse.kind == MouseEventKind::Down(MouseButton::Left)
+        && ui::viewer_header_area(size).contains(clicked))
+    .then_some(Action::CycleSort)
+}
+
+/// Render whichever screen the session is on
+fn draw(f: &mut Frame, session: &Session) {
+    if !ui::ensure_min_size(f) {
+        ui::render_too_small(f);
+        return;
+    }
+    let app = &session.app;
+    match &session.phase {
+        Phase::Onboarding => {
+            let path = session
+                .options
+                .vault_path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default();
+            ui::render_onboarding(f, &path);
+        }
+        Phase::MasterPassword => {
+            ui::render(f, app, true, &session.master_input, None);
+        }
+        Phase::Main => {
+            ui::render(f, app, false, "", None);
+        }
+        Phase::MoveVault => {
+            ui::render(f, app, false, "", None);
+            ui::render_path_prompt(
+                f,
+                "Move Vault",
+                "New vault path (must not exist yet):",
+                &session.prompt_input,
+            );
+        }
+        Phase::ImportPath => {
+            ui::render(f, app, false, "", None);
+            ui::render_path_prompt(
+                f,
+                "Import",
+                "JSON, CSV or KeePass CSV file to import:",
+                &session.prompt_input,
+            );
+        }
+        Phase::ExportPath => {
+            ui::render(f, app, false, "", None);
+            let title = match &session.export_only {
+                Some(entries) => format!("Encrypted Export ({} marked)", entries.len()),
+                None => "Encrypted Export".to_string(),
+            };
+            ui::render_path_prompt(
+                f,
+                &title,
+                "Destination file (must not exist yet):",
+                &session.prompt_input,
+            );
+        }
+        Phase::ExportPassword => {
+            ui::render(
+                f,
+                app,
+                true,
+                &session.new_password,
+                Some("Password for the exported copy:"),
+            );
+        }
+        Phase::DestroyVault => {
+            ui::render(
+                f,
+                app,
+                true,
+                &session.master_input,
+                Some("Master password, to overwrite and delete the vault:"),
+            );
+        }
+        Phase::Seed => {
+            ui::render(f, app, false, "", None);
+            ui::render_path_prompt(
+                f,
+                "Insecure Seed",
+                "Seed (blank for a random one each time); NOT for real secrets:",
+                &session.prompt_input,
+            );
+        }
+        Phase::TargetBits => {
+            ui::render(f, app, false, "", None);
+            ui::render_path_prompt(
+                f,
+                "Target Strength",
+                "Bits of entropy to reach (e.g. 128):",
+                &session.prompt_input,
+            );
+        }
+        Phase::PresetName => {
+            ui::render(f, app, false, "", None);
+            ui::render_path_prompt(
+                f,
+                "Save Preset",
+                "Name for the current generator settings:",
+                &session.prompt_input,
+            );
+        }
+        Phase::Duplicates { selected } => {
+            ui::render(f, app, false, "", None);
+            if let Some(group) = session.duplicates.first() {
+                ui::render_duplicates(f, group, *selected, session.duplicates.len());
+            }
+        }
+        Phase::Stats => {
+            ui::render(f, app, false, "", None);
+            if let Some(stats) = &session.stats {
+                ui::render_stats(f, stats);
+            }
+        }
+        Phase::Audit => {
+            ui::render(f, app, false, "", None);
+            if let Some(report) = &session.audit {
+                ui::render_audit(f, report);
+            }
+        }
+        Phase::ImportStrategy { selected } => {
+            ui::render(f, app, false, "", None);
+            ui::render_merge_choice(f, *selected);
+        }
+        Phase::ChangeMasterPassword { step } => {
+            let prompt = match step {
+                ChangeStep::EnterOld => ("Enter current master password:", &session.master_input),
+                ChangeStep::EnterNew => ("Enter NEW master password:", &session.new_password),
+                ChangeStep::ConfirmNew => {
+                    ("Confirm NEW master password:", &session.confirm_password)
+                }
+            };
+            ui::render(f, app, true, prompt.1, Some(prompt.0));
+        }
+        Phase::ViewPasswords { mode } => {
+            if let Some(ref state) = session.viewer {
+                let context = ui::ViewContext {
+                    state,
+                    mode,
+                    read_only: app.read_only,
+                    type_out: session.config.type_out,
+                    allow_network: session.config.allow_network,
+                    allow_reveal_all: session.config.allow_reveal_all,
+                };
+                ui::render_password_list(f, &context, session.has_unsaved_changes());
+                if let Some(entry) = state.entries.get(state.selected) {
+                    match mode {
+                        ViewMode::ShowQr => ui::render_qr(f, &entry.password),
+                        ViewMode::Detail => {
+                            ui::render_entry_detail(f, entry, state.is_revealed(state.selected))
                         }
-                        KeyCode::Backspace => {
-                            if let Some(input) = app.current_text_input() {
-                                input.pop();
+                        ViewMode::EditNotes => {
+                            if let Some(editor) = &state.notes {
+                                ui::render_notes_editor(f, &editor.lines, editor.cursor);
                             }
                         }
-                        KeyCode::Char(c) => {
-                            if let Some(input) = app.current_text_input() {
-                                input.push(c);
+                        ViewMode::EditEntry => {
+                            if let Some(draft) = &state.draft {
+                                ui::render_entry_form(
+                                    f,
+                                    draft,
+                                    &state.draft_expiry,
+                                    state.draft_field,
+                                );
                             }
                         }
-                        _ => {}
-                    }
-                }
-                Phase::ChangeMasterPassword { step } => {
-                    match key.code {
-                        KeyCode::Esc => {
-                            // Cancel and go back to main
-                            phase = Phase::Main;
-                            master_input.clear();
-                            new_password.clear();
-                            confirm_password.clear();
-                            app.error = None;
-                        }
-                        KeyCode::Enter => {
-                            match step {
-                                ChangeStep::EnterOld => {
-                                    // Verify old password by trying to load
-                                    match Storage::new(&master_input) {
-                                        Ok(s) => {
-                                            storage = Some(s);
-                                            *step = ChangeStep::EnterNew;
-                                            app.error = None;
-                                        }
-                                        Err(e) => {
-                                            app.error = Some(e);
-                                            master_input.clear();
-                                        }
-                                    }
-                                }
-                                ChangeStep::EnterNew => {
-                                    if new_password.is_empty() {
-                                        app.error = Some("Password cannot be empty".into());
-                                    } else {
-                                        *step = ChangeStep::ConfirmNew;
-                                        app.error = None;
-                                    }
-                                }
-                                ChangeStep::ConfirmNew => {
-                                    if confirm_password != new_password {
-                                        app.error = Some("Passwords don't match".into());
-                                        confirm_password.clear();
-                                    } else if let Some(ref store) = storage {
-                                        match store.change_master_password(&new_password) {
-                                            Ok(new_store) => {
-                                                storage = Some(new_store);
-                                                app.status_message =
-                                                    Some("✓ Master password changed!".into());
-                                                app.error = None;
-                                                phase = Phase::Main;
-                                                master_input.clear();
-                                                new_password.clear();
-                                                confirm_password.clear();
-                                            }
-                                            Err(e) => {
-                                                app.error = Some(format!("Failed: {}", e));
-                                            }
-                                        }
-                                    }
-                                }
+                        ViewMode::BreachReport => {
+                            if let Some(report) = &state.breach_report {
+                                ui::render_breach_report(f, &entry.name, report);
                             }
                         }
-                        KeyCode::Backspace => match step {
-                            ChangeStep::EnterOld => {
-                                master_input.pop();
-                            }
-                            ChangeStep::EnterNew => {
-                                new_password.pop();
-                            }
-                            ChangeStep::ConfirmNew => {
-                                confirm_password.pop();
-                            }
-                        },
-                        KeyCode::Char(c) => match step {
-                            ChangeStep::EnterOld => master_input.push(c),
-                            ChangeStep::EnterNew => new_password.push(c),
-                            ChangeStep::ConfirmNew => confirm_password.push(c),
-                        },
                         _ => {}
                     }
                 }
-                Phase::ViewPasswords { mode } => {
-                    if let Some(state) = &mut viewer_state {
-                        match mode {
-                            ViewMode::Browse => {
-                                match key.code {
-                                    KeyCode::Esc | KeyCode::Char('q') => {
-                                        phase = Phase::Main;
-                                        viewer_state = None;
-                                    }
-                                    KeyCode::Up | KeyCode::Char('k') => {
-                                        if state.selected > 0 {
-                                            state.selected -= 1;
-                                        }
-                                        state.status_message = None;
-                                    }
-                                    KeyCode::Down | KeyCode::Char('j') => {
-                                        if state.selected + 1 < state.entries.len() {
-                                            state.selected += 1;
-                                        }
-                                        state.status_message = None;
-                                    }
-                                    KeyCode::Enter | KeyCode::Char(' ') => {
-                                        // Toggle reveal for selected entry
-                                        if state.revealed.contains(&state.selected) {
-                                            state.revealed.remove(&state.selected);
-                                        } else {
-                                            state.revealed.insert(state.selected);
-                                        }
-                                    }
-                                    KeyCode::Char('r') => {
-                                        // Reveal all
-                                        for i in 0..state.entries.len() {
-                                            state.revealed.insert(i);
-                                        }
-                                    }
-                                    KeyCode::Char('H') => {
-                                        // Hide all (shifted to avoid conflict with vim left)
-                                        state.revealed.clear();
-                                    }
-                                    KeyCode::Char('y') => {
-                                        // Copy password to clipboard
-                                        if !state.entries.is_empty() {
-                                            if let Ok(mut clipboard) = Clipboard::new() {
-                                                let pwd = &state.entries[state.selected].password;
-                                                if clipboard.set_text(pwd.clone()).is_ok() {
-                                                    state.status_message =
-                                                        Some("✓ Copied to clipboard!".into());
-                                                } else {
-                                                    state.status_message =
-                                                        Some("✗ Failed to copy".into());
-                                                }
-                                            } else {
-                                                state.status_message =
-                                                    Some("✗ Clipboard unavailable".into());
-                                            }
-                                        }
-                                    }
-                                    KeyCode::Char('d') => {
-                                        // Confirm delete
-                                        if !state.entries.is_empty() {
-                                            *mode = ViewMode::ConfirmDelete;
-                                        }
-                                    }
-                                    KeyCode::Char('e') => {
-                                        // Start editing name
-                                        if !state.entries.is_empty() {
-                                            state.edit_buffer =
-                                                state.entries[state.selected].name.clone();
-                                            *mode = ViewMode::EditName;
-                                        }
-                                    }
-                                    KeyCode::Char('p') => {
-                                        // Start editing password
-                                        if !state.entries.is_empty() {
-                                            state.edit_buffer =
-                                                state.entries[state.selected].password.clone();
-                                            state.revealed.insert(state.selected);
-                                            *mode = ViewMode::EditPassword;
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            ViewMode::ConfirmDelete => {
-                                match key.code {
-                                    KeyCode::Char('y') | KeyCode::Enter => {
-                                        // Confirm delete
-                                        if let Some(ref store) = storage {
-                                            match store.delete(state.selected) {
-                                                Ok(_) => {
-                                                    state.entries.remove(state.selected);
-                                                    if state.selected >= state.entries.len()
-                                                        && state.selected > 0
-                                                    {
-                                                        state.selected -= 1;
-                                                    }
-                                                    state.revealed.clear();
-                                                    state.status_message =
-                                                        Some("✓ Deleted!".into());
-                                                }
-                                                Err(e) => {
-                                                    state.status_message = Some(format!("✗ {}", e));
-                                                }
-                                            }
-                                        }
-                                        *mode = ViewMode::Browse;
-                                    }
-                                    KeyCode::Char('n') | KeyCode::Esc => {
-                                        // Cancel delete
-                                        *mode = ViewMode::Browse;
-                                        state.status_message = None;
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            ViewMode::EditName => {
-                                match key.code {
-                                    KeyCode::Esc => {
-                                        *mode = ViewMode::Browse;
-                                        state.edit_buffer.clear();
-                                        state.status_message = None;
-                                    }
-                                    KeyCode::Enter => {
-                                        // Save name change
-                                        if !state.edit_buffer.trim().is_empty() {
-                                            if let Some(ref store) = storage {
-                                                let mut entry =
-                                                    state.entries[state.selected].clone();
-                                                entry.name = state.edit_buffer.clone();
-                                                match store.update(state.selected, entry.clone()) {
-                                                    Ok(_) => {
-                                                        state.entries[state.selected] = entry;
-                                                        state.status_message =
-                                                            Some("✓ Name updated!".into());
-                                                    }
-                                                    Err(e) => {
-                                                        state.status_message =
-                                                            Some(format!("✗ {}", e));
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        state.edit_buffer.clear();
-                                        *mode = ViewMode::Browse;
-                                    }
-                                    KeyCode::Backspace => {
-                                        state.edit_buffer.pop();
-                                    }
-                                    KeyCode::Char(c) => {
-                                        state.edit_buffer.push(c);
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            ViewMode::EditPassword => {
-                                match key.code {
-                                    KeyCode::Esc => {
-                                        *mode = ViewMode::Browse;
-                                        state.edit_buffer.clear();
-                                        state.status_message = None;
-                                    }
-                                    KeyCode::Enter => {
-                                        // Save password change
-                                        if !state.edit_buffer.is_empty() {
-                                            if let Some(ref store) = storage {
-                                                let mut entry =
-                                                    state.entries[state.selected].clone();
-                                                entry.password = state.edit_buffer.clone();
-                                                match store.update(state.selected, entry.clone()) {
-                                                    Ok(_) => {
-                                                        state.entries[state.selected] = entry;
-                                                        state.status_message =
-                                                            Some("✓ Password updated!".into());
-                                                    }
-                                                    Err(e) => {
-                                                        state.status_message =
-                                                            Some(format!("✗ {}", e));
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        state.edit_buffer.clear();
-                                        *mode = ViewMode::Browse;
-                                    }
-                                    KeyCode::Backspace => {
-                                        state.edit_buffer.pop();
-                                    }
-                                    KeyCode::Char(c) => {
-                                        state.edit_buffer.push(c);
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                    }
-                }
             }
         }
     }
+    if let Some(confirm) = &session.confirm {
+        ui::render_confirm(f, &confirm.prompt);
+    }
 }
@@ -1,34 +1,622 @@
-use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::config::{Config, DirProfile, Preset};
+use super::generator::{
+    ClassWeights, GenMode, GenOptions, LONG_PASSWORD_WARNING, MAX_LENGTH, generate_password,
+    generate_with_rng, min_length_for_bits, strength_label,
+};
+use super::logging::redact;
+use super::storage::{PasswordEntry, SortMode, reused_passwords, sanitize_name};
+use super::wordlist;
 
 /// Viewer modes for password list
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ViewMode {
     Browse,
-    ConfirmDelete,
     EditName,
     EditPassword,
+    EditUrl,
+    ConfirmQr,
+    ShowQr,
+    Search,
+    /// Stepping through the selected password one character at a time
+    RevealChar,
+    /// Every field of the selected entry, with per-field copy keys
+    Detail,
+    /// Asking before the online breach check contacts the API
+    ConfirmNetwork,
+    /// Popup with the result of the online breach check
+    BreachReport,
+    /// The new name is already used by another entry
+    ConfirmRename,
+    /// Form editing every text field of the selected entry at once
+    EditEntry,
+    /// Typing the path of the vault to move the selected entry to
+    TransferPath,
+    /// Typing the master password of that vault
+    TransferPassword,
+    /// Multi-line editor for the selected entry's notes
+    EditNotes,
+}
+
+/// Multi-line text being edited, with a (line, column) cursor in characters
+pub struct NotesEditor {
+    pub lines: Vec<String>,
+    pub cursor: (usize, usize),
+}
+
+impl NotesEditor {
+    /// Start editing `text` with the cursor at its end
+    pub fn new(text: &str) -> Self {
+        let lines: Vec<String> = text.split('\n').map(String::from).collect();
+        let last = lines.len() - 1;
+        let cursor = (last, lines[last].chars().count());
+        Self { lines, cursor }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Byte offset of the cursor within its line
+    fn byte_at_cursor(&self) -> usize {
+        let (row, col) = self.cursor;
+        self.lines[row]
+            .char_indices()
+            .nth(col)
+            .map_or(self.lines[row].len(), |(i, _)| i)
+    }
+
+    /// Insert a character at the cursor; `'\n'` splits the line
+    pub fn insert(&mut self, c: char) {
+        let at = self.byte_at_cursor();
+        let (row, col) = self.cursor;
+        if c == '\n' {
+            let rest = self.lines[row].split_off(at);
+            self.lines.insert(row + 1, rest);
+            self.cursor = (row + 1, 0);
+        } else {
+            self.lines[row].insert(at, c);
+            self.cursor = (row, col + 1);
+        }
+    }
+
+    /// Delete the character before the cursor, joining lines at the start of one
+    pub fn backspace(&mut self) {
+        let (row, col) = self.cursor;
+        if col > 0 {
+            self.cursor.1 -= 1;
+            let at = self.byte_at_cursor();
+            self.lines[row].remove(at);
+        } else if row > 0 {
+            let line = self.lines.remove(row);
+            let prev = &mut self.lines[row - 1];
+            self.cursor = (row - 1, prev.chars().count());
+            prev.push_str(&line);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        let (row, col) = self.cursor;
+        if col > 0 {
+            self.cursor.1 -= 1;
+        } else if row > 0 {
+            self.cursor = (row - 1, self.lines[row - 1].chars().count());
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        let (row, col) = self.cursor;
+        if col < self.lines[row].chars().count() {
+            self.cursor.1 += 1;
+        } else if row + 1 < self.lines.len() {
+            self.cursor = (row + 1, 0);
+        }
+    }
+
+    /// Move up or down a line, keeping the column where the line is long enough
+    pub fn move_vertical(&mut self, down: bool) {
+        let (row, col) = self.cursor;
+        let target = if down {
+            (row + 1).min(self.lines.len() - 1)
+        } else {
+            row.saturating_sub(1)
+        };
+        self.cursor = (target, col.min(self.lines[target].chars().count()));
+    }
+}
+
+/// Fields of the all-fields edit form, in Tab order
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EntryField {
+    Name,
+    Username,
+    Url,
+    Password,
+    Notes,
+    Expires,
+}
+
+impl EntryField {
+    pub const ALL: [EntryField; 6] = [
+        Self::Name,
+        Self::Username,
+        Self::Url,
+        Self::Password,
+        Self::Notes,
+        Self::Expires,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Username => "Username",
+            Self::Url => "URL",
+            Self::Password => "Password",
+            Self::Notes => "Notes",
+            Self::Expires => "Expires",
+        }
+    }
+
+    /// Move to the next field, wrapping around
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&f| f == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// Move to the previous field, wrapping around
+    pub fn prev(self) -> Self {
+        let i = Self::ALL.iter().position(|&f| f == self).unwrap_or(0);
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// Number of generated passwords kept in the session history
+pub const HISTORY_LEN: usize = 10;
+
+/// Number of recent errors kept for the error log
+pub const ERROR_LOG_LEN: usize = 50;
+
+/// How long a timed reveal keeps a password visible
+pub const REVEAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a quick delete can still be undone
+pub const UNDO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a status message stays on screen
+pub const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A status line that clears itself after `STATUS_TIMEOUT`
+#[derive(Default)]
+pub struct TimedStatus(Option<(String, Instant)>);
+
+impl TimedStatus {
+    /// Show `msg` from now on
+    pub fn set(&mut self, msg: String) {
+        self.0 = Some((msg, Instant::now()));
+    }
+
+    /// The message, if one is showing
+    pub fn get(&self) -> Option<&str> {
+        self.0.as_ref().map(|(msg, _)| msg.as_str())
+    }
+
+    /// Remove the message right away
+    pub fn clear(&mut self) {
+        self.0 = None;
+    }
+
+    /// Clear the message once it has been shown for `STATUS_TIMEOUT`
+    pub fn expire(&mut self) {
+        if self
+            .0
+            .as_ref()
+            .is_some_and(|(_, at)| at.elapsed() >= STATUS_TIMEOUT)
+        {
+            self.0 = None;
+        }
+    }
+}
+
+/// State for the password viewer
+pub struct ViewerState {
+    pub entries: Vec<PasswordEntry>,
+    pub selected: usize,
+    pub revealed: HashSet<usize>,
+    /// Entries revealed only until the stored deadline
+    pub timed_reveals: HashMap<usize, Instant>,
+    /// Position shown by the one-character reveal, per entry
+    pub char_cursor: HashMap<usize, usize>,
+    pub status_message: TimedStatus,
+    pub edit_buffer: String,
+    /// Show the password edit buffer as dots while typing
+    pub mask_edit: bool,
+    /// Copy of the selected entry being changed in the all-fields form
+    pub draft: Option<PasswordEntry>,
+    /// Field of `draft` receiving input
+    pub draft_field: EntryField,
+    /// Expiry date as typed in the form, checked when the draft is saved
+    pub draft_expiry: String,
+    /// Notes of the selected entry while the multi-line editor is open
+    pub notes: Option<NotesEditor>,
+    /// Outcome of the last online breach check: the count, or why it failed
+    pub breach_report: Option<Result<u64, String>>,
+    /// Quick-deleted entry not yet removed from the vault: its index, the
+    /// entry itself and when the undo window closes
+    pub pending_delete: Option<(usize, PasswordEntry, Instant)>,
+    /// Entries picked for a batch delete or export, by index into `entries`
+    pub marked: HashSet<usize>,
+    /// Case-insensitive filter over all text fields
+    pub search_query: String,
+    /// Indices into `entries` that match `search_query`, in display order
+    pub filtered_indices: Vec<usize>,
+    /// Display order of the list; the vault itself keeps its order
+    pub sort: SortMode,
+    /// Reverse `sort`
+    pub sort_desc: bool,
+}
+
+impl ViewerState {
+    pub fn new(entries: Vec<PasswordEntry>, selected: usize) -> Self {
+        let mut state = Self {
+            entries,
+            selected,
+            revealed: HashSet::new(),
+            timed_reveals: HashMap::new(),
+            char_cursor: HashMap::new(),
+            status_message: TimedStatus::default(),
+            edit_buffer: String::new(),
+            mask_edit: false,
+            draft: None,
+            draft_field: EntryField::Name,
+            draft_expiry: String::new(),
+            notes: None,
+            breach_report: None,
+            pending_delete: None,
+            marked: HashSet::new(),
+            search_query: String::new(),
+            filtered_indices: Vec::new(),
+            sort: SortMode::Stored,
+            sort_desc: false,
+        };
+        state.refresh_filter();
+        state
+    }
+
+    /// Recompute the visible entries, keeping the selection on a visible one
+    pub fn refresh_filter(&mut self) {
+        let query = self.search_query.to_lowercase();
+        self.filtered_indices = (0..self.entries.len())
+            .filter(|&i| query.is_empty() || self.entries[i].matched_field(&query).is_some())
+            .collect();
+        let entries = &self.entries;
+        self.filtered_indices.sort_by(|&a, &b| {
+            let order = self.sort.compare(&entries[a], &entries[b]);
+            if self.sort_desc {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        if !self.filtered_indices.contains(&self.selected) {
+            self.selected = self
+                .filtered_indices
+                .iter()
+                .find(|&&i| i >= self.selected)
+                .or(self.filtered_indices.last())
+                .copied()
+                .unwrap_or(0);
+        }
+    }
+
+    /// Step through stored order, name ascending/descending, then date
+    /// ascending/descending
+    pub fn cycle_sort(&mut self) {
+        (self.sort, self.sort_desc) = match (self.sort, self.sort_desc) {
+            (SortMode::Stored, _) => (SortMode::Name, false),
+            (SortMode::Name, false) => (SortMode::Name, true),
+            (SortMode::Name, true) => (SortMode::Created, false),
+            (SortMode::Created, false) => (SortMode::Created, true),
+            (SortMode::Created, true) => (SortMode::Stored, false),
+        };
+        self.refresh_filter();
+    }
+
+    /// Insert `entry` at `index` and select it, keeping reveals on the entries they belong to
+    pub fn insert_entry(&mut self, index: usize, entry: PasswordEntry) {
+        let shift = |i: usize| if i >= index { i + 1 } else { i };
+        self.revealed = self.revealed.iter().map(|&i| shift(i)).collect();
+        self.marked = self.marked.iter().map(|&i| shift(i)).collect();
+        self.timed_reveals = self
+            .timed_reveals
+            .iter()
+            .map(|(&i, &until)| (shift(i), until))
+            .collect();
+        self.char_cursor = self
+            .char_cursor
+            .iter()
+            .map(|(&i, &pos)| (shift(i), pos))
+            .collect();
+        self.entries.insert(index, entry);
+        self.selected = index;
+        self.refresh_filter();
+    }
+
+    /// Drop the selected entry after it left the vault, selecting a neighbour
+    pub fn remove_selected(&mut self) {
+        self.entries.remove(self.selected);
+        if self.selected >= self.entries.len() && self.selected > 0 {
+            self.selected -= 1;
+        }
+        self.revealed.clear();
+        self.timed_reveals.clear();
+        self.char_cursor.clear();
+        self.marked.clear();
+        self.refresh_filter();
+    }
+
+    /// Drop every marked entry after they left the vault
+    pub fn remove_marked(&mut self) {
+        let mut doomed: Vec<usize> = self.marked.drain().collect();
+        doomed.sort_unstable();
+        // Highest first so the remaining indices stay valid
+        for &index in doomed.iter().rev() {
+            self.entries.remove(index);
+        }
+        self.selected -= doomed.iter().filter(|&&i| i < self.selected).count();
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        self.revealed.clear();
+        self.timed_reveals.clear();
+        self.char_cursor.clear();
+        self.refresh_filter();
+    }
+
+    /// Add the selected entry to the batch selection, or take it out again
+    pub fn toggle_mark(&mut self) {
+        if !self.marked.remove(&self.selected) {
+            self.marked.insert(self.selected);
+        }
+    }
+
+    /// Marked entries in vault order
+    pub fn marked_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self.marked.iter().copied().collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    /// Show or mask the selected entry until toggled again
+    pub fn toggle_reveal(&mut self) {
+        if !self.revealed.remove(&self.selected) {
+            self.revealed.insert(self.selected);
+        }
+    }
+
+    /// Reveal every entry that passes the current filter
+    pub fn reveal_visible(&mut self) {
+        self.revealed.extend(self.filtered_indices.iter().copied());
+    }
+
+    /// Mask every entry that passes the current filter, including timed reveals
+    pub fn hide_visible(&mut self) {
+        for index in &self.filtered_indices {
+            self.revealed.remove(index);
+            self.timed_reveals.remove(index);
+        }
+    }
+
+    /// Reveal the selected entry for `REVEAL_TIMEOUT`
+    pub fn reveal_temporarily(&mut self) {
+        self.timed_reveals
+            .insert(self.selected, Instant::now() + REVEAL_TIMEOUT);
+    }
+
+    /// Move the selected entry's one-character reveal by `delta`, staying in bounds
+    pub fn step_char(&mut self, delta: isize) {
+        let len = self.entries[self.selected].password.chars().count();
+        let cursor = self.char_cursor.entry(self.selected).or_default();
+        *cursor = cursor
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+
+    /// Password at `index` masked except for the character under its reveal cursor,
+    /// with the 1-based position
+    pub fn char_reveal(&self, index: usize) -> (String, usize) {
+        let len = self.entries[index].password.chars().count();
+        let cursor = self
+            .char_cursor
+            .get(&index)
+            .copied()
+            .unwrap_or(0)
+            .min(len.saturating_sub(1));
+        let masked = self.entries[index]
+            .password
+            .chars()
+            .enumerate()
+            .map(|(i, c)| if i == cursor { c } else { '•' })
+            .collect();
+        (masked, cursor + 1)
+    }
+
+    /// Open the all-fields form on a copy of the selected entry
+    pub fn start_draft(&mut self) {
+        let entry = self.entries[self.selected].clone();
+        self.draft_expiry = entry.expires_at.clone().unwrap_or_default();
+        self.draft = Some(entry);
+        self.draft_field = EntryField::Name;
+    }
+
+    /// Text of the form field receiving input
+    pub fn draft_input(&mut self) -> Option<&mut String> {
+        let draft = self.draft.as_mut()?;
+        Some(match self.draft_field {
+            EntryField::Name => &mut draft.name,
+            EntryField::Username => &mut draft.username,
+            EntryField::Url => &mut draft.url,
+            EntryField::Password => &mut draft.password,
+            EntryField::Notes => &mut draft.notes,
+            EntryField::Expires => &mut self.draft_expiry,
+        })
+    }
+
+    /// Whether the entry at `index` is currently shown in clear
+    pub fn is_revealed(&self, index: usize) -> bool {
+        self.revealed.contains(&index) || self.timed_reveals.contains_key(&index)
+    }
+
+    /// Whole seconds left on a timed reveal, rounded up
+    pub fn reveal_remaining(&self, index: usize) -> Option<u64> {
+        self.timed_reveals.get(&index).map(|deadline| {
+            let left = deadline.saturating_duration_since(Instant::now());
+            left.as_secs() + u64::from(left.subsec_nanos() > 0)
+        })
+    }
+
+    /// Take an entry out of the list but keep it for `UNDO_TIMEOUT` in case
+    /// the delete is undone
+    pub fn stage_delete(&mut self) {
+        let index = self.selected;
+        let entry = self.entries[index].clone();
+        self.remove_selected();
+        self.pending_delete = Some((index, entry, Instant::now() + UNDO_TIMEOUT));
+    }
+
+    /// Put a staged delete back where it was
+    pub fn undo_delete(&mut self) -> bool {
+        match self.pending_delete.take() {
+            Some((index, entry, _)) => {
+                self.insert_entry(index, entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Name and whole seconds left of a staged delete
+    pub fn undo_remaining(&self) -> Option<(&str, u64)> {
+        self.pending_delete.as_ref().map(|(_, entry, deadline)| {
+            let left = deadline.saturating_duration_since(Instant::now());
+            (
+                entry.name.as_str(),
+                left.as_secs() + u64::from(left.subsec_nanos() > 0),
+            )
+        })
+    }
+
+    /// Re-mask passwords whose timed reveal has run out
+    pub fn expire_reveals(&mut self) {
+        let now = Instant::now();
+        self.timed_reveals.retain(|_, deadline| *deadline > now);
+    }
+
+    /// Whether `selected` points at a visible entry
+    pub fn has_selection(&self) -> bool {
+        self.filtered_indices.contains(&self.selected)
+    }
+
+    /// Move the selection to the previous visible entry, from the top to the
+    /// bottom when `wrap` is set
+    pub fn select_prev(&mut self, wrap: bool) {
+        if let Some(pos) = self
+            .filtered_indices
+            .iter()
+            .position(|&i| i == self.selected)
+        {
+            if pos > 0 {
+                self.selected = self.filtered_indices[pos - 1];
+            } else if wrap {
+                self.selected = self.filtered_indices[self.filtered_indices.len() - 1];
+            }
+        }
+    }
+
+    /// Move the selection to the next visible entry, from the bottom to the
+    /// top when `wrap` is set
+    pub fn select_next(&mut self, wrap: bool) {
+        if let Some(pos) = self
+            .filtered_indices
+            .iter()
+            .position(|&i| i == self.selected)
+        {
+            if pos + 1 < self.filtered_indices.len() {
+                self.selected = self.filtered_indices[pos + 1];
+            } else if wrap {
+                self.selected = self.filtered_indices[0];
+            }
+        }
+    }
+
+    /// Show a status message that clears itself after `STATUS_TIMEOUT`
+    pub fn set_status(&mut self, msg: String) {
+        self.status_message.set(msg);
+    }
+
+    /// Current status message, if any
+    pub fn status(&self) -> Option<&str> {
+        self.status_message.get()
+    }
+
+    /// Drop the status message once it has been shown long enough
+    pub fn expire_status(&mut self) {
+        self.status_message.expire();
+    }
 }
 
 /// Available input fields
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum InputField {
     Name,
+    Anonymous,
     Length,
+    Mode,
     ToggleSpecial,
     ToggleLetters,
     ToggleNumbers,
+    ToggleExtended,
+    MustMatch,
+    TrailingDigit,
+    TrailingSpecial,
     Generate,
 }
 
 impl InputField {
+    /// Field reached with number key `n` on the main screen
+    pub fn from_number(n: u32) -> Option<Self> {
+        match n {
+            1 => Some(Self::Name),
+            2 => Some(Self::Length),
+            3 => Some(Self::ToggleSpecial),
+            4 => Some(Self::ToggleLetters),
+            5 => Some(Self::ToggleNumbers),
+            6 => Some(Self::Generate),
+            _ => None,
+        }
+    }
+
+    /// Typing goes into this field, so plain digits belong to it
+    pub fn is_text(self) -> bool {
+        matches!(self, Self::Name | Self::Length | Self::MustMatch)
+    }
+
     /// Move to the next field
     pub fn next(self) -> Self {
         match self {
-            Self::Name => Self::Length,
-            Self::Length => Self::ToggleSpecial,
+            Self::Name => Self::Anonymous,
+            Self::Anonymous => Self::Length,
+            Self::Length => Self::Mode,
+            Self::Mode => Self::ToggleSpecial,
             Self::ToggleSpecial => Self::ToggleLetters,
             Self::ToggleLetters => Self::ToggleNumbers,
-            Self::ToggleNumbers => Self::Generate,
+            Self::ToggleNumbers => Self::ToggleExtended,
+            Self::ToggleExtended => Self::MustMatch,
+            Self::MustMatch => Self::TrailingDigit,
+            Self::TrailingDigit => Self::TrailingSpecial,
+            Self::TrailingSpecial => Self::Generate,
             Self::Generate => Self::Name,
         }
     }
@@ -37,11 +625,17 @@ impl InputField {
     pub fn prev(self) -> Self {
         match self {
             Self::Name => Self::Generate,
-            Self::Length => Self::Name,
-            Self::ToggleSpecial => Self::Length,
+            Self::Anonymous => Self::Name,
+            Self::Length => Self::Anonymous,
+            Self::Mode => Self::Length,
+            Self::ToggleSpecial => Self::Mode,
             Self::ToggleLetters => Self::ToggleSpecial,
             Self::ToggleNumbers => Self::ToggleLetters,
-            Self::Generate => Self::ToggleNumbers,
+            Self::ToggleExtended => Self::ToggleNumbers,
+            Self::MustMatch => Self::ToggleExtended,
+            Self::TrailingDigit => Self::MustMatch,
+            Self::TrailingSpecial => Self::TrailingDigit,
+            Self::Generate => Self::TrailingSpecial,
         }
     }
 }
@@ -49,93 +643,352 @@ impl InputField {
 /// Main application state
 pub struct App {
     pub name_input: String,
+    /// Allow saving without a name, under an `entry-<timestamp>` placeholder
+    pub anonymous: bool,
     pub length_input: String,
+    /// Pattern for template mode, edited in place of the length
+    pub template_input: String,
+    /// Character password or word passphrase
+    pub mode: GenMode,
     pub use_special: bool,
     pub use_letters: bool,
     pub use_numbers: bool,
+    /// Also draw from non-ASCII symbols
+    pub use_extended: bool,
+    /// Optional regex the generated password has to match
+    pub must_match_input: String,
+    /// Force the password to end with a digit
+    pub trailing_digit: bool,
+    /// Force the password to end with a special character
+    pub trailing_special: bool,
+    /// Per-class odds from the config; uniform over characters when unset
+    pub class_weights: Option<ClassWeights>,
     pub active_field: InputField,
     pub generated_password: Option<String>,
+    /// Settings `generated_password` came from, saved with the entry
+    pub generated_with: Option<GenOptions>,
     pub error: Option<String>,
-    pub status_message: Option<String>,
+    pub status_message: TimedStatus,
+    /// Viewing only: saving and editing are disabled
+    pub read_only: bool,
+    /// The vault file is readable by other users
+    pub loose_permissions: bool,
+    /// Vault changes are held in memory and not yet written
+    pub dirty: bool,
+    /// Generating never saves; Ctrl+S stores the shown password
+    pub explicit_save: bool,
+    /// The shown password has not been stored yet (explicit-save mode)
+    pub pending_save: bool,
+    /// Generate from a seed (`--insecure-seeded`): reproducible, so NOT secure
+    pub seeded: bool,
+    /// Seed entered by the user; each password gets a random one when unset
+    pub seed: Option<u64>,
+    /// Seed the shown password was generated from, in seeded mode
+    pub last_seed: Option<u64>,
+    /// Passwords generated this session, newest first (never persisted)
+    pub recent: VecDeque<String>,
+    pub show_history: bool,
+    pub history_selected: usize,
+    /// Preset the generator settings were last loaded from or saved as
+    pub active_preset: Option<String>,
+    /// Recent errors, newest first, so they can be read after they're replaced
+    pub error_log: VecDeque<(SystemTime, String)>,
+    pub show_error_log: bool,
+    pub error_log_scroll: usize,
 }
 
 impl App {
     pub fn new() -> Self {
         Self {
             name_input: String::new(),
+            anonymous: false,
             length_input: String::from("16"),
+            template_input: String::new(),
+            mode: GenMode::Password,
             use_special: true,
             use_letters: true,
             use_numbers: true,
+            use_extended: false,
+            must_match_input: String::new(),
+            trailing_digit: false,
+            trailing_special: false,
+            class_weights: None,
             active_field: InputField::Name,
             generated_password: None,
+            generated_with: None,
             error: None,
-            status_message: None,
+            status_message: TimedStatus::default(),
+            read_only: false,
+            loose_permissions: false,
+            dirty: false,
+            explicit_save: false,
+            pending_save: false,
+            seeded: false,
+            seed: None,
+            last_seed: None,
+            recent: VecDeque::new(),
+            show_history: false,
+            history_selected: 0,
+            active_preset: None,
+            error_log: VecDeque::new(),
+            show_error_log: false,
+            error_log_scroll: 0,
         }
     }
 
+    /// Fresh state with the generator and saving behaviour the config asks for
+    pub fn from_config(config: &Config) -> Self {
+        let mut app = Self::new();
+        app.read_only = config.read_only;
+        app.explicit_save = config.explicit_save;
+        app.class_weights = config.class_weights;
+        app
+    }
+
     /// Generate a password based on current settings
     pub fn generate(&mut self) {
         self.error = None;
-        self.status_message = None;
+        self.status_message.clear();
         self.generated_password = None;
+        self.pending_save = false;
 
         // Validate name
-        if self.name_input.trim().is_empty() {
-            self.error = Some("Please enter a password name".into());
+        if sanitize_name(&self.name_input).is_empty() && !self.anonymous {
+            self.set_error("Please enter a password name".into());
             return;
         }
 
-        // Validate length
-        let length: usize = match self.length_input.parse() {
-            Ok(n) if n > 0 && n <= 128 => n,
-            Ok(_) => {
-                self.error = Some("Length must be 1-128".into());
-                return;
+        let seed = self.seeded.then(|| self.seed.unwrap_or_else(rand::random));
+        self.last_seed = seed;
+        let generated = self.gen_options().and_then(|opts| {
+            let password = match seed {
+                Some(seed) => generate_with_rng(&opts, &mut StdRng::seed_from_u64(seed)),
+                None => generate_password(&opts),
+            };
+            password.map(|pwd| (pwd, opts))
+        });
+        match generated {
+            Ok((password, opts)) => {
+                self.recent.push_front(password.clone());
+                self.recent.truncate(HISTORY_LEN);
+                self.generated_password = Some(password);
+                self.generated_with = Some(opts);
             }
-            Err(_) => {
-                self.error = Some("Invalid length".into());
-                return;
+            Err(e) => self.set_error(e),
+        }
+    }
+
+    /// Open or close the session history panel
+    pub fn toggle_history(&mut self) {
+        self.show_history = !self.show_history;
+        self.history_selected = 0;
+    }
+
+    /// Move the history selection up
+    pub fn history_prev(&mut self) {
+        self.history_selected = self.history_selected.saturating_sub(1);
+    }
+
+    /// Move the history selection down
+    pub fn history_next(&mut self) {
+        if self.history_selected + 1 < self.recent.len() {
+            self.history_selected += 1;
+        }
+    }
+
+    /// Generation settings from the form, validating the length field
+    pub fn gen_options(&self) -> Result<GenOptions, String> {
+        if self.mode == GenMode::Template {
+            if self.template_input.is_empty() {
+                return Err("Enter a template".into());
             }
+            // The length is unused; keep whatever the field held for other modes
+            return Ok(self.options_with_length(self.length_input.parse().unwrap_or(0)));
+        }
+        let length: usize = match self.length_input.parse() {
+            Ok(n) if n > 0 && n <= MAX_LENGTH => n,
+            Ok(_) => return Err(format!("Length must be 1-{}", MAX_LENGTH)),
+            Err(_) => return Err("Invalid length".into()),
         };
+        Ok(self.options_with_length(length))
+    }
 
-        // Build character set
-        let mut charset = String::new();
+    /// Set the length to the shortest one reaching `target_bits` with the
+    /// current settings, returning it
+    pub fn suggest_length(&mut self, target_bits: f64) -> Result<usize, String> {
+        if target_bits.is_nan() || target_bits <= 0.0 {
+            return Err("Target must be a positive number of bits".into());
+        }
+        let mut opts = self.options_with_length(1);
+        let symbols = match self.mode {
+            GenMode::Password => opts.charset_size(),
+            GenMode::Pin => 10,
+            // Random capitalization doubles the choices per word
+            GenMode::Passphrase => 2 * wordlist::word_count(),
+            GenMode::Template => return Err("A template sets its own length".into()),
+        };
+        if symbols == 0 {
+            return Err("Enable at least one character type".into());
+        }
+        opts.length = min_length_for_bits(target_bits, symbols).max(1);
+        // Pinned trailing characters draw from smaller classes; top up for them
+        while opts.entropy_bits() < target_bits && opts.length <= MAX_LENGTH {
+            opts.length += 1;
+        }
+        if opts.length > MAX_LENGTH {
+            return Err(format!(
+                "{:.0} bits needs more than {} characters",
+                target_bits, MAX_LENGTH
+            ));
+        }
+        self.length_input = opts.length.to_string();
+        Ok(opts.length)
+    }
 
-        if self.use_letters {
-            charset.push_str("abcdefghijklmnopqrstuvwxyz");
-            charset.push_str("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+    fn options_with_length(&self, length: usize) -> GenOptions {
+        GenOptions {
+            mode: self.mode,
+            length,
+            use_special: self.use_special,
+            use_letters: self.use_letters,
+            use_numbers: self.use_numbers,
+            use_extended: self.use_extended,
+            min_per_class: 0,
+            must_match: (!self.must_match_input.is_empty()).then(|| self.must_match_input.clone()),
+            require_trailing_digit: self.trailing_digit,
+            require_trailing_special: self.trailing_special,
+            class_weights: self.class_weights,
+            template: (self.mode == GenMode::Template).then(|| self.template_input.clone()),
         }
+    }
+
+    /// Password mode with every character class switched off, so Generate would fail
+    pub fn charset_empty(&self) -> bool {
+        self.gen_options()
+            .is_ok_and(|opts| opts.mode == GenMode::Password && opts.charset_size() == 0)
+    }
 
-        if self.use_numbers {
-            charset.push_str("0123456789");
+    /// Load generator settings from a preset
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        self.length_input = preset.length.to_string();
+        self.mode = preset.mode;
+        self.use_special = preset.use_special;
+        self.use_letters = preset.use_letters;
+        self.use_numbers = preset.use_numbers;
+        self.use_extended = preset.use_extended;
+        self.must_match_input = preset.must_match.clone().unwrap_or_default();
+        self.trailing_digit = preset.require_trailing_digit;
+        self.trailing_special = preset.require_trailing_special;
+        if let Some(ref template) = preset.template {
+            self.template_input = template.clone();
         }
+        self.active_preset = Some(preset.name.clone());
+        self.error = None;
+    }
 
-        if self.use_special {
-            charset.push_str("!@#$%^&*()_+-=[]{}|;:,.<>?");
+    /// Override the generator defaults with whatever a directory profile sets
+    pub fn apply_profile(&mut self, profile: &DirProfile) {
+        if let Some(length) = profile.length {
+            self.length_input = length.to_string();
+        }
+        if let Some(mode) = profile.mode {
+            self.mode = mode;
         }
+        let toggles = [
+            (profile.use_special, &mut self.use_special),
+            (profile.use_letters, &mut self.use_letters),
+            (profile.use_numbers, &mut self.use_numbers),
+            (profile.use_extended, &mut self.use_extended),
+            (profile.require_trailing_digit, &mut self.trailing_digit),
+            (profile.require_trailing_special, &mut self.trailing_special),
+        ];
+        for (value, field) in toggles {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        if let Some(ref pattern) = profile.must_match {
+            self.must_match_input = pattern.clone();
+        }
+    }
 
-        if charset.is_empty() {
-            self.error = Some("Enable at least one character type".into());
-            return;
+    /// Capture the current generator settings as a preset called `name`
+    pub fn current_preset(&self, name: &str) -> Result<Preset, String> {
+        let opts = self.gen_options()?;
+        Ok(Preset {
+            name: name.to_string(),
+            length: opts.length,
+            mode: opts.mode,
+            use_special: opts.use_special,
+            use_letters: opts.use_letters,
+            use_numbers: opts.use_numbers,
+            use_extended: opts.use_extended,
+            must_match: opts.must_match,
+            require_trailing_digit: opts.require_trailing_digit,
+            require_trailing_special: opts.require_trailing_special,
+            template: opts.template,
+        })
+    }
+
+    /// Show an error in the result box and record it in the error log
+    pub fn set_error(&mut self, msg: String) {
+        let msg = redact(&msg);
+        self.log_error(msg.clone());
+        self.error = Some(msg);
+    }
+
+    /// Record an error shown somewhere other than the result box
+    pub fn log_error(&mut self, msg: String) {
+        let msg = redact(&msg);
+        log::warn!("{}", msg);
+        self.error_log.push_front((SystemTime::now(), msg));
+        self.error_log.truncate(ERROR_LOG_LEN);
+    }
+
+    /// Open or close the error log popup
+    pub fn toggle_error_log(&mut self) {
+        self.show_error_log = !self.show_error_log;
+        self.error_log_scroll = 0;
+    }
+
+    /// Scroll the error log towards newer entries
+    pub fn error_log_prev(&mut self) {
+        self.error_log_scroll = self.error_log_scroll.saturating_sub(1);
+    }
+
+    /// Scroll the error log towards older entries
+    pub fn error_log_next(&mut self) {
+        if self.error_log_scroll + 1 < self.error_log.len() {
+            self.error_log_scroll += 1;
         }
+    }
 
-        // Generate password
-        let mut rng = rand::rng();
-        let chars: Vec<char> = charset.chars().collect();
-        let password: String = (0..length)
-            .map(|_| chars[rng.random_range(0..chars.len())])
-            .collect();
+    /// Show a status message that clears itself after `STATUS_TIMEOUT`
+    pub fn set_status(&mut self, msg: String) {
+        self.status_message.set(msg);
+    }
+
+    /// Current status message, if any
+    pub fn status(&self) -> Option<&str> {
+        self.status_message.get()
+    }
 
-        self.generated_password = Some(password);
+    /// Drop the status message once it has been shown long enough
+    pub fn expire_status(&mut self) {
+        self.status_message.expire();
     }
 
     /// Toggle the current field if it's a toggle
     pub fn toggle_current(&mut self) {
         match self.active_field {
+            InputField::Anonymous => self.anonymous = !self.anonymous,
+            InputField::Mode => self.mode = self.mode.next(),
             InputField::ToggleSpecial => self.use_special = !self.use_special,
             InputField::ToggleLetters => self.use_letters = !self.use_letters,
             InputField::ToggleNumbers => self.use_numbers = !self.use_numbers,
+            InputField::ToggleExtended => self.use_extended = !self.use_extended,
+            InputField::TrailingDigit => self.trailing_digit = !self.trailing_digit,
+            InputField::TrailingSpecial => self.trailing_special = !self.trailing_special,
             InputField::Generate => self.generate(),
             _ => {}
         }
@@ -145,30 +998,55 @@ impl App {
     pub fn current_text_input(&mut self) -> Option<&mut String> {
         match self.active_field {
             InputField::Name => Some(&mut self.name_input),
+            InputField::Length if self.mode == GenMode::Template => Some(&mut self.template_input),
             InputField::Length => Some(&mut self.length_input),
+            InputField::MustMatch => Some(&mut self.must_match_input),
             _ => None,
         }
     }
 
-    /// Navigate to next field
-    pub fn next_field(&mut self) {
-        self.active_field = self.active_field.next();
+    /// Navigate to next field, from the last back to the first when `wrap` is set
+    pub fn next_field(&mut self, wrap: bool) {
+        let next = self.active_field.next();
+        if wrap || next != InputField::Name {
+            self.active_field = next;
+        }
     }
 
-    /// Navigate to previous field
-    pub fn prev_field(&mut self) {
-        self.active_field = self.active_field.prev();
+    /// Navigate to previous field, from the first to the last when `wrap` is set
+    pub fn prev_field(&mut self, wrap: bool) {
+        let prev = self.active_field.prev();
+        if wrap || prev != InputField::Generate {
+            self.active_field = prev;
+        }
     }
 
-    /// Get the current password entry for saving
-    pub fn get_entry(&self) -> Option<super::storage::PasswordEntry> {
-        self.generated_password
-            .as_ref()
-            .map(|pwd| super::storage::PasswordEntry {
-                name: self.name_input.clone(),
-                password: pwd.clone(),
-                created_at: chrono_timestamp(),
-            })
+    /// Caution shown with a generated password too long for many sites
+    pub fn length_warning(&self) -> Option<String> {
+        let len = self.generated_password.as_ref()?.chars().count();
+        (len > LONG_PASSWORD_WARNING).then(|| {
+            format!(
+                "⚠ {} characters: many sites reject passwords over {}",
+                len, LONG_PASSWORD_WARNING
+            )
+        })
+    }
+
+    /// Get the current password entry for saving; a blank name in anonymous
+    /// mode becomes `entry-<timestamp>`, leaving the name field blank
+    pub fn get_entry(&self) -> Option<PasswordEntry> {
+        let created_at = chrono_timestamp();
+        let mut name = sanitize_name(&self.name_input);
+        if name.is_empty() && self.anonymous {
+            name = format!("entry-{}", created_at);
+        }
+        self.generated_password.as_ref().map(|pwd| PasswordEntry {
+            name,
+            password: pwd.clone(),
+            created_at,
+            gen_options: self.generated_with.clone(),
+            ..Default::default()
+        })
     }
 
     /// Clear inputs after successful save
@@ -185,8 +1063,106 @@ impl Default for App {
     }
 }
 
+/// Rough entropy of an arbitrary password in bits, based on which
+/// character classes it draws from
+pub fn estimate_entropy(password: &str) -> f64 {
+    let (mut lower, mut upper, mut digit, mut symbol, mut other) =
+        (false, false, false, false, false);
+    for c in password.chars() {
+        match c {
+            'a'..='z' => lower = true,
+            'A'..='Z' => upper = true,
+            '0'..='9' => digit = true,
+            c if c.is_ascii_punctuation() || c == ' ' => symbol = true,
+            _ => other = true,
+        }
+    }
+
+    let pool = [
+        (lower, 26),
+        (upper, 26),
+        (digit, 10),
+        (symbol, 33),
+        (other, 100),
+    ]
+    .iter()
+    .filter(|(used, _)| *used)
+    .map(|(_, size)| size)
+    .sum::<u32>();
+
+    if pool == 0 {
+        return 0.0;
+    }
+    password.chars().count() as f64 * (pool as f64).log2()
+}
+
+/// Typed text is mostly capitals, as when Caps Lock was left on. The key state
+/// itself can't be read portably, so this guesses from what arrived
+pub fn caps_lock_suspected(input: &str) -> bool {
+    let letters = input.chars().filter(|c| c.is_alphabetic()).count();
+    let upper = input.chars().filter(|c| c.is_uppercase()).count();
+    letters >= 4 && upper * 4 >= letters * 3
+}
+
+/// Indices of entries whose password estimates as weak
+pub fn weak_passwords(entries: &[PasswordEntry]) -> Vec<usize> {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| strength_label(estimate_entropy(&e.password)) == "Weak")
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Aggregate figures for the statistics screen
+#[derive(Debug, Default, PartialEq)]
+pub struct EntryStats {
+    pub total: usize,
+    pub weak: usize,
+    /// Entries whose password is also used by another entry
+    pub reused: usize,
+    /// Name and creation time of the oldest and newest entries
+    pub oldest: Option<(String, u64)>,
+    pub newest: Option<(String, u64)>,
+    pub average_length: f64,
+    /// Entries per tag, most used first
+    pub tags: Vec<(String, usize)>,
+}
+
+impl EntryStats {
+    pub fn compute(entries: &[PasswordEntry]) -> Self {
+        let dated = || {
+            entries
+                .iter()
+                .filter_map(|e| Some((e.name.clone(), e.created_at.parse::<u64>().ok()?)))
+        };
+        let mut tags: HashMap<&str, usize> = HashMap::new();
+        for tag in entries.iter().flat_map(|e| &e.tags) {
+            *tags.entry(tag).or_default() += 1;
+        }
+        let mut tags: Vec<(String, usize)> =
+            tags.into_iter().map(|(t, n)| (t.to_string(), n)).collect();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let total_length: usize = entries.iter().map(|e| e.password.chars().count()).sum();
+
+        Self {
+            total: entries.len(),
+            weak: weak_passwords(entries).len(),
+            reused: reused_passwords(entries).iter().map(Vec::len).sum(),
+            oldest: dated().min_by_key(|(_, t)| *t),
+            newest: dated().max_by_key(|(_, t)| *t),
+            average_length: if entries.is_empty() {
+                0.0
+            } else {
+                total_length as f64 / entries.len() as f64
+            },
+            tags,
+        }
+    }
+}
+
 /// Simple timestamp without external dependency
-fn chrono_timestamp() -> String {
+pub fn chrono_timestamp() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
     let duration = SystemTime::now()
         .duration_since(UNIX_EPOCH)
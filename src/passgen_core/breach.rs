@@ -0,0 +1,83 @@
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::path::Path;
+
+/// Hex digits of the SHA-1 hash that a range lookup reveals
+pub const PREFIX_LEN: usize = 5;
+
+/// HaveIBeenPwned k-anonymity endpoint; the hash prefix is appended
+pub const RANGE_API: &str = "https://api.pwnedpasswords.com/range/";
+
+/// Uppercase hex SHA-1 of `password`, split into the range prefix and the
+/// suffix that never leaves this machine
+pub fn hash_parts(password: &str) -> (String, String) {
+    let hex: String = Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    let (prefix, suffix) = hex.split_at(PREFIX_LEN);
+    (prefix.to_string(), suffix.to_string())
+}
+
+/// Times `suffix` appears in a range listing of `SUFFIX:COUNT` lines
+pub fn count_in_range(range: &str, suffix: &str) -> u64 {
+    range
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(s, _)| s.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Breach count for `password` from a downloaded HaveIBeenPwned range file
+/// (`<ranges_dir>/<PREFIX>.txt`), without any network access
+pub fn check_pwned_local(password: &str, ranges_dir: &Path) -> Result<u64, String> {
+    let (prefix, suffix) = hash_parts(password);
+    let file = ranges_dir.join(format!("{}.txt", prefix));
+    let range = fs::read_to_string(&file)
+        .map_err(|e| format!("Cannot read range file {}: {}", file.display(), e))?;
+    Ok(count_in_range(&range, &suffix))
+}
+
+/// Breach count for `password` from the HaveIBeenPwned API. Only the first
+/// `PREFIX_LEN` hex digits of the hash are sent; the match happens locally
+pub fn check_pwned_online(password: &str) -> Result<u64, String> {
+    let (prefix, suffix) = hash_parts(password);
+    let range = ureq::get(format!("{}{}", RANGE_API, prefix))
+        // Padded responses hide how many suffixes share the prefix
+        .header("Add-Padding", "true")
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| format!("Breach lookup failed: {}", e))?;
+    Ok(count_in_range(&range, &suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_range_lookup() {
+        // SHA-1("password") = 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        let (prefix, suffix) = hash_parts("password");
+        assert_eq!(prefix, "5BAA6");
+        assert_eq!(suffix, "1E4C9B93F3F0682250B6CF8331B7EE68FD8");
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("5BAA6.txt"),
+            "003D68EB55068C33ACE09247EE4C639306B:3\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n",
+        )
+        .unwrap();
+        assert_eq!(check_pwned_local("password", dir.path()).unwrap(), 9545824);
+
+        // Same prefix file, different suffix
+        fs::write(
+            dir.path().join("5BAA6.txt"),
+            "003D68EB55068C33ACE09247EE4C639306B:3\n",
+        )
+        .unwrap();
+        assert_eq!(check_pwned_local("password", dir.path()).unwrap(), 0);
+        assert!(check_pwned_local("something else", dir.path()).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use arboard::Clipboard;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// How a copy reached the clipboard
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyChannel {
+    /// The system clipboard took the text
+    Native,
+    /// An OSC 52 sequence went to the terminal, which may ignore it
+    Terminal,
+}
+
+impl CopyChannel {
+    /// Status line for copying `what` this way
+    pub fn status(self, what: &str) -> String {
+        match self {
+            Self::Native => format!("✓ Copied {} to clipboard", what),
+            Self::Terminal => format!("✓ Sent {} to terminal clipboard", what),
+        }
+    }
+}
+
+/// Put `text` on the system clipboard, falling back to an OSC 52 escape
+/// sequence when there is no native clipboard (e.g. over SSH)
+pub fn copy_to_clipboard(text: &str) -> Result<CopyChannel, String> {
+    let native = Clipboard::new()
+        .map_err(|_| "Clipboard unavailable".to_string())
+        .and_then(|mut clipboard| {
+            clipboard
+                .set_text(text.to_string())
+                .map_err(|_| "Failed to copy".to_string())
+        });
+    match native {
+        Ok(()) => Ok(CopyChannel::Native),
+        Err(e) => copy_osc52(text)
+            .map(|_| CopyChannel::Terminal)
+            .map_err(|osc| format!("{}; {}", e, osc)),
+    }
+}
+
+/// Empty the clipboard, through the same channels `copy_to_clipboard` uses
+pub fn clear_clipboard() -> Result<(), String> {
+    let native = Clipboard::new()
+        .map_err(|_| "Clipboard unavailable".to_string())
+        .and_then(|mut clipboard| {
+            clipboard
+                .clear()
+                .map_err(|_| "Failed to clear clipboard".to_string())
+        });
+    match native {
+        Ok(()) => Ok(()),
+        Err(e) => copy_osc52("").map_err(|osc| format!("{}; {}", e, osc)),
+    }
+}
+
+/// Ask the terminal emulator itself to set the clipboard
+fn copy_osc52(text: &str) -> Result<(), String> {
+    let mut sequence = format!("\x1b]52;c;{}\x07", BASE64.encode(text));
+    // tmux only forwards escape sequences wrapped in its passthrough envelope
+    if std::env::var_os("TMUX").is_some() {
+        sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+    }
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(|e| format!("OSC 52 copy failed: {}", e))
+}
+
+/// Sysctl that has to be on for unprivileged TIOCSTI on recent Linux kernels
+#[cfg(unix)]
+const LEGACY_TIOCSTI: &str = "/proc/sys/dev/tty/legacy_tiocsti";
+
+#[cfg(unix)]
+fn open_tty() -> Result<std::fs::File, String> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| format!("No terminal to type into: {}", e))
+}
+
+/// Whether `type_out` can work here, checked before leaving the UI so a refusal
+/// can still be shown there
+#[cfg(unix)]
+pub fn type_out_available() -> Result<(), String> {
+    open_tty()?;
+    let disabled = std::fs::read_to_string(LEGACY_TIOCSTI).is_ok_and(|v| v.trim() == "0");
+    // SAFETY: geteuid has no preconditions
+    if disabled && unsafe { libc::geteuid() } != 0 {
+        return Err("Typing out needs dev.tty.legacy_tiocsti=1 on this kernel".into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn type_out_available() -> Result<(), String> {
+    Err("Typing out is only supported on Unix terminals".into())
+}
+
+/// Push `text` into the terminal's input queue one character at a time, as if
+/// typed, for whatever reads the terminal next — normally the shell prompt,
+/// which echoes it and runs it on Enter. For sessions that drop pasted
+/// characters. On failure the queue is flushed so no partial text is left
+#[cfg(unix)]
+pub fn type_out(text: &str, delay: Duration) -> Result<(), String> {
+    use std::os::fd::AsRawFd;
+    let tty = open_tty()?;
+    let mut buf = [0u8; 4];
+    for c in text.chars() {
+        for byte in c.encode_utf8(&mut buf).bytes() {
+            // SAFETY: TIOCSTI reads a single byte through the pointer, which
+            // stays valid for the whole call
+            let result = unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCSTI, &byte) };
+            if result != 0 {
+                let error = io::Error::last_os_error();
+                // SAFETY: the descriptor is open for the whole call
+                unsafe { libc::tcflush(tty.as_raw_fd(), libc::TCIFLUSH) };
+                return Err(format!("Typing failed, input discarded: {}", error));
+            }
+        }
+        std::thread::sleep(delay);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn type_out(_text: &str, _delay: Duration) -> Result<(), String> {
+    type_out_available()
+}
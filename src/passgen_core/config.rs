@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::generator::{ClassWeights, GenMode};
+
+/// Non-secret user preferences, stored in plain JSON next to the vault
+#[derive(Serialize, Deserialize)]
+pub struct Config {
+    /// Restore the viewer selection to the last-viewed entry
+    #[serde(default = "default_true")]
+    pub remember_selection: bool,
+    /// Name of the entry selected when the viewer was last closed
+    #[serde(default)]
+    pub last_selected: Option<String>,
+    /// Always open the vault read-only
+    #[serde(default)]
+    pub read_only: bool,
+    /// The first-run welcome screen has been dismissed
+    #[serde(default)]
+    pub seen_onboarding: bool,
+    /// Up/Down and field navigation wrap around at the ends instead of stopping
+    #[serde(default)]
+    pub wrap_navigation: bool,
+    /// Directory of downloaded HaveIBeenPwned range files for offline breach checks
+    #[serde(default)]
+    pub pwned_ranges_dir: Option<PathBuf>,
+    /// Permit the online breach check; nothing touches the network unless set
+    #[serde(default)]
+    pub allow_network: bool,
+    /// Template for the formatted copy; see `PasswordEntry::format`
+    #[serde(default = "default_copy_format")]
+    pub copy_format: String,
+    /// Empty the clipboard on exit if something was copied this session
+    #[serde(default = "default_true")]
+    pub clear_clipboard_on_exit: bool,
+    /// Record when each entry is copied or revealed; costs a vault write each time
+    #[serde(default)]
+    pub track_last_used: bool,
+    /// Let `r` in the viewer reveal every listed password at once; off so a
+    /// stray key can't expose the whole vault
+    #[serde(default)]
+    pub allow_reveal_all: bool,
+    /// `d` in the viewer deletes at once, with a few seconds to undo, instead
+    /// of asking first
+    #[serde(default)]
+    pub quick_delete: bool,
+    /// Allow `T` in the viewer: quit and type the selected password at the shell
+    /// prompt. Warning: the shell shows it in clear, and pressing Enter runs it
+    /// as a command and saves it to the shell history. For sessions where the
+    /// clipboard is missing or drops characters
+    #[serde(default)]
+    pub type_out: bool,
+    /// Pause between typed characters
+    #[serde(default = "default_type_out_delay_ms")]
+    pub type_out_delay_ms: u64,
+    /// Enter only generates; the shown password is stored with Ctrl+S
+    #[serde(default)]
+    pub explicit_save: bool,
+    /// Hold vault writes until this many milliseconds pass without another
+    /// change, so a burst of edits costs one save; 0 writes every change at once
+    #[serde(default)]
+    pub save_debounce_ms: u64,
+    /// Program and arguments run after every successful save, with the vault
+    /// path appended (e.g. a sync script). Off unless set
+    #[serde(default)]
+    pub post_save_command: Option<Vec<String>>,
+    /// Also keep a plaintext list of entry names, URLs and tags (no secrets)
+    /// at this path, for diffing in version control
+    #[serde(default)]
+    pub metadata_manifest: Option<PathBuf>,
+    /// Newline-separated wordlist for passphrases, replacing the built-in one;
+    /// `--wordlist` takes precedence
+    #[serde(default)]
+    pub wordlist: Option<PathBuf>,
+    /// Bias generated passwords toward some character classes, e.g.
+    /// `{"special": 3}`; unset keeps every character equally likely. Weights
+    /// above `MAX_CLASS_WEIGHT` make the whole setting ignored
+    #[serde(default)]
+    pub class_weights: Option<ClassWeights>,
+    /// Named generator settings, in the order they cycle
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    /// Vault location chosen with the move action; the default location when unset
+    #[serde(default)]
+    pub vault_path: Option<PathBuf>,
+    /// Where this config was loaded from; `None` means the default location
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+/// A saved set of generator options
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub length: usize,
+    #[serde(default)]
+    pub mode: GenMode,
+    pub use_special: bool,
+    pub use_letters: bool,
+    pub use_numbers: bool,
+    #[serde(default)]
+    pub use_extended: bool,
+    #[serde(default)]
+    pub must_match: Option<String>,
+    #[serde(default)]
+    pub require_trailing_digit: bool,
+    #[serde(default)]
+    pub require_trailing_special: bool,
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// File in the working directory whose generator settings override the defaults
+pub const DIR_PROFILE_NAME: &str = ".passgenrc";
+
+/// Per-directory generator defaults read from a JSON `.passgenrc`; fields left
+/// out keep the built-in default
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirProfile {
+    pub length: Option<usize>,
+    pub mode: Option<GenMode>,
+    pub use_special: Option<bool>,
+    pub use_letters: Option<bool>,
+    pub use_numbers: Option<bool>,
+    pub use_extended: Option<bool>,
+    pub must_match: Option<String>,
+    pub require_trailing_digit: Option<bool>,
+    pub require_trailing_special: Option<bool>,
+}
+
+impl DirProfile {
+    /// Read the profile in `dir`; `Ok(None)` when there is none
+    pub fn load(dir: &Path) -> Result<Option<Self>, String> {
+        let path = dir.join(DIR_PROFILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_type_out_delay_ms() -> u64 {
+    30
+}
+
+fn default_copy_format() -> String {
+    "{name}: {password}".into()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            remember_selection: true,
+            last_selected: None,
+            read_only: false,
+            seen_onboarding: false,
+            wrap_navigation: false,
+            pwned_ranges_dir: None,
+            allow_network: false,
+            copy_format: default_copy_format(),
+            clear_clipboard_on_exit: true,
+            track_last_used: false,
+            allow_reveal_all: false,
+            quick_delete: false,
+            type_out: false,
+            type_out_delay_ms: default_type_out_delay_ms(),
+            explicit_save: false,
+            save_debounce_ms: 0,
+            post_save_command: None,
+            metadata_manifest: None,
+            wordlist: None,
+            class_weights: None,
+            presets: Vec::new(),
+            vault_path: None,
+            path: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config file, falling back to defaults if it's missing or unreadable
+    pub fn load() -> Self {
+        match Self::default_path() {
+            Ok(path) => Self::load_from(&path),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Load a config file at an explicit path; later saves go back to it
+    pub fn load_from(path: &Path) -> Self {
+        let mut config: Self = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        config.path = Some(path.to_path_buf());
+        if let Some(Err(e)) = config.class_weights.map(|w| w.validate()) {
+            log::warn!("ignoring class_weights: {}", e);
+            config.class_weights = None;
+        }
+        config
+    }
+
+    /// Write the config file
+    pub fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => Self::default_path()?,
+        };
+        let output = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Serialization failed: {}", e))?;
+        fs::write(&path, output).map_err(|e| format!("Failed to write config: {}", e))
+    }
+
+    /// Record the entry the viewer was closed on, if remembering is enabled
+    pub fn remember_selection(&mut self, name: Option<&String>) -> Result<(), String> {
+        if !self.remember_selection {
+            return Ok(());
+        }
+        self.last_selected = name.cloned();
+        self.save()
+    }
+
+    /// Get default config path
+    fn default_path() -> Result<PathBuf, String> {
+        let home = dirs::home_dir().ok_or_else(|| "Cannot find home directory".to_string())?;
+        Ok(home.join(".passgen_config.json"))
+    }
+}
@@ -0,0 +1,882 @@
+use rand::Rng;
+use rand::distr::Distribution;
+use rand::distr::weighted::WeightedIndex;
+use rand::seq::SliceRandom;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::wordlist;
+
+/// Non-ASCII symbols offered by the opt-in extended set. Each is a single
+/// code point of display width 1, so they never split or misalign.
+pub const EXTENDED_SYMBOLS: &str = "§±×÷€£¥¢°µ¶¿¡«»¤©®™•←→↑↓∞≈≠≤≥";
+
+/// Separators a passphrase may be joined with; one is picked per phrase
+pub const PASSPHRASE_SEPARATORS: &[char] = &['-', '_', '.', ':', '+'];
+
+/// What kind of secret to produce
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenMode {
+    /// Random characters from the enabled classes
+    #[default]
+    Password,
+    /// Random words from the active wordlist; `length` is the word count
+    Passphrase,
+    /// Random digits, skipping trivially guessable ones; `length` is the digit count
+    Pin,
+    /// A fixed pattern like `Word-####-!!` with placeholders filled at random;
+    /// `length` is unused
+    Template,
+}
+
+impl GenMode {
+    /// The mode after this one, wrapping around
+    pub fn next(self) -> Self {
+        match self {
+            Self::Password => Self::Passphrase,
+            Self::Passphrase => Self::Pin,
+            Self::Pin => Self::Template,
+            Self::Template => Self::Password,
+        }
+    }
+
+    /// Name shown in the form
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Password => "Password",
+            Self::Passphrase => "Passphrase",
+            Self::Pin => "PIN",
+            Self::Template => "Template",
+        }
+    }
+}
+
+/// The letter class
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Lowercase letters, for the `a` template placeholder
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+
+/// Uppercase letters, for the `A` template placeholder
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// The digit class
+const DIGITS: &str = "0123456789";
+
+/// The ASCII special-character class
+const SPECIALS: &str = "!@#$%^&*()_+-=[]{}|;:,.<>?";
+
+/// Largest per-class weight a config may set
+pub const MAX_CLASS_WEIGHT: u32 = 1_000;
+
+/// Longest password, passphrase or PIN the form accepts
+pub const MAX_LENGTH: usize = 1024;
+
+/// Beyond this many characters many sites truncate or reject a password
+pub const LONG_PASSWORD_WARNING: usize = 128;
+
+/// How many trivial PINs may be rejected before giving up
+pub const MAX_PIN_ATTEMPTS: usize = 1_000;
+
+/// How many candidates `must_match` may reject before giving up
+pub const MAX_MATCH_ATTEMPTS: usize = 10_000;
+
+/// Settings that fully describe how a password is generated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenOptions {
+    pub mode: GenMode,
+    pub length: usize,
+    pub use_special: bool,
+    pub use_letters: bool,
+    pub use_numbers: bool,
+    pub use_extended: bool,
+    /// Guarantee at least this many characters from each enabled class
+    pub min_per_class: usize,
+    /// Keep regenerating until the password matches this regex
+    pub must_match: Option<String>,
+    /// Password mode: end with a digit (before any trailing special)
+    pub require_trailing_digit: bool,
+    /// Password mode: end with a special character
+    pub require_trailing_special: bool,
+    /// Pick each character's class by weight instead of uniformly over all characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_weights: Option<ClassWeights>,
+    /// Template mode: the pattern to fill, see `parse_template`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// Per-character odds of each class when filling a password, relative to the
+/// uniform draw: a weight of 3 makes each of the class's characters three times
+/// as likely. Unset classes weigh 1, so the default changes nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassWeights {
+    pub letters: u32,
+    pub numbers: u32,
+    pub special: u32,
+    pub extended: u32,
+}
+
+impl Default for ClassWeights {
+    fn default() -> Self {
+        Self {
+            letters: 1,
+            numbers: 1,
+            special: 1,
+            extended: 1,
+        }
+    }
+}
+
+impl ClassWeights {
+    /// Weight of one of the classes `GenOptions::classes` returns
+    fn of(&self, class: &str) -> u32 {
+        match class {
+            LETTERS => self.letters,
+            DIGITS => self.numbers,
+            SPECIALS => self.special,
+            _ => self.extended,
+        }
+    }
+
+    /// Odds of drawing any character of `class`: its weight times its size
+    fn of_class(&self, class: &str) -> u64 {
+        u64::from(self.of(class)) * class.chars().count() as u64
+    }
+
+    /// An error naming the first weight above `MAX_CLASS_WEIGHT`
+    pub fn validate(&self) -> Result<(), String> {
+        let weights = [
+            ("letters", self.letters),
+            ("numbers", self.numbers),
+            ("special", self.special),
+            ("extended", self.extended),
+        ];
+        match weights.iter().find(|(_, w)| *w > MAX_CLASS_WEIGHT) {
+            Some((class, w)) => Err(format!(
+                "Class weight {} for {} is above {}",
+                w, class, MAX_CLASS_WEIGHT
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl GenOptions {
+    /// Each enabled character class, in charset order
+    pub fn classes(&self) -> Vec<&'static str> {
+        let mut classes = Vec::new();
+
+        if self.use_letters {
+            classes.push(LETTERS);
+        }
+
+        if self.use_numbers {
+            classes.push(DIGITS);
+        }
+
+        if self.use_special {
+            classes.push(SPECIALS);
+        }
+
+        if self.use_extended {
+            classes.push(EXTENDED_SYMBOLS);
+        }
+
+        classes
+    }
+
+    /// Characters a password may be drawn from
+    pub fn charset(&self) -> Vec<char> {
+        self.classes().concat().chars().collect()
+    }
+
+    /// Number of characters a password may be drawn from; 0 means nothing to generate
+    pub fn charset_size(&self) -> usize {
+        self.classes()
+            .iter()
+            .map(|class| class.chars().count())
+            .sum()
+    }
+
+    /// Entropy of one freely drawn character, in bits. With class weights a
+    /// character from class c has probability w_c / W, where W sums w * n over
+    /// the classes.
+    fn bits_per_char(&self) -> f64 {
+        let classes = self.classes();
+        let Some(weights) = &self.class_weights else {
+            return (self.charset_size() as f64).log2();
+        };
+        let total: u64 = classes.iter().map(|c| weights.of_class(c)).sum();
+        if total == 0 {
+            return 0.0;
+        }
+        let total = total as f64;
+        classes
+            .iter()
+            .filter(|c| weights.of(c) > 0)
+            .map(|c| {
+                let p = f64::from(weights.of(c)) / total;
+                -(weights.of_class(c) as f64) / total * p.log2()
+            })
+            .sum()
+    }
+
+    /// Entropy of a password generated with these settings, in bits
+    pub fn entropy_bits(&self) -> f64 {
+        match self.mode {
+            GenMode::Passphrase => return passphrase_entropy(self.length, wordlist::word_count()),
+            GenMode::Pin => return self.length as f64 * 10f64.log2(),
+            GenMode::Template => {
+                return parse_template(self.template.as_deref().unwrap_or_default())
+                    .map(|slots| {
+                        slots
+                            .iter()
+                            .filter_map(|slot| slot.class())
+                            .map(|class| (class.chars().count() as f64).log2())
+                            .sum()
+                    })
+                    .unwrap_or(0.0);
+            }
+            GenMode::Password => {}
+        }
+        let size = self.charset_size();
+        if size == 0 {
+            return 0.0;
+        }
+        // Pinned trailing characters only draw from their own class
+        let pinned = [
+            (self.require_trailing_digit, DIGITS),
+            (self.require_trailing_special, SPECIALS),
+        ];
+        let mut bits = 0.0;
+        let mut free = self.length;
+        for (_, class) in pinned.iter().filter(|(on, _)| *on) {
+            if free > 0 {
+                bits += (class.len() as f64).log2();
+                free -= 1;
+            }
+        }
+        bits + free as f64 * self.bits_per_char()
+    }
+}
+
+/// One position of a parsed template
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    Literal(char),
+    /// Filled with a random character of this class
+    Random(&'static str),
+}
+
+impl Slot {
+    fn class(self) -> Option<&'static str> {
+        match self {
+            Self::Random(class) => Some(class),
+            Self::Literal(_) => None,
+        }
+    }
+}
+
+/// Split a template into slots: `#` is a digit, `!` a special character, `A`
+/// an uppercase and `a` a lowercase letter. A backslash makes the next
+/// placeholder (or backslash) literal; everything else stays as written.
+fn parse_template(template: &str) -> Result<Vec<Slot>, String> {
+    let mut slots = Vec::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        slots.push(match c {
+            '#' => Slot::Random(DIGITS),
+            '!' => Slot::Random(SPECIALS),
+            'A' => Slot::Random(UPPERCASE),
+            'a' => Slot::Random(LOWERCASE),
+            '\\' => match chars.next() {
+                Some(escaped @ ('#' | '!' | 'A' | 'a' | '\\')) => Slot::Literal(escaped),
+                Some(other) => {
+                    return Err(format!(
+                        "Unknown placeholder '\\{}': only # ! A a and \\ can be escaped",
+                        other
+                    ));
+                }
+                None => return Err("Template ends with a lone '\\'".into()),
+            },
+            literal => Slot::Literal(literal),
+        });
+    }
+    if !slots.iter().any(|slot| slot.class().is_some()) {
+        return Err("Template needs at least one placeholder: # ! A or a".into());
+    }
+    Ok(slots)
+}
+
+/// Fill every placeholder of `template` from `rng`
+fn generate_from_template<R: Rng + ?Sized>(template: &str, rng: &mut R) -> Result<String, String> {
+    Ok(parse_template(template)?
+        .into_iter()
+        .map(|slot| match slot {
+            Slot::Literal(c) => c,
+            Slot::Random(class) => {
+                let class: Vec<char> = class.chars().collect();
+                class[rng.random_range(0..class.len())]
+            }
+        })
+        .collect())
+}
+
+/// Shortest length reaching `target_bits` when every character is drawn uniformly
+/// from `charset_size` symbols; 0 when the target is not positive or no length can
+pub fn min_length_for_bits(target_bits: f64, charset_size: usize) -> usize {
+    if charset_size < 2 || target_bits.is_nan() || target_bits <= 0.0 {
+        return 0;
+    }
+    (target_bits / (charset_size as f64).log2()).ceil() as usize
+}
+
+/// Entropy of a passphrase in bits: each word contributes its pick from the list
+/// plus one bit for random capitalization, and the phrase one separator choice
+pub fn passphrase_entropy(word_count: usize, wordlist_size: usize) -> f64 {
+    if word_count == 0 || wordlist_size == 0 {
+        return 0.0;
+    }
+    word_count as f64 * ((wordlist_size as f64).log2() + 1.0)
+        + (PASSPHRASE_SEPARATORS.len() as f64).log2()
+}
+
+/// Human-readable strength for an entropy estimate
+pub fn strength_label(bits: f64) -> &'static str {
+    match bits {
+        b if b < 40.0 => "Weak",
+        b if b < 60.0 => "Fair",
+        b if b < 80.0 => "Strong",
+        _ => "Very strong",
+    }
+}
+
+/// Generate a password with the thread-local CSPRNG
+pub fn generate_password(opts: &GenOptions) -> Result<String, String> {
+    generate_with_rng(opts, &mut rand::rng())
+}
+
+/// Generate a password drawing randomness from `rng`
+pub fn generate_with_rng<R: Rng + ?Sized>(
+    opts: &GenOptions,
+    rng: &mut R,
+) -> Result<String, String> {
+    let Some(pattern) = &opts.must_match else {
+        return generate_candidate(opts, rng);
+    };
+    let regex = Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+    for _ in 0..MAX_MATCH_ATTEMPTS {
+        let candidate = generate_candidate(opts, rng)?;
+        if regex.is_match(&candidate) {
+            return Ok(candidate);
+        }
+    }
+    Err(format!(
+        "No match for /{}/ after {} attempts",
+        pattern, MAX_MATCH_ATTEMPTS
+    ))
+}
+
+/// One password for `opts`, ignoring `must_match`
+fn generate_candidate<R: Rng + ?Sized>(opts: &GenOptions, rng: &mut R) -> Result<String, String> {
+    match opts.mode {
+        GenMode::Passphrase => return generate_passphrase(opts.length, rng),
+        GenMode::Pin => return generate_pin(opts.length, rng),
+        GenMode::Template => {
+            return generate_from_template(opts.template.as_deref().unwrap_or_default(), rng);
+        }
+        GenMode::Password => {}
+    }
+    if opts.length == 0 {
+        return Err("Length must be at least 1".into());
+    }
+
+    let classes = opts.classes();
+    if classes.is_empty() {
+        return Err("Enable at least one character type".into());
+    }
+    if opts.require_trailing_digit && !opts.use_numbers {
+        return Err("Trailing digit needs numbers enabled".into());
+    }
+    if opts.require_trailing_special && !opts.use_special {
+        return Err("Trailing special needs special characters enabled".into());
+    }
+    let pinned =
+        usize::from(opts.require_trailing_digit) + usize::from(opts.require_trailing_special);
+    if pinned > opts.length {
+        return Err(format!(
+            "Length {} is too short for the trailing rules",
+            opts.length
+        ));
+    }
+    if opts.min_per_class * classes.len() > opts.length {
+        return Err(format!(
+            "Length {} is too short for {} of each character type",
+            opts.length, opts.min_per_class
+        ));
+    }
+
+    // Draw the guaranteed characters first, fill the rest from the full set, then shuffle
+    let mut password: Vec<char> = Vec::with_capacity(opts.length);
+    for class in &classes {
+        let class: Vec<char> = class.chars().collect();
+        for _ in 0..opts.min_per_class {
+            password.push(class[rng.random_range(0..class.len())]);
+        }
+    }
+    match &opts.class_weights {
+        None => {
+            let chars = opts.charset();
+            while password.len() < opts.length {
+                password.push(chars[rng.random_range(0..chars.len())]);
+            }
+        }
+        Some(weights) => {
+            let pick = WeightedIndex::new(classes.iter().map(|c| weights.of_class(c)))
+                .map_err(|_| "Give at least one enabled character type a weight".to_string())?;
+            let classes: Vec<Vec<char>> = classes.iter().map(|c| c.chars().collect()).collect();
+            while password.len() < opts.length {
+                let class = &classes[pick.sample(rng)];
+                password.push(class[rng.random_range(0..class.len())]);
+            }
+        }
+    }
+    password.shuffle(rng);
+
+    // Fix up the tail: special last, digit just before it
+    let mut end = password.len();
+    for (required, class) in [
+        (opts.require_trailing_special, SPECIALS),
+        (opts.require_trailing_digit, DIGITS),
+    ] {
+        if required {
+            pin_to_end(&mut password[..end], class, rng);
+            end -= 1;
+        }
+    }
+
+    Ok(password.into_iter().collect())
+}
+
+/// Make the last character of `password` one from `class`. A character of that
+/// class already in the slice is swapped there, so per-class minimums still hold;
+/// only when there is none is the last one replaced.
+fn pin_to_end<R: Rng + ?Sized>(password: &mut [char], class: &str, rng: &mut R) {
+    let last = password.len() - 1;
+    let candidates: Vec<usize> = (0..password.len())
+        .filter(|&i| class.contains(password[i]))
+        .collect();
+    if candidates.is_empty() {
+        let class: Vec<char> = class.chars().collect();
+        password[last] = class[rng.random_range(0..class.len())];
+    } else {
+        password.swap(candidates[rng.random_range(0..candidates.len())], last);
+    }
+}
+
+/// `length` random digits, redrawn while the result is trivial
+fn generate_pin<R: Rng + ?Sized>(length: usize, rng: &mut R) -> Result<String, String> {
+    if length == 0 {
+        return Err("PIN needs at least one digit".into());
+    }
+    for _ in 0..MAX_PIN_ATTEMPTS {
+        let pin: Vec<u8> = (0..length).map(|_| rng.random_range(0..10)).collect();
+        if length < 2 || !is_trivial_pin(&pin) {
+            return Ok(pin.iter().map(|d| char::from(b'0' + d)).collect());
+        }
+    }
+    Err(format!(
+        "No non-trivial {}-digit PIN after {} attempts",
+        length, MAX_PIN_ATTEMPTS
+    ))
+}
+
+/// Runs like 1234 or 9876, and repeats of a shorter block like 0000 or 1212
+fn is_trivial_pin(digits: &[u8]) -> bool {
+    let steps: Vec<i8> = digits.windows(2).map(|w| w[1] as i8 - w[0] as i8).collect();
+    if steps.iter().all(|&s| s == 1) || steps.iter().all(|&s| s == -1) {
+        return true;
+    }
+    (1..=digits.len() / 2)
+        .filter(|&block| digits.len().is_multiple_of(block))
+        .any(|block| digits.chunks(block).all(|c| c == &digits[..block]))
+}
+
+/// `word_count` random words, each maybe capitalized, joined by one random separator
+fn generate_passphrase<R: Rng + ?Sized>(word_count: usize, rng: &mut R) -> Result<String, String> {
+    if word_count == 0 {
+        return Err("Passphrase needs at least one word".into());
+    }
+    let separator = PASSPHRASE_SEPARATORS[rng.random_range(0..PASSPHRASE_SEPARATORS.len())];
+    let words: Vec<String> = (0..word_count)
+        .map(|_| {
+            let word = wordlist::word(rng.random_range(0..wordlist::word_count()));
+            if rng.random_bool(0.5) {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect();
+    Ok(words.join(&separator.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn options() -> impl Strategy<Value = GenOptions> {
+        (0usize..64, any::<[bool; 4]>(), 0usize..8).prop_map(
+            |(length, [special, letters, numbers, extended], min)| GenOptions {
+                mode: GenMode::Password,
+                length,
+                use_special: special,
+                use_letters: letters,
+                use_numbers: numbers,
+                use_extended: extended,
+                min_per_class: min,
+                must_match: None,
+                require_trailing_digit: false,
+                require_trailing_special: false,
+                class_weights: None,
+                template: None,
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn output_honours_options(opts in options(), seed in any::<u64>()) {
+            let classes = opts.classes();
+            let feasible = opts.length > 0
+                && !classes.is_empty()
+                && opts.min_per_class * classes.len() <= opts.length;
+            let result = generate_with_rng(&opts, &mut StdRng::seed_from_u64(seed));
+            prop_assert_eq!(result.is_ok(), feasible);
+            prop_assert_eq!(opts.charset_size() == 0, classes.is_empty());
+            prop_assert_eq!(opts.charset_size(), opts.charset().len());
+
+            if let Ok(password) = result {
+                let charset = opts.charset();
+                prop_assert_eq!(password.chars().count(), opts.length);
+                prop_assert!(password.chars().all(|c| charset.contains(&c)));
+                for class in classes {
+                    let hits = password.chars().filter(|c| class.contains(*c)).count();
+                    prop_assert!(hits >= opts.min_per_class);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn class_weights_bias_the_fill() {
+        let mut opts = GenOptions {
+            mode: GenMode::Password,
+            length: 2000,
+            use_special: true,
+            use_letters: true,
+            use_numbers: true,
+            use_extended: false,
+            min_per_class: 0,
+            must_match: None,
+            require_trailing_digit: false,
+            require_trailing_special: false,
+            class_weights: None,
+            template: None,
+        };
+        let uniform = opts.entropy_bits();
+        let specials = |password: &str| password.chars().filter(|c| SPECIALS.contains(*c)).count();
+        let mut rng = StdRng::seed_from_u64(9);
+
+        // Default weights match the uniform draw: about 26/88 specials
+        opts.class_weights = Some(ClassWeights::default());
+        let password = generate_with_rng(&opts, &mut rng).unwrap();
+        assert!((500..700).contains(&specials(&password)));
+        assert!((opts.entropy_bits() - uniform).abs() < 1e-6);
+
+        // Tripling specials only triples their odds: 78/140 of the characters
+        opts.class_weights = Some(ClassWeights {
+            special: 3,
+            ..Default::default()
+        });
+        let password = generate_with_rng(&opts, &mut rng).unwrap();
+        assert!((1000..1250).contains(&specials(&password)));
+        assert!(opts.entropy_bits() < uniform);
+
+        opts.class_weights = Some(ClassWeights {
+            letters: 0,
+            numbers: 0,
+            ..Default::default()
+        });
+        let password = generate_with_rng(&opts, &mut rng).unwrap();
+        assert_eq!(specials(&password), 2000);
+        assert!((opts.entropy_bits() - 2000.0 * 26f64.log2()).abs() < 1e-6);
+
+        // Huge weights neither overflow nor panic, though a config rejects them
+        let huge = ClassWeights {
+            letters: u32::MAX,
+            ..Default::default()
+        };
+        assert!(huge.validate().is_err());
+        opts.class_weights = Some(huge);
+        assert!(generate_with_rng(&opts, &mut rng).is_ok());
+        assert!(opts.entropy_bits().is_finite());
+
+        opts.class_weights = Some(ClassWeights {
+            letters: 0,
+            numbers: 0,
+            ..Default::default()
+        });
+        opts.use_special = false;
+        assert!(generate_with_rng(&opts, &mut rng).is_err());
+        assert_eq!(opts.entropy_bits(), 0.0);
+    }
+
+    #[test]
+    fn must_match_filters_candidates() {
+        let mut opts = GenOptions {
+            mode: GenMode::Password,
+            length: 8,
+            use_special: false,
+            use_letters: true,
+            use_numbers: true,
+            use_extended: false,
+            min_per_class: 0,
+            must_match: Some("^[a-z]".into()),
+            require_trailing_digit: false,
+            require_trailing_special: false,
+            class_weights: None,
+            template: None,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            let password = generate_with_rng(&opts, &mut rng).unwrap();
+            assert!(password.starts_with(|c: char| c.is_ascii_lowercase()));
+        }
+
+        opts.must_match = Some("!".into());
+        assert!(generate_with_rng(&opts, &mut rng).is_err());
+        opts.must_match = Some("(".into());
+        assert!(generate_with_rng(&opts, &mut rng).is_err());
+    }
+
+    #[test]
+    fn trailing_rules_pin_the_tail() {
+        let mut opts = GenOptions {
+            mode: GenMode::Password,
+            length: 10,
+            use_special: true,
+            use_letters: true,
+            use_numbers: true,
+            use_extended: false,
+            min_per_class: 2,
+            must_match: None,
+            require_trailing_digit: true,
+            require_trailing_special: true,
+            class_weights: None,
+            template: None,
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let password: Vec<char> = generate_with_rng(&opts, &mut rng)
+                .unwrap()
+                .chars()
+                .collect();
+            assert!(SPECIALS.contains(password[9]));
+            assert!(DIGITS.contains(password[8]));
+            for class in opts.classes() {
+                assert!(password.iter().filter(|&&c| class.contains(c)).count() >= 2);
+            }
+        }
+
+        opts.min_per_class = 0;
+        opts.require_trailing_special = false;
+        let password = generate_with_rng(&opts, &mut rng).unwrap();
+        assert!(password.ends_with(|c: char| c.is_ascii_digit()));
+
+        opts.use_numbers = false;
+        assert!(generate_with_rng(&opts, &mut rng).is_err());
+    }
+
+    #[test]
+    fn pins_skip_trivial_patterns() {
+        for trivial in ["0000", "1234", "9876", "1212", "123123", "55"] {
+            let digits: Vec<u8> = trivial.bytes().map(|b| b - b'0').collect();
+            assert!(is_trivial_pin(&digits), "{}", trivial);
+        }
+        for fine in ["1593", "2024", "8080 1", "1122"] {
+            let digits: Vec<u8> = fine
+                .bytes()
+                .filter(u8::is_ascii_digit)
+                .map(|b| b - b'0')
+                .collect();
+            assert!(!is_trivial_pin(&digits), "{}", fine);
+        }
+
+        let opts = GenOptions {
+            mode: GenMode::Pin,
+            length: 4,
+            use_special: true,
+            use_letters: true,
+            use_numbers: false,
+            use_extended: false,
+            min_per_class: 0,
+            must_match: None,
+            require_trailing_digit: false,
+            require_trailing_special: false,
+            class_weights: None,
+            template: None,
+        };
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..100 {
+            let pin = generate_with_rng(&opts, &mut rng).unwrap();
+            assert_eq!(pin.len(), 4);
+            assert!(pin.chars().all(|c| c.is_ascii_digit()));
+        }
+        assert!((opts.entropy_bits() - 4.0 * 10f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn max_length_generates_quickly() {
+        let mut opts = GenOptions {
+            mode: GenMode::Password,
+            length: MAX_LENGTH,
+            use_special: true,
+            use_letters: true,
+            use_numbers: true,
+            use_extended: true,
+            min_per_class: 2,
+            must_match: None,
+            require_trailing_digit: true,
+            require_trailing_special: true,
+            class_weights: None,
+            template: None,
+        };
+        let started = std::time::Instant::now();
+        let mut rng = StdRng::seed_from_u64(5);
+        assert_eq!(
+            generate_with_rng(&opts, &mut rng).unwrap().chars().count(),
+            MAX_LENGTH
+        );
+        opts.mode = GenMode::Pin;
+        assert_eq!(
+            generate_with_rng(&opts, &mut rng).unwrap().len(),
+            MAX_LENGTH
+        );
+        opts.mode = GenMode::Passphrase;
+        assert!(generate_with_rng(&opts, &mut rng).is_ok());
+        assert!(opts.entropy_bits().is_finite());
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn passphrase_uses_word_count() {
+        let opts = GenOptions {
+            mode: GenMode::Passphrase,
+            length: 4,
+            use_special: false,
+            use_letters: false,
+            use_numbers: false,
+            use_extended: false,
+            min_per_class: 0,
+            must_match: None,
+            require_trailing_digit: false,
+            require_trailing_special: false,
+            class_weights: None,
+            template: None,
+        };
+        let phrase = generate_with_rng(&opts, &mut StdRng::seed_from_u64(7)).unwrap();
+        let words: Vec<_> = phrase.split(PASSPHRASE_SEPARATORS).collect();
+        assert_eq!(words.len(), 4);
+        assert!(
+            words
+                .iter()
+                .all(|w| wordlist::WORDS.contains(&w.to_lowercase().as_str()))
+        );
+
+        // A 4-word phrase is judged by its words, not its ~20 characters
+        let bits = opts.entropy_bits();
+        assert!((bits - passphrase_entropy(4, wordlist::WORDS.len())).abs() < f64::EPSILON);
+        assert!(bits < crate::passgen_core::app::estimate_entropy(&phrase));
+    }
+
+    #[test]
+    fn template_fills_placeholders_and_keeps_literals() {
+        let mut opts = GenOptions {
+            mode: GenMode::Template,
+            length: 0,
+            use_special: false,
+            use_letters: false,
+            use_numbers: false,
+            use_extended: false,
+            min_per_class: 0,
+            must_match: None,
+            require_trailing_digit: false,
+            require_trailing_special: false,
+            class_weights: None,
+            template: Some(r"Word-####-!!Aa\#\\".into()),
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let out: Vec<char> = generate_with_rng(&opts, &mut rng)
+                .unwrap()
+                .chars()
+                .collect();
+            assert_eq!(out.len(), 16);
+            assert_eq!(out[..5].iter().collect::<String>(), "Word-");
+            assert!(out[5..9].iter().all(char::is_ascii_digit));
+            assert_eq!(out[9], '-');
+            assert!(out[10..12].iter().all(|c| SPECIALS.contains(*c)));
+            assert!(out[12].is_ascii_uppercase() && out[13].is_ascii_lowercase());
+            assert_eq!(out[14..].iter().collect::<String>(), r"#\");
+        }
+        let expected =
+            4.0 * 10f64.log2() + 2.0 * (SPECIALS.len() as f64).log2() + 2.0 * 26f64.log2();
+        assert!((opts.entropy_bits() - expected).abs() < 1e-9);
+
+        for bad in ["", "Word", r"a\x", r"a\"] {
+            opts.template = Some(bad.into());
+            assert!(generate_with_rng(&opts, &mut rng).is_err(), "{:?}", bad);
+            assert_eq!(opts.entropy_bits(), 0.0);
+        }
+    }
+
+    #[test]
+    fn min_length_reaches_target_bits() {
+        // The full printable set: 52 letters, 10 digits, 32 symbols
+        assert_eq!(min_length_for_bits(128.0, 94), 20);
+        assert_eq!(min_length_for_bits(64.0, 2), 64);
+        assert_eq!(min_length_for_bits(1.0, 10), 1);
+        assert_eq!(min_length_for_bits(0.0, 94), 0);
+        assert_eq!(min_length_for_bits(128.0, 1), 0);
+        assert_eq!(min_length_for_bits(f64::NAN, 94), 0);
+    }
+
+    #[test]
+    fn custom_wordlist_needs_enough_distinct_words() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.txt");
+        assert!(wordlist::read_wordlist(&path).is_err());
+
+        let short: String = (0..wordlist::MIN_CUSTOM_WORDS - 1)
+            .map(|i| format!("word{}\n", i))
+            .collect();
+        std::fs::write(&path, format!("{}\n  \nword0\n", short)).unwrap();
+        assert!(wordlist::read_wordlist(&path).is_err());
+
+        std::fs::write(&path, format!("{} extra \n", short)).unwrap();
+        let words = wordlist::read_wordlist(&path).unwrap();
+        assert_eq!(words.len(), wordlist::MIN_CUSTOM_WORDS);
+        assert_eq!(words.last().map(String::as_str), Some("extra"));
+    }
+}
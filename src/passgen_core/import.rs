@@ -0,0 +1,284 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use super::app::chrono_timestamp;
+use super::storage::{PasswordEntry, Storage};
+
+/// What to do with an imported entry whose name is already in the vault
+#[derive(PartialEq, Clone, Copy)]
+pub enum MergeStrategy {
+    Skip,
+    Overwrite,
+    /// Keep both, renaming the imported one
+    KeepBoth,
+}
+
+impl MergeStrategy {
+    pub const ALL: [MergeStrategy; 3] = [Self::Skip, Self::Overwrite, Self::KeepBoth];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Skip => "Skip duplicates",
+            Self::Overwrite => "Overwrite existing",
+            Self::KeepBoth => "Keep both (rename imported)",
+        }
+    }
+}
+
+/// How many imported entries ended up in each category
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} skipped",
+            self.added, self.updated, self.skipped
+        )
+    }
+}
+
+/// One row of a CSV export; only name and password are required
+#[derive(Deserialize)]
+struct CsvRow {
+    name: String,
+    password: String,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    notes: String,
+    #[serde(default)]
+    created_at: String,
+}
+
+/// One row of a KeePass CSV export, matched by header so column order doesn't matter
+#[derive(Deserialize)]
+struct KeepassRow {
+    #[serde(rename = "Account")]
+    account: String,
+    #[serde(rename = "Password")]
+    password: String,
+    #[serde(rename = "Login Name", default)]
+    login_name: String,
+    #[serde(rename = "Web Site", default)]
+    web_site: String,
+    #[serde(rename = "Comments", default)]
+    comments: String,
+}
+
+/// Import a JSON array of entries
+pub fn import_json(
+    storage: &Storage,
+    path: &Path,
+    strategy: MergeStrategy,
+) -> Result<ImportReport, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let incoming: Vec<PasswordEntry> =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid JSON: {}", e))?;
+    import_entries(storage, incoming, strategy)
+}
+
+/// Import a CSV file with a `name,password[,url,username,notes,created_at]` header
+pub fn import_csv(
+    storage: &Storage,
+    path: &Path,
+    strategy: MergeStrategy,
+) -> Result<ImportReport, String> {
+    let mut reader =
+        csv::Reader::from_path(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let mut incoming = Vec::new();
+    for row in reader.deserialize::<CsvRow>() {
+        let row = row.map_err(|e| format!("Invalid CSV: {}", e))?;
+        incoming.push(PasswordEntry {
+            name: row.name,
+            password: row.password,
+            created_at: if row.created_at.is_empty() {
+                chrono_timestamp()
+            } else {
+                row.created_at
+            },
+            url: row.url,
+            username: row.username,
+            notes: row.notes,
+            ..Default::default()
+        });
+    }
+    import_entries(storage, incoming, strategy)
+}
+
+/// The file's header row is KeePass's `Account,Login Name,Password,Web Site,Comments`
+pub fn is_keepass_csv(path: &Path) -> bool {
+    csv::Reader::from_path(path)
+        .and_then(|mut reader| reader.headers().cloned())
+        .is_ok_and(|headers| {
+            headers.iter().any(|h| h == "Account") && headers.iter().any(|h| h == "Login Name")
+        })
+}
+
+/// Import a KeePass CSV export. Rows without a password are skipped and
+/// counted as such
+pub fn import_keepass_csv(
+    storage: &Storage,
+    path: &Path,
+    strategy: MergeStrategy,
+) -> Result<ImportReport, String> {
+    let mut reader =
+        csv::Reader::from_path(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let mut incoming = Vec::new();
+    let mut empty = 0;
+    for row in reader.deserialize::<KeepassRow>() {
+        let row = row.map_err(|e| format!("Invalid KeePass CSV: {}", e))?;
+        if row.password.is_empty() {
+            empty += 1;
+            continue;
+        }
+        incoming.push(PasswordEntry {
+            name: row.account,
+            password: row.password,
+            created_at: chrono_timestamp(),
+            url: row.web_site,
+            username: row.login_name,
+            notes: row.comments,
+            ..Default::default()
+        });
+    }
+    let mut report = import_entries(storage, incoming, strategy)?;
+    report.skipped += empty;
+    Ok(report)
+}
+
+fn import_entries(
+    storage: &Storage,
+    incoming: Vec<PasswordEntry>,
+    strategy: MergeStrategy,
+) -> Result<ImportReport, String> {
+    let mut entries = storage.load()?;
+    let report = merge(&mut entries, incoming, strategy);
+    if report.added + report.updated > 0 {
+        storage.save_all(&entries)?;
+    }
+    Ok(report)
+}
+
+/// Fold `incoming` into `entries`, resolving name clashes with `strategy`
+pub fn merge(
+    entries: &mut Vec<PasswordEntry>,
+    incoming: Vec<PasswordEntry>,
+    strategy: MergeStrategy,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+    for mut entry in incoming {
+        let existing = entries.iter().position(|e| e.name == entry.name);
+        match (existing, strategy) {
+            (None, _) => {
+                entries.push(entry);
+                report.added += 1;
+            }
+            (Some(_), MergeStrategy::Skip) => report.skipped += 1,
+            (Some(i), MergeStrategy::Overwrite) => {
+                entries[i] = entry;
+                report.updated += 1;
+            }
+            (Some(_), MergeStrategy::KeepBoth) => {
+                entry.name.push_str(" (imported)");
+                entries.push(entry);
+                report.added += 1;
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, password: &str) -> PasswordEntry {
+        PasswordEntry {
+            name: name.into(),
+            password: password.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_strategies() {
+        let existing = vec![entry("mail", "old"), entry("bank", "old")];
+        let incoming = || vec![entry("mail", "new"), entry("forum", "new")];
+
+        let mut entries = existing.clone();
+        let report = merge(&mut entries, incoming(), MergeStrategy::Skip);
+        assert_eq!((report.added, report.updated, report.skipped), (1, 0, 1));
+        assert_eq!(entries[0].password, "old");
+
+        let mut entries = existing.clone();
+        let report = merge(&mut entries, incoming(), MergeStrategy::Overwrite);
+        assert_eq!((report.added, report.updated, report.skipped), (1, 1, 0));
+        assert_eq!(entries[0].password, "new");
+        assert_eq!(entries.len(), 3);
+
+        let mut entries = existing;
+        let report = merge(&mut entries, incoming(), MergeStrategy::KeepBoth);
+        assert_eq!((report.added, report.updated, report.skipped), (2, 0, 0));
+        assert_eq!(entries[2].name, "mail (imported)");
+    }
+
+    #[test]
+    fn csv_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        let csv = dir.path().join("export.csv");
+        fs::write(
+            &csv,
+            "name,password,url\nmail,\"pa,ss\",mail.example.com\nbank,secret,\n",
+        )
+        .unwrap();
+
+        let report = import_csv(&storage, &csv, MergeStrategy::Skip).unwrap();
+        assert_eq!(report.to_string(), "2 added, 0 updated, 0 skipped");
+        let entries = storage.load().unwrap();
+        assert_eq!(entries[0].password, "pa,ss");
+        assert_eq!(entries[0].url, "mail.example.com");
+
+        let report = import_csv(&storage, &csv, MergeStrategy::Skip).unwrap();
+        assert_eq!(report.skipped, 2);
+    }
+
+    #[test]
+    fn keepass_csv_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        let csv = dir.path().join("keepass.csv");
+        // Columns out of KeePass's usual order still line up by header
+        fs::write(
+            &csv,
+            "Password,Account,Web Site,Login Name,Comments\n\
+             s3cret,mail,mail.example.com,me@example.com,\"two\nlines\"\n\
+             ,bank,bank.example.com,me,\n",
+        )
+        .unwrap();
+        assert!(is_keepass_csv(&csv));
+
+        let report = import_keepass_csv(&storage, &csv, MergeStrategy::Skip).unwrap();
+        assert_eq!(report.to_string(), "1 added, 0 updated, 1 skipped");
+        let entries = storage.load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "mail");
+        assert_eq!(entries[0].password, "s3cret");
+        assert_eq!(entries[0].username, "me@example.com");
+        assert_eq!(entries[0].url, "mail.example.com");
+        assert_eq!(entries[0].notes, "two\nlines");
+
+        let generic = dir.path().join("export.csv");
+        fs::write(&generic, "name,password\nmail,pw\n").unwrap();
+        assert!(!is_keepass_csv(&generic));
+    }
+}
@@ -0,0 +1,195 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable that turns logging on, e.g. `PASSGEN_LOG=debug`
+pub const LOG_ENV: &str = "PASSGEN_LOG";
+
+/// Stored passwords `redact` scrubs; shorter ones would match ordinary words
+const MIN_SECRET_LEN: usize = 6;
+
+/// Shortest run of base64 characters `redact` treats as an encoded blob
+const MIN_BLOB_LEN: usize = 24;
+
+/// Passwords of the vaults loaded since the last lock
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Add loaded passwords to those `redact` looks for
+pub fn remember_secrets<'a>(secrets: impl IntoIterator<Item = &'a str>) {
+    if let Ok(mut known) = SECRETS.lock() {
+        for secret in secrets {
+            if secret.chars().count() >= MIN_SECRET_LEN && !known.iter().any(|k| k == secret) {
+                known.push(secret.to_string());
+            }
+        }
+    }
+}
+
+/// Drop every remembered password, e.g. when the vault is locked
+pub fn forget_secrets() {
+    if let Ok(mut known) = SECRETS.lock() {
+        known.clear();
+    }
+}
+
+/// Scrub remembered passwords and long base64 blobs from a message before it
+/// is shown or logged
+pub fn redact(s: &str) -> String {
+    match SECRETS.lock() {
+        Ok(known) => redact_with(s, &known.iter().map(String::as_str).collect::<Vec<_>>()),
+        Err(_) => redact_with(s, &[]),
+    }
+}
+
+/// `redact` against an explicit list of passwords; ones shorter than
+/// `MIN_SECRET_LEN` are left alone
+pub fn redact_with(s: &str, secrets: &[&str]) -> String {
+    let mut secrets: Vec<&str> = secrets
+        .iter()
+        .copied()
+        .filter(|secret| secret.chars().count() >= MIN_SECRET_LEN)
+        .collect();
+    // Longest first, so a password containing another is replaced whole
+    secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    let mut out = s.to_string();
+    for secret in secrets {
+        out = out.replace(secret, "[redacted]");
+    }
+
+    let is_b64 = |c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_');
+    let mut result = String::with_capacity(out.len());
+    let mut run = String::new();
+    let flush = |run: &mut String, result: &mut String| {
+        let looks_encoded = run.len() >= MIN_BLOB_LEN
+            && run.chars().any(|c| c.is_ascii_digit())
+            && run.chars().any(|c| c.is_ascii_lowercase())
+            && run.chars().any(|c| c.is_ascii_uppercase());
+        result.push_str(if looks_encoded { "[redacted]" } else { run });
+        run.clear();
+    };
+    for c in out.chars() {
+        if is_b64(c) {
+            run.push(c);
+        } else {
+            flush(&mut run, &mut result);
+            result.push(c);
+        }
+    }
+    flush(&mut run, &mut result);
+    result
+}
+
+/// Appends log records to a private file. Callers never pass secrets:
+/// records carry counts, paths and phases, not passwords or keys, and
+/// every record goes through `redact` in case one slips in.
+struct FileLogger {
+    file: Mutex<File>,
+    level: LevelFilter,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}: {}",
+                secs,
+                record.level(),
+                record.target(),
+                redact(&record.args().to_string())
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Where the log goes: beside the config in the home directory, else the working directory
+pub fn log_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".passgen.log")
+}
+
+/// Start logging if `PASSGEN_LOG` names a level; returns the log file in use
+pub fn init() -> Result<Option<PathBuf>, String> {
+    let Some(level) = std::env::var(LOG_ENV).ok().filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let level: LevelFilter = level
+        .parse()
+        .map_err(|_| format!("{} must be error, warn, info, debug or trace", LOG_ENV))?;
+    if level == LevelFilter::Off {
+        return Ok(None);
+    }
+
+    let path = log_path();
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options
+        .open(&path)
+        .map_err(|e| format!("Failed to open log file: {}", e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // An existing log keeps its old mode on open, so tighten it explicitly
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    }
+
+    log::set_boxed_logger(Box::new(FileLogger {
+        file: Mutex::new(file),
+        level,
+    }))
+    .map_err(|e| format!("Failed to start logging: {}", e))?;
+    log::set_max_level(level);
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_scrubs_passwords_and_blobs() {
+        let secrets = ["Tr0ub4dor&3", "1234", "Tr0ub4dor&3xyz"];
+        assert_eq!(
+            redact_with("bad token near 'Tr0ub4dor&3' at 1234", &secrets),
+            "bad token near '[redacted]' at 1234"
+        );
+        assert_eq!(
+            redact_with("got Tr0ub4dor&3xyz", &secrets),
+            "got [redacted]"
+        );
+        assert_eq!(
+            redact_with("nonce c2VjcmV0IHZhdWx0IGJsb2I9PQ== rejected", &[]),
+            "nonce [redacted] rejected"
+        );
+        // Paths and ordinary words are left alone
+        let plain = "Failed to read /home/someone/.passgen_vault.enc";
+        assert_eq!(redact_with(plain, &secrets), plain);
+    }
+}
@@ -1,3 +1,13 @@
 pub mod app;
+pub mod breach;
+pub mod clipboard;
+pub mod config;
+pub mod generator;
+pub mod import;
+pub mod logging;
+pub mod session;
 pub mod storage;
 pub mod ui;
+pub mod wordlist;
+#[cfg(feature = "yubikey")]
+pub mod yubikey;
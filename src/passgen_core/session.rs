@@ -0,0 +1,3521 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use log::debug;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::app::{
+    App, EntryStats, InputField, NotesEditor, ViewMode, ViewerState, chrono_timestamp,
+};
+use super::breach::{check_pwned_local, check_pwned_online};
+use super::clipboard::{clear_clipboard, copy_to_clipboard, type_out_available};
+use super::config::Config;
+use super::generator::generate_password;
+use super::import::{MergeStrategy, import_csv, import_json, import_keepass_csv, is_keepass_csv};
+use super::logging::forget_secrets;
+use super::storage::{
+    AuditReport, Cipher, PasswordEntry, Storage, VAULT_PATH_ENV, allowed_in_name, duplicate_groups,
+    is_expired, name_exists, parse_expiry, sanitize_name,
+};
+
+/// Application phase
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Phase {
+    Onboarding,
+    MasterPassword,
+    Main,
+    ChangeMasterPassword {
+        step: ChangeStep,
+    },
+    MoveVault,
+    ImportPath,
+    ImportStrategy {
+        selected: usize,
+    },
+    /// Choosing which entry of the first pending duplicate group to keep
+    Duplicates {
+        selected: usize,
+    },
+    PresetName,
+    /// Entering a strength in bits to derive the length from
+    TargetBits,
+    /// Entering the seed for insecure seeded generation
+    Seed,
+    ExportPath,
+    ExportPassword,
+    ViewPasswords {
+        mode: ViewMode,
+    },
+    /// Aggregate figures about the vault
+    Stats,
+    /// Entries that need attention, and why
+    Audit,
+    /// Re-entering the master password before the vault is destroyed
+    DestroyVault,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChangeStep {
+    EnterOld,
+    EnterNew,
+    ConfirmNew,
+}
+
+/// Something the user asked for, independent of the key that triggered it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Action {
+    /// Leave the app
+    Quit,
+    /// Forget the unlocked vault and return to the master password prompt
+    Lock,
+    /// Quit or lock even though held changes could not be written
+    DiscardAndQuit,
+    DiscardAndLock,
+    /// Write held vault changes now
+    SaveNow,
+    /// Type a character into whichever field has focus
+    Input(char),
+    /// Delete the last character of the focused field
+    Backspace,
+    /// Accept the current prompt, dialog or edit
+    Confirm,
+    /// Back out of the current prompt, dialog or edit
+    Cancel,
+
+    // Generator screen
+    /// Focus a field directly, from a number key
+    JumpToField(InputField),
+    NextField,
+    PrevField,
+    ToggleField,
+    GenerateAndSave,
+    ChangeMasterPassword,
+    /// Prompt for a new vault location
+    MoveVault,
+    /// Prompt for a JSON or CSV file to import
+    Import,
+    /// Write a copy of the vault under a separate password
+    ExportEncrypted,
+    OpenViewer,
+    /// Re-read the vault into the viewer, keeping the selected entry if it's still there
+    ReloadViewer,
+    /// Show aggregate statistics about the vault
+    OpenStats,
+    /// List weak, reused and expired entries
+    OpenAudit,
+    /// Rewrite the vault without blank fields, under a fresh nonce
+    CompactVault,
+    FixPermissions,
+    /// Look for entries sharing a name and offer to clean them up
+    FindDuplicates,
+    /// Leave the current duplicate group as it is
+    SkipGroup,
+    /// Switch the generator settings to the next saved preset
+    NextPreset,
+    /// Prompt for a name and store the generator settings under it
+    SavePreset,
+    /// Prompt for a target strength and set the length to reach it
+    SuggestLength,
+    /// Prompt for the generation seed (seeded mode only)
+    EnterSeed,
+    ToggleHistory,
+    /// Open or close the recent-errors popup
+    ToggleErrorLog,
+    HistoryPrev,
+    HistoryNext,
+    CopyHistory,
+
+    // Password viewer
+    CloseViewer,
+    SelectPrev,
+    SelectNext,
+    ToggleReveal,
+    RevealTemporarily,
+    RevealAll,
+    HideAll,
+    CopySelected,
+    CopyAndClose,
+    /// Quit, then type the selected password into the terminal
+    TypeAndQuit,
+    /// Copy the selected entry rendered through the configured template
+    CopyFormatted,
+    /// Show every field of the selected entry
+    OpenDetail,
+    /// Look the selected password up in the local breach data
+    CheckBreach,
+    /// Ask to look the selected password up through the HaveIBeenPwned API
+    CheckBreachOnline,
+    CopyUsername,
+    CopyUrl,
+    StartSearch,
+    ClearSearch,
+    /// Switch the list to the next sort column or direction
+    CycleSort,
+    /// Add the selected entry to the batch selection, or take it out again
+    ToggleMark,
+    /// Write an encrypted copy of just the marked entries
+    ExportMarked,
+    /// Ask for confirmation before deleting the selected or marked entries
+    DeleteSelected,
+    /// Delete the selected or marked entries; only ever queued behind a `Confirm`
+    DeleteConfirmed,
+    /// Bring back a quick-deleted entry while its undo window is open
+    UndoDelete,
+    /// Overwrite and delete the vault file, after re-authenticating
+    DestroyVault,
+    DestroyConfirmed,
+    /// Insert a copy of the selected entry right after it
+    DuplicateSelected,
+    EditName,
+    EditPassword,
+    EditUrl,
+    /// Edit every text field of the selected entry in one form
+    EditEntry,
+    /// Open the multi-line notes editor for the selected entry
+    EditNotes,
+    /// Move the selected entry to another vault
+    TransferEntry,
+    OpenUrl,
+    /// Ask for confirmation before showing the selected password as a QR code
+    ShowQr,
+    /// Step through the selected password one character at a time
+    RevealByChar,
+    /// Reveal the previous character
+    CharPrev,
+    /// Reveal the next character
+    CharNext,
+    /// Mask or unmask the password being edited
+    ToggleEditMask,
+    /// Replace the password being edited with a freshly generated one
+    RegeneratePassword,
+}
+
+/// A yes/no question shown over the current screen; `action` is applied on yes
+pub struct Confirm {
+    pub prompt: String,
+    pub action: Action,
+}
+
+impl Confirm {
+    pub fn new(prompt: String, action: Action) -> Self {
+        Self { prompt, action }
+    }
+}
+
+/// Startup settings that stay fixed for the whole session
+#[derive(Default)]
+pub struct SessionOptions {
+    /// Vault location; `None` if the home directory couldn't be found
+    pub vault_path: Option<PathBuf>,
+    pub keyfile: Option<PathBuf>,
+    pub yubikey: bool,
+    pub read_only: bool,
+    /// Cipher for a vault created this session
+    pub cipher: Cipher,
+    /// Offer reproducible generation from a seed, for testing and teaching only
+    pub insecure_seeded: bool,
+}
+
+/// All state behind the TUI, driven one `Action` at a time
+pub struct Session {
+    pub app: App,
+    pub config: Config,
+    pub options: SessionOptions,
+    pub phase: Phase,
+    pub master_input: String,
+    pub storage: Option<Storage>,
+    /// For password change flow
+    pub new_password: String,
+    pub confirm_password: String,
+    /// Text typed into the current plain-text prompt (paths, preset names)
+    pub prompt_input: String,
+    /// For password viewer
+    pub viewer: Option<ViewerState>,
+    /// Duplicate-name groups still to review, as (vault index, entry)
+    pub duplicates: Vec<Vec<(usize, PasswordEntry)>>,
+    /// Figures shown on the statistics screen
+    pub stats: Option<EntryStats>,
+    /// Findings shown on the audit screen
+    pub audit: Option<AuditReport>,
+    /// Entries marked in the viewer, exported instead of the whole vault
+    pub export_only: Option<Vec<PasswordEntry>>,
+    /// Entries deleted so far in this duplicate review
+    duplicates_removed: usize,
+    /// Something was copied this session and may still be on the clipboard
+    pub clipboard_dirty: bool,
+    /// Pending yes/no question; takes all input until answered
+    pub confirm: Option<Confirm>,
+    /// Set once the user asked to leave
+    pub quit: bool,
+    /// Typed into the terminal once the interface has shut down
+    pub type_on_exit: Option<String>,
+    /// Whether typing out can work in this terminal; replaceable for tests
+    pub type_out_check: fn() -> Result<(), String>,
+}
+
+impl Session {
+    pub fn new(config: Config, options: SessionOptions) -> Self {
+        let mut app = App::from_config(&config);
+        app.read_only |= options.read_only;
+        app.seeded = options.insecure_seeded;
+        // First run: explain the app before the vault gets created
+        let phase = match &options.vault_path {
+            Some(path) if !path.exists() && !config.seen_onboarding => Phase::Onboarding,
+            _ => Phase::MasterPassword,
+        };
+        Self {
+            app,
+            config,
+            options,
+            phase,
+            master_input: String::new(),
+            storage: None,
+            new_password: String::new(),
+            confirm_password: String::new(),
+            prompt_input: String::new(),
+            viewer: None,
+            duplicates: Vec::new(),
+            stats: None,
+            audit: None,
+            export_only: None,
+            duplicates_removed: 0,
+            clipboard_dirty: false,
+            confirm: None,
+            quit: false,
+            type_on_exit: None,
+            type_out_check: type_out_available,
+        }
+    }
+
+    /// Wipe what we copied, unless disabled; call before restoring the terminal
+    pub fn clear_clipboard_on_exit(&mut self) {
+        if !self.clipboard_dirty || !self.config.clear_clipboard_on_exit {
+            return;
+        }
+        match clear_clipboard() {
+            Ok(_) => self.clipboard_dirty = false,
+            Err(e) => log::warn!("clipboard not cleared on exit: {}", e),
+        }
+    }
+
+    /// Drop status messages and reveals whose time is up
+    pub fn tick(&mut self) {
+        self.app.expire_status();
+        if let Some(Err(e)) = self.storage.as_ref().and_then(|s| s.flush_if_idle()) {
+            self.app.set_error(format!("Save failed: {}", e));
+        }
+        self.app.dirty = self.has_unsaved_changes();
+        let undo_expired = self.viewer.as_ref().is_some_and(|state| {
+            state
+                .pending_delete
+                .as_ref()
+                .is_some_and(|(_, _, deadline)| Instant::now() >= *deadline)
+        });
+        if undo_expired {
+            self.commit_pending_delete();
+        }
+        if let Some(state) = &mut self.viewer {
+            state.expire_status();
+            state.expire_reveals();
+        }
+    }
+
+    /// Remove a quick-deleted entry from the vault once it can no longer be undone
+    fn commit_pending_delete(&mut self) {
+        let Some(state) = &mut self.viewer else {
+            return;
+        };
+        let Some((index, entry, _)) = state.pending_delete.take() else {
+            return;
+        };
+        if let Some(ref store) = self.storage
+            && let Err(e) = store
+                .check_entries(&[(index, &entry.name)])
+                .and_then(|_| store.delete(index))
+        {
+            state.insert_entry(index, entry);
+            state.set_status(format!("✗ {}", e));
+            self.app.log_error(e.into());
+        }
+    }
+
+    /// Translate a key press into an action for the current phase
+    pub fn map_key(&self, key: KeyEvent) -> Option<Action> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl && key.code == KeyCode::Char('l') && self.is_unlocked() {
+            return Some(Action::Lock);
+        }
+        if self.confirm.is_some() {
+            return match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => Some(Action::Confirm),
+                KeyCode::Char('n') | KeyCode::Esc => Some(Action::Cancel),
+                _ => None,
+            };
+        }
+        let action = match self.phase {
+            Phase::Onboarding => match key.code {
+                KeyCode::Esc => Action::Quit,
+                KeyCode::Enter => Action::Confirm,
+                _ => return None,
+            },
+            Phase::MasterPassword => match key.code {
+                KeyCode::Esc => Action::Quit,
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+            Phase::Main if self.app.show_error_log => match key.code {
+                KeyCode::Esc | KeyCode::F(2) => Action::ToggleErrorLog,
+                KeyCode::Up | KeyCode::Char('k') => Action::SelectPrev,
+                KeyCode::Down | KeyCode::Char('j') => Action::SelectNext,
+                _ => return None,
+            },
+            Phase::Main if self.app.show_history => match key.code {
+                KeyCode::Esc => Action::ToggleHistory,
+                KeyCode::Char('r') if ctrl => Action::ToggleHistory,
+                KeyCode::Up | KeyCode::Char('k') => Action::HistoryPrev,
+                KeyCode::Down | KeyCode::Char('j') => Action::HistoryNext,
+                KeyCode::Enter => Action::CopyHistory,
+                _ => return None,
+            },
+            Phase::Main => match key.code {
+                KeyCode::Char('r') if ctrl => Action::ToggleHistory,
+                KeyCode::F(2) => Action::ToggleErrorLog,
+                KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+                KeyCode::Char('f') if ctrl && self.app.loose_permissions => Action::FixPermissions,
+                KeyCode::Char('p') if ctrl => Action::MoveVault,
+                KeyCode::Char('o') if ctrl => Action::Import,
+                KeyCode::Char('e') if ctrl => Action::ExportEncrypted,
+                KeyCode::Char('d') if ctrl => Action::FindDuplicates,
+                KeyCode::Char('n') if ctrl => Action::NextPreset,
+                KeyCode::Char('w') if ctrl => Action::SavePreset,
+                KeyCode::Char('b') if ctrl => Action::SuggestLength,
+                KeyCode::Char('a') if ctrl => Action::OpenAudit,
+                KeyCode::Char('k') if ctrl => Action::CompactVault,
+                KeyCode::Char('g') if ctrl && self.app.seeded => Action::EnterSeed,
+                KeyCode::Char('x') if ctrl => Action::DestroyVault,
+                KeyCode::Char('s') if ctrl => Action::SaveNow,
+                KeyCode::Char('c') => Action::ChangeMasterPassword,
+                KeyCode::Char('v') => Action::OpenViewer,
+                // Plain letters and digits only when they can't be meant as text
+                KeyCode::Char('i')
+                    if key.modifiers.contains(KeyModifiers::ALT)
+                        || !self.app.active_field.is_text() =>
+                {
+                    Action::OpenStats
+                }
+                KeyCode::Char(c @ '1'..='6')
+                    if key.modifiers.contains(KeyModifiers::ALT)
+                        || !self.app.active_field.is_text() =>
+                {
+                    Action::JumpToField(InputField::from_number(c.to_digit(10)?)?)
+                }
+                KeyCode::Tab | KeyCode::Down => Action::NextField,
+                KeyCode::BackTab | KeyCode::Up => Action::PrevField,
+                KeyCode::Enter => Action::GenerateAndSave,
+                KeyCode::Char(' ') => Action::ToggleField,
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+            Phase::Duplicates { .. } => match key.code {
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Char('s') | KeyCode::Right => Action::SkipGroup,
+                KeyCode::Up | KeyCode::Char('k') => Action::SelectPrev,
+                KeyCode::Down | KeyCode::Char('j') => Action::SelectNext,
+                _ => return None,
+            },
+            Phase::Stats => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('i') => Action::Cancel,
+                _ => return None,
+            },
+            Phase::Audit => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => Action::Cancel,
+                KeyCode::Char('a') if ctrl => Action::Cancel,
+                _ => return None,
+            },
+            Phase::ImportStrategy { .. } => match key.code {
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Up | KeyCode::Char('k') => Action::SelectPrev,
+                KeyCode::Down | KeyCode::Char('j') => Action::SelectNext,
+                _ => return None,
+            },
+            Phase::ChangeMasterPassword { .. }
+            | Phase::MoveVault
+            | Phase::ImportPath
+            | Phase::ExportPath
+            | Phase::ExportPassword
+            | Phase::DestroyVault
+            | Phase::PresetName
+            | Phase::TargetBits
+            | Phase::Seed => match key.code {
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+            Phase::ViewPasswords { mode } => self.map_viewer_key(mode, key)?,
+        };
+        Some(action)
+    }
+
+    fn map_viewer_key(&self, mode: ViewMode, key: KeyEvent) -> Option<Action> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let filtering = self
+            .viewer
+            .as_ref()
+            .is_some_and(|state| !state.search_query.is_empty());
+        let undoable = self
+            .viewer
+            .as_ref()
+            .is_some_and(|state| state.pending_delete.is_some());
+        let action = match mode {
+            ViewMode::Browse => match key.code {
+                // First Esc drops the active filter
+                KeyCode::Esc if filtering => Action::ClearSearch,
+                KeyCode::Char('/') => Action::StartSearch,
+                KeyCode::Char('s') if ctrl => Action::SaveNow,
+                KeyCode::Char('s') => Action::CycleSort,
+                KeyCode::Esc | KeyCode::Char('q') => Action::CloseViewer,
+                KeyCode::Up | KeyCode::Char('k') => Action::SelectPrev,
+                KeyCode::Down | KeyCode::Char('j') => Action::SelectNext,
+                KeyCode::Enter => Action::ToggleReveal,
+                KeyCode::Char(' ') => Action::ToggleMark,
+                KeyCode::Char('X') => Action::ExportMarked,
+                KeyCode::Char('t') => Action::RevealTemporarily,
+                KeyCode::Char('i') => Action::RevealByChar,
+                KeyCode::Char('r') => Action::RevealAll,
+                // Shifted to avoid conflict with vim left
+                KeyCode::Char('H') => Action::HideAll,
+                KeyCode::Char('y') => Action::CopySelected,
+                KeyCode::Char('Y') => Action::CopyAndClose,
+                KeyCode::Char('T') => Action::TypeAndQuit,
+                KeyCode::Char('c') => Action::CopyFormatted,
+                KeyCode::Char('v') => Action::OpenDetail,
+                KeyCode::Char('B') => Action::CheckBreach,
+                KeyCode::Char('N') => Action::CheckBreachOnline,
+                KeyCode::Char('d') => Action::DeleteSelected,
+                KeyCode::Char('D') => Action::DuplicateSelected,
+                KeyCode::Char('e') => Action::EditName,
+                KeyCode::Char('p') => Action::EditPassword,
+                KeyCode::Char('u') if undoable => Action::UndoDelete,
+                KeyCode::Char('u') => Action::EditUrl,
+                KeyCode::Char('E') => Action::EditEntry,
+                KeyCode::Char('n') => Action::EditNotes,
+                KeyCode::Char('M') => Action::TransferEntry,
+                KeyCode::Char('Q') => Action::ShowQr,
+                KeyCode::Char('O') => Action::OpenUrl,
+                KeyCode::Char('R') => Action::ReloadViewer,
+                _ => return None,
+            },
+            ViewMode::Search => match key.code {
+                KeyCode::Esc => Action::ClearSearch,
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+            ViewMode::BreachReport => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Action::Cancel,
+                _ => return None,
+            },
+            ViewMode::ConfirmQr | ViewMode::ConfirmNetwork | ViewMode::ConfirmRename => {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => Action::Confirm,
+                    KeyCode::Char('n') | KeyCode::Esc => Action::Cancel,
+                    _ => return None,
+                }
+            }
+            ViewMode::ShowQr => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => Action::Cancel,
+                _ => return None,
+            },
+            ViewMode::Detail => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('v') => Action::Cancel,
+                KeyCode::Char('u') => Action::CopyUsername,
+                KeyCode::Char('p') => Action::CopySelected,
+                KeyCode::Char('l') => Action::CopyUrl,
+                KeyCode::Enter | KeyCode::Char(' ') => Action::ToggleReveal,
+                _ => return None,
+            },
+            ViewMode::RevealChar => match key.code {
+                KeyCode::Esc | KeyCode::Char('i') | KeyCode::Enter => Action::Cancel,
+                KeyCode::Left | KeyCode::Char('h') => Action::CharPrev,
+                KeyCode::Right | KeyCode::Char('l') => Action::CharNext,
+                _ => return None,
+            },
+            ViewMode::TransferPath | ViewMode::TransferPassword => match key.code {
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+            ViewMode::EditNotes => match key.code {
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Char('s') if ctrl => Action::Confirm,
+                KeyCode::Enter => Action::Input('\n'),
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Up => Action::SelectPrev,
+                KeyCode::Down => Action::SelectNext,
+                KeyCode::Left => Action::CharPrev,
+                KeyCode::Right => Action::CharNext,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+            ViewMode::EditEntry => match key.code {
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Tab | KeyCode::Down => Action::NextField,
+                KeyCode::BackTab | KeyCode::Up => Action::PrevField,
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+            ViewMode::EditName | ViewMode::EditPassword | ViewMode::EditUrl => match key.code {
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Char('g') if ctrl && mode == ViewMode::EditPassword => {
+                    Action::RegeneratePassword
+                }
+                KeyCode::Char('r') if ctrl && mode == ViewMode::EditPassword => {
+                    Action::ToggleEditMask
+                }
+                KeyCode::Enter => Action::Confirm,
+                KeyCode::Backspace => Action::Backspace,
+                KeyCode::Char(c) => Action::Input(c),
+                _ => return None,
+            },
+        };
+        Some(action)
+    }
+
+    /// Whether applying `action` will wait on the online breach check
+    pub fn contacts_network(&self, action: Action) -> bool {
+        action == Action::Confirm
+            && self.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::ConfirmNetwork,
+                }
+    }
+
+    /// Whether applying `action` will run the (deliberately slow) key derivation
+    pub fn derives_key(&self, action: Action) -> bool {
+        if action != Action::Confirm {
+            return false;
+        }
+        match self.phase {
+            Phase::MasterPassword => !self.master_input.is_empty(),
+            Phase::ExportPassword => !self.new_password.is_empty(),
+            Phase::DestroyVault => !self.master_input.is_empty(),
+            Phase::ViewPasswords {
+                mode: ViewMode::TransferPassword,
+            } => self
+                .viewer
+                .as_ref()
+                .is_some_and(|state| !state.edit_buffer.is_empty()),
+            Phase::ChangeMasterPassword { step } => match step {
+                ChangeStep::EnterOld => true,
+                ChangeStep::EnterNew => false,
+                ChangeStep::ConfirmNew => self.confirm_password == self.new_password,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether a vault is open and the user is past every password prompt
+    pub fn is_unlocked(&self) -> bool {
+        self.storage.is_some()
+            && matches!(
+                self.phase,
+                Phase::Main
+                    | Phase::ViewPasswords { .. }
+                    | Phase::ImportStrategy { .. }
+                    | Phase::Duplicates { .. }
+                    | Phase::Stats
+                    | Phase::Audit
+            )
+    }
+
+    /// Drop the vault and everything sensitive, back to the master password prompt
+    pub fn lock(&mut self) {
+        forget_secrets();
+        self.storage = None;
+        self.viewer = None;
+        self.duplicates.clear();
+        self.stats = None;
+        self.audit = None;
+        self.export_only = None;
+        self.master_input.clear();
+        self.new_password.clear();
+        self.confirm_password.clear();
+        self.prompt_input.clear();
+        self.confirm = None;
+        self.app.generated_password = None;
+        self.app.recent.clear();
+        self.app.show_history = false;
+        self.app.error = None;
+        self.app.status_message.clear();
+        self.phase = Phase::MasterPassword;
+    }
+
+    /// Whether vault changes are held in memory, not yet written
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.storage
+            .as_ref()
+            .is_some_and(|store| store.has_unsaved_changes())
+    }
+
+    /// Write held changes now. If that fails, show why and ask whether to
+    /// run `discard` anyway; false means the caller must not go ahead
+    fn flush_unsaved(&mut self, discard: Action) -> bool {
+        let Some(store) = &self.storage else {
+            return true;
+        };
+        match store.flush() {
+            Ok(()) => true,
+            Err(e) => {
+                self.app
+                    .set_error(format!("Save failed, unsaved changes kept: {}", e));
+                let verb = if discard == Action::DiscardAndQuit {
+                    "Quit"
+                } else {
+                    "Lock"
+                };
+                self.confirm = Some(Confirm::new(
+                    format!(
+                        "Unsaved changes could not be written. {} and lose them?",
+                        verb
+                    ),
+                    discard,
+                ));
+                false
+            }
+        }
+    }
+
+    /// Carry out an action against the current state
+    pub fn apply(&mut self, action: Action) {
+        // Any other action closes the undo window of a quick delete
+        if action != Action::UndoDelete {
+            self.commit_pending_delete();
+        }
+        let before = self.phase;
+        self.dispatch(action);
+        self.app.dirty = self.has_unsaved_changes();
+        if let Some(e) = self
+            .storage
+            .as_ref()
+            .and_then(|store| store.take_after_save_error())
+        {
+            match &mut self.viewer {
+                Some(state) => state.set_status(format!("⚠ Saved; {}", e)),
+                None => self.app.set_status(format!("⚠ Saved; {}", e)),
+            }
+            self.app.log_error(e);
+        }
+        if self.phase != before {
+            debug!("phase {:?} -> {:?}", before, self.phase);
+        }
+    }
+
+    fn dispatch(&mut self, action: Action) {
+        match action {
+            Action::Quit => {
+                if self.flush_unsaved(Action::DiscardAndQuit) {
+                    self.quit = true;
+                }
+                return;
+            }
+            Action::Lock => {
+                if self.flush_unsaved(Action::DiscardAndLock) {
+                    self.lock();
+                }
+                return;
+            }
+            Action::DiscardAndQuit => {
+                if let Some(ref store) = self.storage {
+                    store.discard_pending();
+                }
+                self.quit = true;
+                return;
+            }
+            Action::DiscardAndLock => {
+                if let Some(ref store) = self.storage {
+                    store.discard_pending();
+                }
+                self.lock();
+                return;
+            }
+            Action::SaveNow if self.phase == Phase::Main && self.app.pending_save => {
+                save_generated(self.storage.as_ref(), &mut self.app);
+                return;
+            }
+            Action::SaveNow => {
+                let status = match self.storage.as_ref().map(|s| s.flush()) {
+                    Some(Err(e)) => {
+                        self.app.log_error(format!("Save failed: {}", e));
+                        format!("✗ Save failed: {}", e)
+                    }
+                    _ => "✓ All changes saved".to_string(),
+                };
+                match &mut self.viewer {
+                    Some(state) => state.set_status(status),
+                    None => self.app.set_status(status),
+                }
+                return;
+            }
+            _ => {}
+        }
+        // An open question answers to yes/no only, then runs what it guarded
+        if let Some(confirm) = self.confirm.take() {
+            match action {
+                Action::Confirm => self.dispatch(confirm.action),
+                Action::Cancel => {}
+                _ => self.confirm = Some(confirm),
+            }
+            return;
+        }
+        match self.phase {
+            Phase::Onboarding => self.apply_onboarding(action),
+            Phase::MasterPassword => self.apply_unlock(action),
+            Phase::Main => self.apply_main(action),
+            Phase::ChangeMasterPassword { step } => self.apply_change(step, action),
+            Phase::MoveVault => self.apply_move(action),
+            Phase::ImportPath => self.apply_import_path(action),
+            Phase::ImportStrategy { selected } => self.apply_import(selected, action),
+            Phase::Duplicates { selected } => self.apply_duplicates(selected, action),
+            Phase::ExportPath => self.apply_export_path(action),
+            Phase::ExportPassword => self.apply_export(action),
+            Phase::DestroyVault => self.apply_destroy(action),
+            Phase::PresetName => self.apply_preset_name(action),
+            Phase::TargetBits => self.apply_target_bits(action),
+            Phase::Seed => self.apply_seed(action),
+            Phase::ViewPasswords { mode } => self.apply_viewer(mode, action),
+            Phase::Stats => {
+                if action == Action::Cancel {
+                    self.stats = None;
+                    self.phase = Phase::Main;
+                }
+            }
+            Phase::Audit => {
+                if action == Action::Cancel {
+                    self.audit = None;
+                    self.phase = Phase::Main;
+                }
+            }
+        }
+    }
+
+    /// Open the vault, mixing in any second factors that were requested
+    fn open_storage(&self, master_password: &str) -> Result<Storage, String> {
+        let path = self.options.vault_path.as_deref().ok_or_else(|| {
+            format!(
+                "Cannot find home directory; set {} to the vault file",
+                VAULT_PATH_ENV
+            )
+        })?;
+        let mut storage = Storage::new_with_factors_at(
+            master_password,
+            path,
+            self.options.keyfile.as_deref(),
+            self.options.yubikey,
+            self.options.cipher,
+        )?;
+        storage.set_post_save_command(self.config.post_save_command.clone());
+        storage.set_metadata_manifest(self.config.metadata_manifest.clone());
+        storage.set_save_debounce(
+            (self.config.save_debounce_ms > 0)
+                .then(|| Duration::from_millis(self.config.save_debounce_ms)),
+        );
+        Ok(storage)
+    }
+
+    fn apply_onboarding(&mut self, action: Action) {
+        if action == Action::Confirm {
+            self.config.seen_onboarding = true;
+            if let Err(e) = self.config.save() {
+                self.app.set_error(e);
+            }
+            self.phase = Phase::MasterPassword;
+        }
+    }
+
+    fn apply_unlock(&mut self, action: Action) {
+        match action {
+            Action::Confirm => {
+                if self.master_input.is_empty() {
+                    return;
+                }
+                match self.open_storage(&self.master_input) {
+                    Ok(s) => {
+                        self.app.loose_permissions = s.permissions_warning().is_some();
+                        self.app.error = s
+                            .permissions_warning()
+                            .map(|w| format!("{} — press [Ctrl+F] to fix", w));
+                        let expired = s
+                            .load()
+                            .map(|entries| entries.iter().filter(|e| is_expired(e)).count())
+                            .unwrap_or(0);
+                        match expired {
+                            0 => {}
+                            1 => self.app.set_status("⚠ 1 password has expired".into()),
+                            n => self
+                                .app
+                                .set_status(format!("⚠ {} passwords have expired", n)),
+                        }
+                        self.storage = Some(s);
+                        self.phase = Phase::Main;
+                    }
+                    Err(e) => self.app.set_error(e),
+                }
+                self.master_input.clear();
+            }
+            Action::Backspace => {
+                self.master_input.pop();
+            }
+            Action::Input(c) => self.master_input.push(c),
+            _ => {}
+        }
+    }
+
+    fn apply_main(&mut self, action: Action) {
+        let app = &mut self.app;
+        match action {
+            Action::ToggleHistory => app.toggle_history(),
+            Action::ToggleErrorLog => app.toggle_error_log(),
+            Action::SelectPrev if app.show_error_log => app.error_log_prev(),
+            Action::SelectNext if app.show_error_log => app.error_log_next(),
+            Action::HistoryPrev => app.history_prev(),
+            Action::HistoryNext => app.history_next(),
+            Action::CopyHistory => {
+                if let Some(pwd) = app.recent.get(app.history_selected) {
+                    match copy_to_clipboard(pwd) {
+                        Ok(channel) => {
+                            self.clipboard_dirty = true;
+                            app.set_status(channel.status("password"));
+                        }
+                        Err(e) => app.set_status(format!("✗ {}", e)),
+                    }
+                    app.toggle_history();
+                }
+            }
+            Action::FixPermissions => {
+                if let Some(ref store) = self.storage {
+                    match store.fix_permissions() {
+                        Ok(_) => {
+                            app.loose_permissions = false;
+                            app.error = None;
+                            app.set_status("✓ Vault permissions set to 600".into());
+                        }
+                        Err(e) => app.set_error(e),
+                    }
+                }
+            }
+            Action::CompactVault if app.read_only => {
+                app.set_status("Read-only mode: the vault can't be compacted".into());
+            }
+            Action::CompactVault => {
+                if let Some(ref store) = self.storage {
+                    match store.compact() {
+                        Ok(report) => {
+                            app.error = None;
+                            app.set_status(format!(
+                                "✓ Vault compacted: {} bytes saved",
+                                report.bytes_saved()
+                            ));
+                        }
+                        Err(e) => app.set_error(format!("Compact failed: {}", e)),
+                    }
+                }
+            }
+            Action::ChangeMasterPassword if app.read_only => {
+                app.set_status("Read-only mode: master password can't be changed".into());
+            }
+            Action::ChangeMasterPassword => {
+                self.phase = Phase::ChangeMasterPassword {
+                    step: ChangeStep::EnterOld,
+                };
+                self.master_input.clear();
+                self.new_password.clear();
+                self.confirm_password.clear();
+                app.error = None;
+                app.status_message.clear();
+            }
+            Action::MoveVault if app.read_only => {
+                app.set_status("Read-only mode: vault can't be moved".into());
+            }
+            Action::MoveVault => {
+                self.phase = Phase::MoveVault;
+                self.prompt_input.clear();
+                app.error = None;
+            }
+            Action::FindDuplicates if app.read_only => {
+                app.set_status("Read-only mode: duplicates can't be removed".into());
+            }
+            Action::FindDuplicates => {
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                match store.load() {
+                    Ok(entries) => {
+                        self.duplicates = duplicate_groups(&entries)
+                            .into_iter()
+                            .map(|group| {
+                                group.into_iter().map(|i| (i, entries[i].clone())).collect()
+                            })
+                            .collect();
+                        if self.duplicates.is_empty() {
+                            app.set_status("✓ No duplicate names".into());
+                        } else {
+                            app.error = None;
+                            self.phase = Phase::Duplicates { selected: 0 };
+                        }
+                    }
+                    Err(e) => app.set_error(e),
+                }
+            }
+            Action::Import if app.read_only => {
+                app.set_status("Read-only mode: nothing can be imported".into());
+            }
+            Action::Import => {
+                self.phase = Phase::ImportPath;
+                self.prompt_input.clear();
+                app.error = None;
+            }
+            Action::NextPreset => {
+                let presets = &self.config.presets;
+                if presets.is_empty() {
+                    app.set_status("No presets yet — press [Ctrl+W] to save one".into());
+                } else {
+                    let next = app
+                        .active_preset
+                        .as_ref()
+                        .and_then(|name| presets.iter().position(|p| &p.name == name))
+                        .map_or(0, |i| (i + 1) % presets.len());
+                    app.apply_preset(&presets[next]);
+                    app.set_status(format!("Preset: {}", presets[next].name));
+                }
+            }
+            Action::EnterSeed => {
+                self.phase = Phase::Seed;
+                self.prompt_input = app.seed.map(|seed| seed.to_string()).unwrap_or_default();
+                app.error = None;
+            }
+            Action::SuggestLength => {
+                self.phase = Phase::TargetBits;
+                self.prompt_input.clear();
+                app.error = None;
+            }
+            Action::SavePreset => {
+                self.phase = Phase::PresetName;
+                self.prompt_input = app.active_preset.clone().unwrap_or_default();
+                app.error = None;
+            }
+            Action::DestroyVault if app.read_only => {
+                app.set_status("Read-only mode: the vault cannot be destroyed".into());
+            }
+            Action::DestroyVault => {
+                self.phase = Phase::DestroyVault;
+                self.master_input.clear();
+                app.error = None;
+            }
+            Action::ExportEncrypted => {
+                self.phase = Phase::ExportPath;
+                self.export_only = None;
+                self.prompt_input.clear();
+                self.new_password.clear();
+                app.error = None;
+            }
+            Action::OpenStats => {
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                match store.load() {
+                    Ok(entries) => {
+                        self.stats = Some(EntryStats::compute(&entries));
+                        self.phase = Phase::Stats;
+                        app.error = None;
+                    }
+                    Err(e) => app.set_error(format!("Failed to load: {}", e)),
+                }
+            }
+            Action::OpenAudit => {
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                match store.audit() {
+                    Ok(report) => {
+                        self.audit = Some(report);
+                        self.phase = Phase::Audit;
+                        app.error = None;
+                    }
+                    Err(e) => app.set_error(format!("Failed to load: {}", e)),
+                }
+            }
+            Action::OpenViewer => {
+                if let Some(ref store) = self.storage {
+                    match store.load() {
+                        Ok(entries) => {
+                            // Restore the last-viewed entry if it still exists
+                            let selected = self
+                                .config
+                                .last_selected
+                                .as_ref()
+                                .filter(|_| self.config.remember_selection)
+                                .and_then(|name| entries.iter().position(|e| &e.name == name))
+                                .unwrap_or(0);
+                            self.viewer = Some(ViewerState::new(entries, selected));
+                            self.phase = Phase::ViewPasswords {
+                                mode: ViewMode::Browse,
+                            };
+                            app.error = None;
+                        }
+                        Err(e) => app.set_error(format!("Failed to load: {}", e)),
+                    }
+                }
+            }
+            Action::JumpToField(field) => app.active_field = field,
+            Action::NextField => app.next_field(self.config.wrap_navigation),
+            Action::PrevField => app.prev_field(self.config.wrap_navigation),
+            Action::GenerateAndSave => {
+                app.generate();
+                // Auto-save if generation succeeded, unless saving is explicit
+                if app.seeded && app.generated_password.is_some() {
+                    app.set_status("Seeded passwords are never saved".into());
+                } else if app.read_only && app.generated_password.is_some() {
+                    app.set_status("Read-only mode: not saved".into());
+                } else if app.explicit_save && app.generated_password.is_some() {
+                    app.pending_save = true;
+                    app.set_status("Press Ctrl+S to save this password".into());
+                } else {
+                    save_generated(self.storage.as_ref(), app);
+                }
+            }
+            Action::ToggleField => app.toggle_current(),
+            Action::Backspace => {
+                if let Some(input) = app.current_text_input() {
+                    input.pop();
+                }
+            }
+            // Control and invisible characters never belong in a name
+            Action::Input(c) if app.active_field == InputField::Name && !allowed_in_name(c) => {}
+            Action::Input(c) => {
+                if let Some(input) = app.current_text_input() {
+                    input.push(c);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_change(&mut self, step: ChangeStep, action: Action) {
+        let input = match step {
+            ChangeStep::EnterOld => &mut self.master_input,
+            ChangeStep::EnterNew => &mut self.new_password,
+            ChangeStep::ConfirmNew => &mut self.confirm_password,
+        };
+        match action {
+            Action::Backspace => {
+                input.pop();
+            }
+            Action::Input(c) => input.push(c),
+            Action::Cancel => {
+                // Cancel and go back to main
+                self.phase = Phase::Main;
+                self.master_input.clear();
+                self.new_password.clear();
+                self.confirm_password.clear();
+                self.app.error = None;
+            }
+            Action::Confirm => match step {
+                ChangeStep::EnterOld => {
+                    // Held edits must reach the file before it is reopened
+                    if let Some(Err(e)) = self.storage.as_ref().map(|s| s.flush()) {
+                        self.app
+                            .set_error(format!("Save failed, unsaved changes kept: {}", e));
+                        return;
+                    }
+                    // Verify old password by trying to load
+                    let opened = self
+                        .open_storage(&self.master_input)
+                        .and_then(|s| s.load().map(|_| s));
+                    match opened {
+                        Ok(s) => {
+                            self.storage = Some(s);
+                            self.phase = Phase::ChangeMasterPassword {
+                                step: ChangeStep::EnterNew,
+                            };
+                            self.app.error = None;
+                        }
+                        Err(e) => {
+                            self.app.set_error(e);
+                            self.master_input.clear();
+                        }
+                    }
+                }
+                ChangeStep::EnterNew => {
+                    if self.new_password.is_empty() {
+                        self.app.set_error("Password cannot be empty".into());
+                    } else {
+                        self.phase = Phase::ChangeMasterPassword {
+                            step: ChangeStep::ConfirmNew,
+                        };
+                        self.app.error = None;
+                    }
+                }
+                ChangeStep::ConfirmNew => {
+                    if self.confirm_password != self.new_password {
+                        self.app.set_error("Passwords don't match".into());
+                        self.confirm_password.clear();
+                    } else if let Some(ref store) = self.storage {
+                        match store.change_master_password(&self.new_password) {
+                            Ok(new_store) => {
+                                let verified = new_store.count();
+                                self.storage = Some(new_store);
+                                self.app.error = None;
+                                match verified {
+                                    Ok(count) => self.app.set_status(format!(
+                                        "✓ Master password changed — verified: {} entries re-keyed",
+                                        count
+                                    )),
+                                    Err(e) => self.app.set_error(format!(
+                                        "Master password changed, but the vault did not verify: {}",
+                                        e
+                                    )),
+                                }
+                                self.phase = Phase::Main;
+                                self.master_input.clear();
+                                self.new_password.clear();
+                                self.confirm_password.clear();
+                            }
+                            Err(e) => self.app.set_error(format!("Failed: {}", e)),
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn apply_move(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.prompt_input.pop();
+            }
+            Action::Input(c) => self.prompt_input.push(c),
+            Action::Cancel => self.phase = Phase::Main,
+            Action::Confirm => {
+                let input = self.prompt_input.trim();
+                if input.is_empty() {
+                    return;
+                }
+                let new_path = expand_path(input);
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                match store.move_to(&new_path) {
+                    Ok(moved) => {
+                        self.storage = Some(moved);
+                        self.config.vault_path = Some(new_path.clone());
+                        if let Err(e) = self.config.save() {
+                            self.app.set_error(e);
+                        }
+                        self.app
+                            .set_status(format!("✓ Vault moved to {}", new_path.display()));
+                        self.options.vault_path = Some(new_path);
+                        self.phase = Phase::Main;
+                    }
+                    Err(e) => self.app.set_error(e),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_import_path(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.prompt_input.pop();
+            }
+            Action::Input(c) => self.prompt_input.push(c),
+            Action::Cancel => self.phase = Phase::Main,
+            Action::Confirm if !self.prompt_input.trim().is_empty() => {
+                if expand_path(self.prompt_input.trim()).is_file() {
+                    self.app.error = None;
+                    self.phase = Phase::ImportStrategy { selected: 0 };
+                } else {
+                    self.app
+                        .set_error(format!("No such file: {}", self.prompt_input.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_import(&mut self, selected: usize, action: Action) {
+        match action {
+            Action::SelectPrev => {
+                self.phase = Phase::ImportStrategy {
+                    selected: selected.saturating_sub(1),
+                }
+            }
+            Action::SelectNext => {
+                self.phase = Phase::ImportStrategy {
+                    selected: (selected + 1).min(MergeStrategy::ALL.len() - 1),
+                }
+            }
+            Action::Cancel => self.phase = Phase::Main,
+            Action::Confirm => {
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                let path = expand_path(self.prompt_input.trim());
+                let strategy = MergeStrategy::ALL[selected];
+                let is_csv = path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+                let result = if is_csv && is_keepass_csv(&path) {
+                    import_keepass_csv(store, &path, strategy)
+                } else if is_csv {
+                    import_csv(store, &path, strategy)
+                } else {
+                    import_json(store, &path, strategy)
+                };
+                match result {
+                    Ok(report) => self.app.set_status(format!("✓ Imported: {}", report)),
+                    Err(e) => self.app.set_error(format!("Import failed: {}", e)),
+                }
+                self.phase = Phase::Main;
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_duplicates(&mut self, selected: usize, action: Action) {
+        let Some(group_len) = self.duplicates.first().map(Vec::len) else {
+            return self.finish_duplicates();
+        };
+        match action {
+            Action::SelectPrev => {
+                self.phase = Phase::Duplicates {
+                    selected: selected.saturating_sub(1),
+                }
+            }
+            Action::SelectNext => {
+                self.phase = Phase::Duplicates {
+                    selected: (selected + 1).min(group_len - 1),
+                }
+            }
+            Action::Cancel => self.finish_duplicates(),
+            Action::SkipGroup => self.next_duplicate_group(),
+            Action::Confirm => {
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                let doomed: Vec<usize> = self.duplicates[0]
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != selected)
+                    .map(|(_, &(index, _))| index)
+                    .collect();
+                if let Err(e) = store.delete_many(&doomed) {
+                    self.app.set_error(e.into());
+                    self.finish_duplicates();
+                    return;
+                }
+                self.duplicates_removed += doomed.len();
+                // Later groups point past the deleted entries; shift them back
+                for (index, _) in self.duplicates.iter_mut().skip(1).flatten() {
+                    *index -= doomed.iter().filter(|&&d| d < *index).count();
+                }
+                self.next_duplicate_group();
+            }
+            _ => {}
+        }
+    }
+
+    /// Drop the current duplicate group and show the next, or finish when none are left
+    fn next_duplicate_group(&mut self) {
+        if !self.duplicates.is_empty() {
+            self.duplicates.remove(0);
+        }
+        if self.duplicates.is_empty() {
+            self.finish_duplicates();
+        } else {
+            self.phase = Phase::Duplicates { selected: 0 };
+        }
+    }
+
+    fn finish_duplicates(&mut self) {
+        if self.duplicates_removed > 0 {
+            self.app.set_status(format!(
+                "✓ Removed {} duplicate entries",
+                self.duplicates_removed
+            ));
+        }
+        self.duplicates.clear();
+        self.duplicates_removed = 0;
+        self.phase = Phase::Main;
+    }
+
+    fn apply_seed(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.prompt_input.pop();
+            }
+            Action::Input(c) => self.prompt_input.push(c),
+            Action::Cancel => self.phase = Phase::Main,
+            Action::Confirm => {
+                let input = self.prompt_input.trim();
+                if input.is_empty() {
+                    self.app.seed = None;
+                    self.app
+                        .set_status("Seed cleared; each password gets a random seed".into());
+                } else if let Ok(seed) = input.parse() {
+                    self.app.seed = Some(seed);
+                    self.app.set_status(format!("Seed set to {}", seed));
+                } else {
+                    self.app.set_error("Seed must be a whole number".into());
+                    return;
+                }
+                self.app.error = None;
+                self.phase = Phase::Main;
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_target_bits(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.prompt_input.pop();
+            }
+            Action::Input(c) => self.prompt_input.push(c),
+            Action::Cancel => self.phase = Phase::Main,
+            Action::Confirm if !self.prompt_input.trim().is_empty() => {
+                let Ok(bits) = self.prompt_input.trim().parse::<f64>() else {
+                    self.app
+                        .set_error("Enter a number of bits, e.g. 128".into());
+                    return;
+                };
+                match self.app.suggest_length(bits) {
+                    Ok(length) => {
+                        let reached = self
+                            .app
+                            .gen_options()
+                            .map(|opts| opts.entropy_bits())
+                            .unwrap_or_default();
+                        self.app
+                            .set_status(format!("✓ Length {} gives ~{:.0} bits", length, reached));
+                        self.app.error = None;
+                        self.phase = Phase::Main;
+                    }
+                    Err(e) => self.app.set_error(e),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_preset_name(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.prompt_input.pop();
+            }
+            Action::Input(c) => self.prompt_input.push(c),
+            Action::Cancel => self.phase = Phase::Main,
+            Action::Confirm if !self.prompt_input.trim().is_empty() => {
+                let name = self.prompt_input.trim().to_string();
+                match self.app.current_preset(&name) {
+                    Ok(preset) => {
+                        // Saving under an existing name replaces that preset
+                        let presets = &mut self.config.presets;
+                        match presets.iter_mut().find(|p| p.name == name) {
+                            Some(existing) => *existing = preset,
+                            None => presets.push(preset),
+                        }
+                        match self.config.save() {
+                            Ok(_) => self.app.set_status(format!("✓ Saved preset '{}'", name)),
+                            Err(e) => self.app.set_error(e),
+                        }
+                        self.app.active_preset = Some(name);
+                        self.phase = Phase::Main;
+                    }
+                    Err(e) => self.app.set_error(e),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_export_path(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.prompt_input.pop();
+            }
+            Action::Input(c) => self.prompt_input.push(c),
+            Action::Cancel => self.finish_export(),
+            Action::Confirm if !self.prompt_input.trim().is_empty() => {
+                if expand_path(self.prompt_input.trim()).exists() {
+                    self.app
+                        .set_error(format!("{} already exists", self.prompt_input.trim()));
+                } else {
+                    self.app.error = None;
+                    self.phase = Phase::ExportPassword;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_export(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.new_password.pop();
+            }
+            Action::Input(c) => self.new_password.push(c),
+            Action::Cancel => self.finish_export(),
+            Action::Confirm if !self.new_password.is_empty() => {
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                let dest = expand_path(self.prompt_input.trim());
+                let result = match self.export_only {
+                    Some(ref entries) => {
+                        store.export_entries_encrypted(&dest, &self.new_password, entries)
+                    }
+                    None => store.export_encrypted(&dest, &self.new_password),
+                };
+                let written = format!("✓ Encrypted export written to {}", dest.display());
+                match (result, &mut self.viewer) {
+                    (Ok(_), Some(state)) => state.set_status(written),
+                    (Err(e), Some(state)) => state.set_status(format!("✗ Export failed: {}", e)),
+                    (Ok(_), None) => self.app.set_status(written),
+                    (Err(e), None) => self.app.set_error(format!("Export failed: {}", e)),
+                }
+                self.finish_export();
+            }
+            _ => {}
+        }
+    }
+
+    /// Leave the export prompts for wherever they were opened from
+    fn finish_export(&mut self) {
+        self.new_password.clear();
+        self.export_only = None;
+        self.phase = if self.viewer.is_some() {
+            Phase::ViewPasswords {
+                mode: ViewMode::Browse,
+            }
+        } else {
+            Phase::Main
+        };
+    }
+
+    fn apply_destroy(&mut self, action: Action) {
+        match action {
+            Action::Backspace => {
+                self.master_input.pop();
+            }
+            Action::Input(c) => self.master_input.push(c),
+            Action::Cancel => {
+                self.master_input.clear();
+                self.phase = Phase::Main;
+            }
+            Action::Confirm if !self.master_input.is_empty() => {
+                // Prove the password again before anything irreversible
+                let verified = self
+                    .open_storage(&self.master_input)
+                    .and_then(|store| store.load());
+                match verified {
+                    Ok(_) => {
+                        self.app.error = None;
+                        self.confirm = Some(Confirm::new(
+                            "Overwrite and delete the vault? Every entry is lost.".into(),
+                            Action::DestroyConfirmed,
+                        ));
+                    }
+                    Err(e) => self.app.set_error(e),
+                }
+                self.master_input.clear();
+            }
+            Action::DestroyConfirmed => {
+                let Some(ref store) = self.storage else {
+                    return;
+                };
+                match store.secure_delete() {
+                    Ok(()) => {
+                        let path = store.path().display().to_string();
+                        self.lock();
+                        self.app.set_status(format!("✓ Vault destroyed: {}", path));
+                    }
+                    Err(e) => {
+                        self.app.set_error(format!("Destroy failed: {}", e));
+                        self.phase = Phase::Main;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_viewer(&mut self, mode: ViewMode, action: Action) {
+        let Some(state) = &mut self.viewer else {
+            return;
+        };
+        let mut next_mode = mode;
+        let track = self.config.track_last_used && !self.app.read_only;
+
+        // Writes by index first make sure the entries are still where the viewer has them
+        let writes = matches!(
+            (mode, action),
+            (
+                ViewMode::Browse,
+                Action::DeleteConfirmed | Action::DuplicateSelected
+            ) | (
+                ViewMode::EditName
+                    | ViewMode::EditPassword
+                    | ViewMode::EditUrl
+                    | ViewMode::ConfirmRename
+                    | ViewMode::EditEntry
+                    | ViewMode::EditNotes
+                    | ViewMode::TransferPassword,
+                Action::Confirm,
+            )
+        );
+        if writes && let Some(ref store) = self.storage {
+            let indices = if action == Action::DeleteConfirmed && !state.marked.is_empty() {
+                state.marked_indices()
+            } else {
+                vec![state.selected]
+            };
+            let expected: Vec<(usize, &str)> = indices
+                .iter()
+                .filter_map(|&i| Some((i, state.entries.get(i)?.name.as_str())))
+                .collect();
+            if let Err(e) = store.check_entries(&expected) {
+                state.edit_buffer.clear();
+                state.draft = None;
+                state.notes = None;
+                self.prompt_input.clear();
+                state.set_status(format!("✗ {} — press [R] to reload", e));
+                self.app.log_error(e.into());
+                self.phase = Phase::ViewPasswords {
+                    mode: ViewMode::Browse,
+                };
+                return;
+            }
+        }
+
+        match (mode, action) {
+            (ViewMode::Browse, action) => match action {
+                Action::ClearSearch => {
+                    state.search_query.clear();
+                    state.refresh_filter();
+                }
+                Action::StartSearch => {
+                    next_mode = ViewMode::Search;
+                    state.status_message.clear();
+                }
+                Action::CycleSort => state.cycle_sort(),
+                Action::CloseViewer => {
+                    if let Some(Err(e)) = self.storage.as_ref().map(|s| s.flush()) {
+                        self.app.set_error(format!("Save failed: {}", e));
+                    }
+                    let name = state.entries.get(state.selected).map(|e| &e.name);
+                    if let Err(e) = self.config.remember_selection(name) {
+                        self.app.set_error(e);
+                    }
+                    self.phase = Phase::Main;
+                    self.viewer = None;
+                    return;
+                }
+                Action::SelectPrev => {
+                    state.select_prev(self.config.wrap_navigation);
+                    state.status_message.clear();
+                }
+                Action::SelectNext => {
+                    state.select_next(self.config.wrap_navigation);
+                    state.status_message.clear();
+                }
+                Action::ReloadViewer => {
+                    let Some(ref store) = self.storage else {
+                        return;
+                    };
+                    match store.load() {
+                        Ok(entries) => {
+                            let name = state.entries.get(state.selected).map(|e| &e.name);
+                            let selected = name
+                                .and_then(|name| entries.iter().position(|e| &e.name == name))
+                                .unwrap_or(0);
+                            *state = ViewerState::new(entries, selected);
+                            state.set_status("✓ Reloaded".into());
+                        }
+                        Err(e) => state.set_status(format!("✗ {}", e)),
+                    }
+                }
+                Action::ToggleReveal if state.has_selection() => {
+                    state.toggle_reveal();
+                    if state.is_revealed(state.selected) {
+                        mark_used(self.storage.as_ref(), track, state);
+                    }
+                }
+                Action::RevealTemporarily if state.has_selection() => {
+                    // Reveal briefly, then re-mask automatically
+                    state.reveal_temporarily();
+                    mark_used(self.storage.as_ref(), track, state);
+                }
+                // Bulk reveal/hide only touch what the filter shows
+                Action::RevealAll if !self.config.allow_reveal_all => {
+                    state.set_status(
+                        "✗ Reveal all is off; reveal entries one by one or set allow_reveal_all"
+                            .into(),
+                    );
+                }
+                Action::RevealAll => state.reveal_visible(),
+                Action::HideAll => state.hide_visible(),
+                Action::CopySelected if state.has_selection() => {
+                    match copy_to_clipboard(&state.entries[state.selected].password) {
+                        Ok(channel) => {
+                            self.clipboard_dirty = true;
+                            state.set_status(channel.status("password"));
+                            mark_used(self.storage.as_ref(), track, state);
+                        }
+                        Err(e) => state.set_status(format!("✗ {}", e)),
+                    }
+                }
+                Action::TypeAndQuit if !self.config.type_out => {
+                    state.set_status("✗ Typing out is off; enable type_out in the config".into());
+                }
+                Action::TypeAndQuit if state.has_selection() => {
+                    if let Err(e) = (self.type_out_check)() {
+                        state.set_status(format!("✗ {}", e));
+                        return;
+                    }
+                    mark_used(self.storage.as_ref(), track, state);
+                    self.type_on_exit = Some(state.entries[state.selected].password.clone());
+                    self.dispatch(Action::Quit);
+                    // Held changes that failed to save keep the session open
+                    if !self.quit {
+                        self.type_on_exit = None;
+                    }
+                    return;
+                }
+                Action::CopyFormatted if state.has_selection() => {
+                    let text = state.entries[state.selected].format(&self.config.copy_format);
+                    match copy_to_clipboard(&text) {
+                        Ok(channel) => {
+                            self.clipboard_dirty = true;
+                            state.set_status(channel.status("formatted entry"));
+                            mark_used(self.storage.as_ref(), track, state);
+                        }
+                        Err(e) => state.set_status(format!("✗ {}", e)),
+                    }
+                }
+                Action::CopyAndClose if state.has_selection() => {
+                    // Copy password and go straight back to the generator
+                    let entry = &state.entries[state.selected];
+                    match copy_to_clipboard(&entry.password) {
+                        Ok(channel) => {
+                            self.clipboard_dirty = true;
+                            mark_used(self.storage.as_ref(), track, state);
+                            let entry = &state.entries[state.selected];
+                            self.app
+                                .set_status(channel.status(&format!("'{}'", entry.name)));
+                            if let Err(e) = self.config.remember_selection(Some(&entry.name)) {
+                                self.app.set_error(e);
+                            }
+                            self.phase = Phase::Main;
+                            self.viewer = None;
+                            return;
+                        }
+                        Err(e) => state.set_status(format!("✗ {}", e)),
+                    }
+                }
+                Action::DeleteSelected
+                | Action::DuplicateSelected
+                | Action::EditName
+                | Action::EditPassword
+                | Action::EditUrl
+                | Action::EditEntry
+                | Action::EditNotes
+                | Action::TransferEntry
+                    if self.app.read_only =>
+                {
+                    state.set_status("✗ Read-only mode: editing disabled".into());
+                }
+                Action::ToggleMark if state.has_selection() => state.toggle_mark(),
+                Action::ExportMarked if state.marked.is_empty() => {
+                    state.set_status("Mark entries with [Space] first".into());
+                }
+                Action::ExportMarked => {
+                    let entries = state
+                        .marked_indices()
+                        .into_iter()
+                        .map(|i| state.entries[i].clone())
+                        .collect();
+                    self.export_only = Some(entries);
+                    self.prompt_input.clear();
+                    self.new_password.clear();
+                    self.app.error = None;
+                    self.phase = Phase::ExportPath;
+                    return;
+                }
+                Action::DeleteSelected if !state.marked.is_empty() => {
+                    self.confirm = Some(Confirm::new(
+                        format!("Delete {} marked entries?", state.marked.len()),
+                        Action::DeleteConfirmed,
+                    ));
+                }
+                Action::DeleteSelected if self.config.quick_delete && state.has_selection() => {
+                    state.stage_delete();
+                }
+                Action::UndoDelete if state.undo_delete() => {
+                    state.set_status("✓ Restored".into());
+                }
+                Action::DeleteSelected if state.has_selection() => {
+                    let name = &state.entries[state.selected].name;
+                    self.confirm = Some(Confirm::new(
+                        format!("Delete '{}'?", name),
+                        Action::DeleteConfirmed,
+                    ));
+                }
+                Action::DeleteConfirmed if !state.marked.is_empty() => {
+                    if let Some(ref store) = self.storage {
+                        match store.delete_many(&state.marked_indices()) {
+                            Ok(_) => {
+                                let count = state.marked.len();
+                                state.remove_marked();
+                                state.set_status(format!("✓ Deleted {} entries", count));
+                            }
+                            Err(e) => {
+                                state.set_status(format!("✗ {}", e));
+                                self.app.log_error(e.into());
+                            }
+                        }
+                    }
+                }
+                Action::DeleteConfirmed if state.has_selection() => {
+                    if let Some(ref store) = self.storage {
+                        match store.delete(state.selected) {
+                            Ok(_) => {
+                                state.remove_selected();
+                                state.set_status("✓ Deleted!".into());
+                            }
+                            Err(e) => {
+                                state.set_status(format!("✗ {}", e));
+                                self.app.log_error(e.into());
+                            }
+                        }
+                    }
+                }
+                Action::DuplicateSelected if state.has_selection() => {
+                    let mut copy = state.entries[state.selected].clone();
+                    copy.name.push_str(" (copy)");
+                    copy.created_at = chrono_timestamp();
+                    let index = state.selected + 1;
+                    if let Some(ref store) = self.storage {
+                        match store.insert_at(index, copy.clone()) {
+                            Ok(_) => {
+                                state.insert_entry(index, copy);
+                                state.set_status("✓ Duplicated — press [e] to rename".into());
+                            }
+                            Err(e) => {
+                                state.set_status(format!("✗ {}", e));
+                                self.app.log_error(e.into());
+                            }
+                        }
+                    }
+                }
+                Action::EditName if state.has_selection() => {
+                    state.edit_buffer = state.entries[state.selected].name.clone();
+                    next_mode = ViewMode::EditName;
+                }
+                Action::EditPassword if state.has_selection() => {
+                    state.edit_buffer = state.entries[state.selected].password.clone();
+                    state.revealed.insert(state.selected);
+                    next_mode = ViewMode::EditPassword;
+                }
+                Action::EditUrl if state.has_selection() => {
+                    state.edit_buffer = state.entries[state.selected].url.clone();
+                    next_mode = ViewMode::EditUrl;
+                }
+                Action::TransferEntry if state.has_selection() => {
+                    state.edit_buffer.clear();
+                    next_mode = ViewMode::TransferPath;
+                }
+                Action::EditEntry if state.has_selection() => {
+                    state.start_draft();
+                    next_mode = ViewMode::EditEntry;
+                }
+                Action::EditNotes if state.has_selection() => {
+                    state.notes = Some(NotesEditor::new(&state.entries[state.selected].notes));
+                    next_mode = ViewMode::EditNotes;
+                }
+                Action::OpenDetail if state.has_selection() => next_mode = ViewMode::Detail,
+                Action::CheckBreachOnline if state.has_selection() => {
+                    if self.config.allow_network {
+                        next_mode = ViewMode::ConfirmNetwork;
+                    } else {
+                        state.set_status(
+                            "Online checks are off — set allow_network: true in the config".into(),
+                        );
+                    }
+                }
+                Action::CheckBreach if state.has_selection() => {
+                    let status = match &self.config.pwned_ranges_dir {
+                        None => "Set pwned_ranges_dir in the config to check breaches".into(),
+                        Some(dir) => {
+                            match check_pwned_local(&state.entries[state.selected].password, dir) {
+                                Ok(0) => "✓ Not found in the breach data".into(),
+                                Ok(n) => format!("⚠ Found in {} breaches — rotate it", n),
+                                Err(e) => {
+                                    self.app.log_error(e.clone());
+                                    format!("✗ {}", e)
+                                }
+                            }
+                        }
+                    };
+                    state.set_status(status);
+                }
+                Action::RevealByChar if state.has_selection() => {
+                    if state.entries[state.selected].password.is_empty() {
+                        state.set_status("Password is empty".into());
+                    } else {
+                        next_mode = ViewMode::RevealChar;
+                    }
+                }
+                Action::ShowQr if state.has_selection() => {
+                    // Ask before putting the secret on screen
+                    next_mode = ViewMode::ConfirmQr;
+                }
+                Action::OpenUrl if state.has_selection() => {
+                    match state.entries[state.selected].launch_url() {
+                        Ok(url) => match open::that_detached(&url) {
+                            Ok(_) => state.set_status(format!("✓ Opened {}", url)),
+                            Err(e) => state.set_status(format!("✗ Failed to open browser: {}", e)),
+                        },
+                        Err(e) => state.set_status(e),
+                    }
+                }
+                _ => {}
+            },
+            (ViewMode::Search, Action::ClearSearch) => {
+                state.search_query.clear();
+                state.refresh_filter();
+                next_mode = ViewMode::Browse;
+            }
+            (ViewMode::Search, Action::Confirm) => next_mode = ViewMode::Browse,
+            (ViewMode::Search, Action::Backspace) => {
+                state.search_query.pop();
+                state.refresh_filter();
+            }
+            (ViewMode::Search, Action::Input(c)) => {
+                state.search_query.push(c);
+                state.refresh_filter();
+            }
+            (ViewMode::Detail, Action::Cancel) => next_mode = ViewMode::Browse,
+            (ViewMode::Detail, Action::ToggleReveal) => {
+                state.toggle_reveal();
+                if state.is_revealed(state.selected) {
+                    mark_used(self.storage.as_ref(), track, state);
+                }
+            }
+            (ViewMode::Detail, Action::CopySelected | Action::CopyUsername | Action::CopyUrl) => {
+                let entry = &state.entries[state.selected];
+                let (label, value) = match action {
+                    Action::CopyUsername => ("Username", &entry.username),
+                    Action::CopyUrl => ("URL", &entry.url),
+                    _ => ("Password", &entry.password),
+                };
+                let status = if value.is_empty() {
+                    format!("{} is empty", label)
+                } else {
+                    match copy_to_clipboard(value) {
+                        Ok(channel) => {
+                            self.clipboard_dirty = true;
+                            channel.status(label)
+                        }
+                        Err(e) => format!("✗ {}", e),
+                    }
+                };
+                if action == Action::CopySelected && status.starts_with('✓') {
+                    mark_used(self.storage.as_ref(), track, state);
+                }
+                state.set_status(status);
+            }
+            (ViewMode::RevealChar, Action::CharPrev) => state.step_char(-1),
+            (ViewMode::RevealChar, Action::CharNext) => state.step_char(1),
+            (ViewMode::RevealChar, Action::Cancel) => next_mode = ViewMode::Browse,
+            (ViewMode::ConfirmQr, Action::Confirm) => next_mode = ViewMode::ShowQr,
+            (ViewMode::ConfirmNetwork, Action::Confirm) if self.config.allow_network => {
+                let report = check_pwned_online(&state.entries[state.selected].password);
+                if let Err(ref e) = report {
+                    self.app.log_error(e.clone());
+                }
+                state.breach_report = Some(report);
+                next_mode = ViewMode::BreachReport;
+            }
+            (ViewMode::BreachReport, Action::Cancel) => {
+                state.breach_report = None;
+                next_mode = ViewMode::Browse;
+            }
+            (ViewMode::ConfirmQr | ViewMode::ConfirmNetwork, Action::Cancel) => {
+                next_mode = ViewMode::Browse;
+                state.status_message.clear();
+            }
+            (ViewMode::ShowQr, Action::Cancel) => next_mode = ViewMode::Browse,
+            (ViewMode::TransferPath | ViewMode::TransferPassword, action) => match action {
+                Action::Backspace => {
+                    state.edit_buffer.pop();
+                }
+                Action::Input(c) => state.edit_buffer.push(c),
+                Action::Cancel => {
+                    state.edit_buffer.clear();
+                    self.prompt_input.clear();
+                    next_mode = ViewMode::Browse;
+                }
+                Action::Confirm
+                    if mode == ViewMode::TransferPath && !state.edit_buffer.trim().is_empty() =>
+                {
+                    let path = expand_path(state.edit_buffer.trim());
+                    if !path.exists() {
+                        state.set_status(format!("✗ No vault at {}", path.display()));
+                    } else {
+                        self.prompt_input = std::mem::take(&mut state.edit_buffer);
+                        state.status_message.clear();
+                        next_mode = ViewMode::TransferPassword;
+                    }
+                }
+                Action::Confirm
+                    if mode == ViewMode::TransferPassword && !state.edit_buffer.is_empty() =>
+                {
+                    let Some(ref store) = self.storage else {
+                        return;
+                    };
+                    let path = expand_path(self.prompt_input.trim());
+                    let name = state.entries[state.selected].name.clone();
+                    // Loading proves the password before anything is written
+                    let result = Storage::new_at(&state.edit_buffer, &path)
+                        .and_then(|target| target.load().map(|_| target))
+                        .and_then(|target| store.transfer_to(state.selected, &target));
+                    match result {
+                        Ok(_) => {
+                            state.remove_selected();
+                            state.set_status(format!("✓ Moved '{}' to {}", name, path.display()));
+                        }
+                        Err(e) => {
+                            state.set_status(format!("✗ {}", e));
+                            self.app.log_error(e);
+                        }
+                    }
+                    state.edit_buffer.clear();
+                    self.prompt_input.clear();
+                    next_mode = ViewMode::Browse;
+                }
+                _ => {}
+            },
+            (ViewMode::EditNotes, action) => {
+                let Some(editor) = state.notes.as_mut() else {
+                    return;
+                };
+                match action {
+                    Action::Input(c) => editor.insert(c),
+                    Action::Backspace => editor.backspace(),
+                    Action::CharPrev => editor.move_left(),
+                    Action::CharNext => editor.move_right(),
+                    Action::SelectPrev => editor.move_vertical(false),
+                    Action::SelectNext => editor.move_vertical(true),
+                    Action::Cancel => {
+                        state.notes = None;
+                        next_mode = ViewMode::Browse;
+                    }
+                    Action::Confirm => {
+                        let mut entry = state.entries[state.selected].clone();
+                        entry.notes = editor.text();
+                        if let Some(ref store) = self.storage {
+                            match store.update(state.selected, entry.clone()) {
+                                Ok(_) => {
+                                    state.entries[state.selected] = entry;
+                                    state.set_status("✓ Notes updated!".into());
+                                    state.notes = None;
+                                    next_mode = ViewMode::Browse;
+                                }
+                                Err(e) => {
+                                    state.set_status(format!("✗ {}", e));
+                                    self.app.log_error(e.into());
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            (ViewMode::EditEntry, action) => match action {
+                Action::Cancel => {
+                    state.draft = None;
+                    next_mode = ViewMode::Browse;
+                }
+                Action::NextField => state.draft_field = state.draft_field.next(),
+                Action::PrevField => state.draft_field = state.draft_field.prev(),
+                Action::Backspace => {
+                    if let Some(input) = state.draft_input() {
+                        input.pop();
+                    }
+                }
+                Action::Input(c) => {
+                    if let Some(input) = state.draft_input() {
+                        input.push(c);
+                    }
+                }
+                Action::Confirm => {
+                    if let Some(mut draft) = state.draft.clone() {
+                        draft.url = draft.url.trim().to_string();
+                        draft.name = sanitize_name(&draft.name);
+                        let expiry = parse_expiry(&state.draft_expiry);
+                        if draft.name.is_empty() || draft.password.is_empty() {
+                            state.set_status("✗ Name and password are required".into());
+                        } else if let Err(e) = expiry {
+                            state.set_status(format!("✗ {}", e));
+                        } else if let Some(ref store) = self.storage {
+                            draft.expires_at = expiry.unwrap_or_default();
+                            match store.update(state.selected, draft.clone()) {
+                                Ok(_) => {
+                                    state.entries[state.selected] = draft;
+                                    state.refresh_filter();
+                                    state.set_status("✓ Entry updated!".into());
+                                    state.draft = None;
+                                    next_mode = ViewMode::Browse;
+                                }
+                                Err(e) => {
+                                    state.set_status(format!("✗ {}", e));
+                                    self.app.log_error(e.into());
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            },
+            (ViewMode::ConfirmRename, Action::Confirm) => {
+                save_edit(
+                    self.storage.as_ref(),
+                    &mut self.app,
+                    state,
+                    ViewMode::EditName,
+                );
+                next_mode = ViewMode::Browse;
+            }
+            // Back to the name being typed so it can be changed
+            (ViewMode::ConfirmRename, Action::Cancel) => next_mode = ViewMode::EditName,
+            (ViewMode::EditName | ViewMode::EditPassword | ViewMode::EditUrl, action) => {
+                match action {
+                    Action::Cancel => {
+                        next_mode = ViewMode::Browse;
+                        state.edit_buffer.clear();
+                        state.status_message.clear();
+                    }
+                    Action::RegeneratePassword if mode == ViewMode::EditPassword => {
+                        // Reuse the entry's own rules; older entries get the current settings
+                        let options = match &state.entries[state.selected].gen_options {
+                            Some(options) => Ok(options.clone()),
+                            None => self.app.gen_options(),
+                        };
+                        match options.and_then(|o| generate_password(&o)) {
+                            Ok(password) => state.edit_buffer = password,
+                            Err(e) => state.set_status(format!("✗ {}", e)),
+                        }
+                    }
+                    Action::ToggleEditMask if mode == ViewMode::EditPassword => {
+                        state.mask_edit = !state.mask_edit;
+                    }
+                    // Renaming onto another entry's name needs a second look
+                    Action::Confirm
+                        if mode == ViewMode::EditName
+                            && name_exists(
+                                &state.entries,
+                                &state.edit_buffer,
+                                Some(state.selected),
+                            ) =>
+                    {
+                        next_mode = ViewMode::ConfirmRename;
+                    }
+                    Action::Confirm => {
+                        save_edit(self.storage.as_ref(), &mut self.app, state, mode);
+                        next_mode = ViewMode::Browse;
+                    }
+                    Action::Backspace => {
+                        state.edit_buffer.pop();
+                    }
+                    Action::Input(c) => state.edit_buffer.push(c),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        self.phase = Phase::ViewPasswords { mode: next_mode };
+    }
+}
+
+/// Stamp the selected entry as used now, when `track` is on. Failures are only
+/// logged: a missed stamp is not worth interrupting a copy for
+fn mark_used(store: Option<&Storage>, track: bool, state: &mut ViewerState) {
+    let Some(store) = store.filter(|_| track) else {
+        return;
+    };
+    let mut entry = state.entries[state.selected].clone();
+    entry.last_used = Some(chrono_timestamp());
+    let result = store
+        .check_entries(&[(state.selected, &entry.name)])
+        .and_then(|_| store.update(state.selected, entry.clone()));
+    match result {
+        Ok(_) => state.entries[state.selected] = entry,
+        Err(e) => log::warn!("last-used stamp not saved: {}", e),
+    }
+}
+
+/// Store the password shown on the generator screen under the entered name
+fn save_generated(store: Option<&Storage>, app: &mut App) {
+    if let Some(store) = store
+        && let Some(mut entry) = app.get_entry()
+    {
+        // Placeholders saved within the same second get a counter
+        if sanitize_name(&app.name_input).is_empty() {
+            let taken = store.load().unwrap_or_default();
+            let base = entry.name.clone();
+            let mut n = 1;
+            while name_exists(&taken, &entry.name, None) {
+                n += 1;
+                entry.name = format!("{}-{}", base, n);
+            }
+        }
+        match store.save(entry) {
+            Ok(_) => {
+                app.pending_save = false;
+                app.set_status(format!("✓ Saved to {}", store.path().display()));
+            }
+            Err(e) => app.set_error(format!("Save failed: {}", e)),
+        }
+    }
+}
+
+/// Write the edit buffer into the field edited in `mode` of the selected entry
+fn save_edit(store: Option<&Storage>, app: &mut App, state: &mut ViewerState, mode: ViewMode) {
+    let mut entry = state.entries[state.selected].clone();
+    let (valid, label) = match mode {
+        ViewMode::EditName => {
+            entry.name = sanitize_name(&state.edit_buffer);
+            (!entry.name.is_empty(), "Name")
+        }
+        ViewMode::EditPassword => {
+            entry.password = state.edit_buffer.clone();
+            (!state.edit_buffer.is_empty(), "Password")
+        }
+        // An empty URL clears it
+        _ => {
+            entry.url = state.edit_buffer.trim().to_string();
+            (true, "URL")
+        }
+    };
+    if valid && let Some(store) = store {
+        match store.update(state.selected, entry.clone()) {
+            Ok(_) => {
+                state.entries[state.selected] = entry;
+                state.set_status(format!("✓ {} updated!", label));
+            }
+            Err(e) => {
+                state.set_status(format!("✗ {}", e));
+                app.log_error(e.into());
+            }
+        }
+    }
+    state.edit_buffer.clear();
+}
+
+/// Resolve a typed path, allowing the usual `~/` shorthand for the home directory
+fn expand_path(input: &str) -> PathBuf {
+    match (input.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(input),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::passgen_core::generator::GenOptions;
+    use crate::passgen_core::storage::SortMode;
+    use crossterm::event::KeyEvent;
+    use std::path::Path;
+
+    fn session(dir: &Path) -> Session {
+        let options = SessionOptions {
+            vault_path: Some(dir.join("vault.enc")),
+            ..Default::default()
+        };
+        Session::new(Config::load_from(&dir.join("config.json")), options)
+    }
+
+    fn type_text(session: &mut Session, text: &str) {
+        for c in text.chars() {
+            session.apply(Action::Input(c));
+        }
+    }
+
+    /// Unlock a fresh session and save one generated entry
+    fn unlocked_with_entry(dir: &Path, name: &str) -> Session {
+        let mut s = session(dir);
+        s.apply(Action::Confirm);
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+        type_text(&mut s, name);
+        s.apply(Action::GenerateAndSave);
+        s
+    }
+
+    #[test]
+    fn out_of_range_class_weights_are_dropped_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, r#"{"class_weights": {"special": 3}}"#).unwrap();
+        assert!(Config::load_from(&path).class_weights.is_some());
+        std::fs::write(&path, r#"{"class_weights": {"letters": 100000000}}"#).unwrap();
+        let config = Config::load_from(&path);
+        assert!(config.class_weights.is_none());
+    }
+
+    #[test]
+    fn directory_profile_overrides_generator_defaults() {
+        use crate::passgen_core::config::{DIR_PROFILE_NAME, DirProfile};
+        use crate::passgen_core::generator::GenMode;
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(DirProfile::load(dir.path()), Ok(None));
+
+        let rc = dir.path().join(DIR_PROFILE_NAME);
+        std::fs::write(&rc, r#"{"lenght": 30}"#).unwrap();
+        assert!(DirProfile::load(dir.path()).is_err());
+
+        std::fs::write(
+            &rc,
+            r#"{"length": 30, "mode": "passphrase", "use_special": false, "must_match": "^a"}"#,
+        )
+        .unwrap();
+        let profile = DirProfile::load(dir.path()).unwrap().unwrap();
+        let mut s = session(dir.path());
+        s.app.apply_profile(&profile);
+        assert_eq!(s.app.length_input, "30");
+        assert!(s.app.mode == GenMode::Passphrase);
+        assert!(!s.app.use_special);
+        // Fields the profile leaves out keep their defaults
+        assert!(s.app.use_letters);
+        assert_eq!(s.app.must_match_input, "^a");
+    }
+
+    #[test]
+    fn caps_lock_hint_needs_mostly_uppercase() {
+        use crate::passgen_core::app::caps_lock_suspected;
+        assert!(caps_lock_suspected("CORRECT HORSE"));
+        assert!(caps_lock_suspected("MASTEr42"));
+        assert!(!caps_lock_suspected("ABC"));
+        assert!(!caps_lock_suspected("Master42"));
+        assert!(!caps_lock_suspected("1234!!!!"));
+    }
+
+    #[test]
+    fn onboarding_is_only_shown_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = session(dir.path());
+        assert!(s.phase == Phase::Onboarding);
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::MasterPassword);
+        assert!(session(dir.path()).phase == Phase::MasterPassword);
+    }
+
+    #[test]
+    fn generate_and_save_persists_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        assert!(s.app.generated_password.is_some());
+
+        s.apply(Action::OpenViewer);
+        let state = s.viewer.as_ref().unwrap();
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.entries[0].name, "mail");
+    }
+
+    #[test]
+    fn anonymous_save_uses_placeholder_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "");
+        assert!(s.app.error.is_some());
+        assert!(s.app.generated_password.is_none());
+
+        s.apply(Action::NextField);
+        assert!(s.app.active_field == InputField::Anonymous);
+        s.apply(Action::ToggleField);
+        s.apply(Action::GenerateAndSave);
+        assert!(s.app.error.is_none());
+        let entries = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(entries.len(), 1);
+        let stamp = entries[0].name.strip_prefix("entry-").unwrap();
+        assert!(stamp.parse::<u64>().is_ok());
+
+        // The name field stays blank, so the next save gets its own name
+        assert!(s.app.name_input.is_empty());
+        s.apply(Action::GenerateAndSave);
+        let entries = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[1].name.starts_with("entry-"));
+        assert_ne!(entries[0].name, entries[1].name);
+    }
+
+    #[test]
+    fn destroy_vault_needs_password_and_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let path = s.storage.as_ref().unwrap().path().to_path_buf();
+        s.apply(Action::DestroyVault);
+        assert!(s.phase == Phase::DestroyVault);
+
+        type_text(&mut s, "wrong");
+        s.apply(Action::Confirm);
+        assert!(s.app.error.is_some());
+        assert!(s.confirm.is_none());
+
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        assert!(s.confirm.is_some());
+        s.apply(Action::Cancel);
+        assert!(path.exists());
+
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        s.apply(Action::Confirm);
+        assert!(!path.exists());
+        assert!(s.phase == Phase::MasterPassword);
+        assert!(s.storage.is_none());
+    }
+
+    #[test]
+    fn type_and_quit_is_gated_by_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::TypeAndQuit);
+        assert!(!s.quit);
+        assert!(s.type_on_exit.is_none());
+
+        s.config.type_out = true;
+        s.type_out_check = || Ok(());
+        s.apply(Action::TypeAndQuit);
+        assert!(s.quit);
+        assert_eq!(s.type_on_exit, s.app.generated_password);
+    }
+
+    #[test]
+    fn type_and_quit_refusal_shows_before_quitting() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.config.type_out = true;
+        s.type_out_check = || Err("No terminal to type into".into());
+        s.apply(Action::OpenViewer);
+        s.apply(Action::TypeAndQuit);
+        assert!(!s.quit);
+        assert!(s.type_on_exit.is_none());
+        let status = s.viewer.as_ref().unwrap().status().unwrap_or_default();
+        assert!(status.contains("No terminal"));
+    }
+
+    #[test]
+    fn delete_needs_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+
+        s.apply(Action::DeleteSelected);
+        assert!(s.confirm.is_some());
+        // Anything but yes/no leaves the question open
+        s.apply(Action::SelectNext);
+        assert!(s.confirm.is_some());
+        s.apply(Action::Cancel);
+        assert!(s.confirm.is_none());
+        assert_eq!(s.viewer.as_ref().unwrap().entries.len(), 1);
+
+        s.apply(Action::DeleteSelected);
+        s.apply(Action::Confirm);
+        assert_eq!(s.viewer.as_ref().unwrap().entries.len(), 0);
+        assert!(s.storage.as_ref().unwrap().load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancelled_marked_export_does_not_narrow_a_later_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "a");
+        s.app.name_input = "b".into();
+        s.apply(Action::GenerateAndSave);
+        s.apply(Action::OpenViewer);
+        s.apply(Action::ToggleMark);
+        s.apply(Action::ExportMarked);
+        s.apply(Action::Cancel);
+        assert!(matches!(s.phase, Phase::ViewPasswords { .. }));
+        assert!(s.export_only.is_none());
+
+        s.apply(Action::CloseViewer);
+        assert!(s.phase == Phase::Main);
+        s.apply(Action::ExportEncrypted);
+        let dest = dir.path().join("all.enc");
+        type_text(&mut s, dest.to_str().unwrap());
+        s.apply(Action::Confirm);
+        type_text(&mut s, "export");
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+        let exported = Storage::new_at("export", &dest).unwrap().load().unwrap();
+        assert_eq!(exported.len(), 2);
+    }
+
+    #[test]
+    fn marked_entries_are_deleted_and_exported_together() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "a");
+        let store = s.storage.as_ref().unwrap();
+        for name in ["b", "c", "d"] {
+            store
+                .save(PasswordEntry {
+                    name: name.into(),
+                    password: format!("pw-{}", name),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        s.apply(Action::OpenViewer);
+
+        // Space marks instead of revealing
+        let space = KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE);
+        assert_eq!(s.map_key(space), Some(Action::ToggleMark));
+        s.apply(Action::ToggleMark);
+        s.apply(Action::SelectNext);
+        s.apply(Action::SelectNext);
+        s.apply(Action::ToggleMark);
+        assert!(s.viewer.as_ref().unwrap().revealed.is_empty());
+
+        s.apply(Action::ExportMarked);
+        assert!(s.phase == Phase::ExportPath);
+        let dest = dir.path().join("marked.enc");
+        type_text(&mut s, dest.to_str().unwrap());
+        s.apply(Action::Confirm);
+        type_text(&mut s, "export");
+        s.apply(Action::Confirm);
+        assert!(matches!(s.phase, Phase::ViewPasswords { .. }));
+        let exported = Storage::new_at("export", &dest).unwrap().load().unwrap();
+        let names: Vec<String> = exported.into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["a", "c"]);
+
+        // One question covers every marked entry
+        s.apply(Action::DeleteSelected);
+        assert_eq!(
+            s.confirm.as_ref().map(|c| c.prompt.as_str()),
+            Some("Delete 2 marked entries?")
+        );
+        s.apply(Action::Confirm);
+        let state = s.viewer.as_ref().unwrap();
+        assert!(state.marked.is_empty());
+        let names: Vec<&str> = state.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["b", "d"]);
+        let stored: Vec<String> = s
+            .storage
+            .as_ref()
+            .unwrap()
+            .load()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(stored, ["b", "d"]);
+    }
+
+    #[test]
+    fn viewer_refuses_writes_after_the_vault_moved_under_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.app.name_input = "bank".into();
+        s.apply(Action::GenerateAndSave);
+        s.apply(Action::OpenViewer);
+        assert_eq!(s.viewer.as_ref().unwrap().entries[0].name, "mail");
+
+        // Another instance drops "mail", so index 0 is now "bank"
+        let other = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        let mut entries = other.load().unwrap();
+        entries.remove(0);
+        other.save_all(&entries).unwrap();
+
+        s.apply(Action::DeleteSelected);
+        s.apply(Action::Confirm);
+        let state = s.viewer.as_ref().unwrap();
+        assert!(state.status().unwrap().contains("[R] to reload"));
+        assert_eq!(s.storage.as_ref().unwrap().load().unwrap()[0].name, "bank");
+
+        s.apply(Action::EditName);
+        type_text(&mut s, "x");
+        s.apply(Action::Confirm);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+        assert!(s.viewer.as_ref().unwrap().edit_buffer.is_empty());
+
+        let key = KeyEvent::new(KeyCode::Char('R'), KeyModifiers::SHIFT);
+        assert_eq!(s.map_key(key), Some(Action::ReloadViewer));
+        s.apply(Action::ReloadViewer);
+        let state = s.viewer.as_ref().unwrap();
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.entries[0].name, "bank");
+        s.apply(Action::DeleteSelected);
+        s.apply(Action::Confirm);
+        assert!(s.storage.as_ref().unwrap().load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn quick_delete_can_be_undone_until_it_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.config.quick_delete = true;
+        s.apply(Action::OpenViewer);
+        let stored = |s: &Session| s.storage.as_ref().unwrap().load().unwrap().len();
+
+        s.apply(Action::DeleteSelected);
+        assert!(s.confirm.is_none());
+        assert!(s.viewer.as_ref().unwrap().entries.is_empty());
+        assert_eq!(stored(&s), 1);
+        let u = KeyEvent::new(KeyCode::Char('u'), KeyModifiers::NONE);
+        assert_eq!(s.map_key(u), Some(Action::UndoDelete));
+        s.apply(Action::UndoDelete);
+        assert_eq!(s.viewer.as_ref().unwrap().entries.len(), 1);
+        assert_eq!(stored(&s), 1);
+
+        // Any other action gives up the undo
+        s.apply(Action::DeleteSelected);
+        s.apply(Action::SelectNext);
+        assert_eq!(stored(&s), 0);
+        assert_eq!(s.map_key(u), Some(Action::EditUrl));
+
+        // So does the window running out
+        s.apply(Action::CloseViewer);
+        type_text(&mut s, "bank");
+        s.apply(Action::GenerateAndSave);
+        s.apply(Action::OpenViewer);
+        s.apply(Action::DeleteSelected);
+        s.tick();
+        assert_eq!(stored(&s), 1);
+        s.viewer
+            .as_mut()
+            .unwrap()
+            .pending_delete
+            .as_mut()
+            .unwrap()
+            .2 = Instant::now();
+        s.tick();
+        assert_eq!(stored(&s), 0);
+        assert!(s.viewer.as_ref().unwrap().pending_delete.is_none());
+    }
+
+    #[test]
+    fn names_with_an_i_can_be_typed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "first");
+        s.app.name_input.clear();
+        for c in "github".chars() {
+            let action = s.map_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            s.apply(action.unwrap());
+        }
+        assert!(s.phase == Phase::Main);
+        assert_eq!(s.app.name_input, "github");
+    }
+
+    #[test]
+    fn stats_screen_summarises_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let entry = |name: &str, password: &str, created: &str, tags: &[&str]| PasswordEntry {
+            name: name.into(),
+            password: password.into(),
+            created_at: created.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        s.storage
+            .as_ref()
+            .unwrap()
+            .save_all(&[
+                entry("mail", "abc", "20", &["work"]),
+                entry("bank", "abc", "10", &["money", "work"]),
+                entry("shop", "Xy7#Xy7#Xy7#Xy7#", "30", &[]),
+            ])
+            .unwrap();
+
+        // In a text field a plain i is typed; Alt+i still opens the screen
+        let key = KeyEvent::new(KeyCode::Char('i'), KeyModifiers::NONE);
+        assert_eq!(s.map_key(key), Some(Action::Input('i')));
+        let alt = KeyEvent::new(KeyCode::Char('i'), KeyModifiers::ALT);
+        assert_eq!(s.map_key(alt), Some(Action::OpenStats));
+        s.apply(Action::JumpToField(InputField::Mode));
+        assert_eq!(s.map_key(key), Some(Action::OpenStats));
+        s.apply(Action::OpenStats);
+        assert!(s.phase == Phase::Stats);
+        let stats = s.stats.as_ref().unwrap();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.weak, 2);
+        assert_eq!(stats.reused, 2);
+        assert_eq!(stats.oldest, Some(("bank".into(), 10)));
+        assert_eq!(stats.newest, Some(("shop".into(), 30)));
+        assert!((stats.average_length - 22.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.tags, [("work".into(), 2), ("money".into(), 1)]);
+
+        s.apply(Action::Cancel);
+        assert!(s.phase == Phase::Main);
+        assert!(s.stats.is_none());
+    }
+
+    #[test]
+    fn edit_name_updates_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::EditName);
+        type_text(&mut s, "2");
+        s.apply(Action::Confirm);
+
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+        assert_eq!(s.storage.as_ref().unwrap().load().unwrap()[0].name, "mail2");
+    }
+
+    #[test]
+    fn read_only_blocks_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(unlocked_with_entry(dir.path(), "mail"));
+
+        let mut s = session(dir.path());
+        s.app.read_only = true;
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        s.apply(Action::OpenViewer);
+        s.apply(Action::DeleteSelected);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+        assert_eq!(s.storage.as_ref().unwrap().load().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn change_master_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::ChangeMasterPassword);
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        type_text(&mut s, "new");
+        s.apply(Action::Confirm);
+        type_text(&mut s, "new");
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+
+        let mut s = session(dir.path());
+        type_text(&mut s, "new");
+        s.apply(Action::Confirm);
+        s.apply(Action::OpenViewer);
+        assert_eq!(s.viewer.as_ref().unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn bulk_reveal_respects_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.app.name_input = "bank".into();
+        s.apply(Action::GenerateAndSave);
+        s.apply(Action::OpenViewer);
+
+        // Off unless the config allows it
+        s.apply(Action::RevealAll);
+        assert!(!s.viewer.as_ref().unwrap().is_revealed(0));
+        s.config.allow_reveal_all = true;
+
+        s.apply(Action::StartSearch);
+        type_text(&mut s, "bank");
+        s.apply(Action::Confirm);
+        s.apply(Action::RevealAll);
+        let state = s.viewer.as_ref().unwrap();
+        assert!(!state.is_revealed(0));
+        assert!(state.is_revealed(1));
+
+        s.apply(Action::ClearSearch);
+        s.apply(Action::RevealAll);
+        s.apply(Action::StartSearch);
+        type_text(&mut s, "mail");
+        s.apply(Action::Confirm);
+        s.apply(Action::HideAll);
+        let state = s.viewer.as_ref().unwrap();
+        assert!(!state.is_revealed(0));
+        assert!(state.is_revealed(1));
+    }
+
+    #[test]
+    fn duplicate_inserts_after_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.app.name_input = "bank".into();
+        s.apply(Action::GenerateAndSave);
+        s.apply(Action::OpenViewer);
+        s.apply(Action::SelectNext);
+        s.apply(Action::ToggleReveal);
+        s.apply(Action::SelectPrev);
+
+        s.apply(Action::DuplicateSelected);
+        let state = s.viewer.as_ref().unwrap();
+        assert_eq!(state.selected, 1);
+        assert!(state.is_revealed(2) && !state.is_revealed(1));
+        let entries = s.storage.as_ref().unwrap().load().unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["mail", "mail (copy)", "bank"]);
+    }
+
+    #[test]
+    fn presets_save_and_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.app.length_input = "20".into();
+        s.apply(Action::SavePreset);
+        type_text(&mut s, "banking");
+        s.apply(Action::Confirm);
+
+        s.app.length_input = "12".into();
+        s.app.use_special = false;
+        s.apply(Action::SavePreset);
+        s.prompt_input.clear();
+        type_text(&mut s, "legacy");
+        s.apply(Action::Confirm);
+
+        let config = Config::load_from(&dir.path().join("config.json"));
+        assert_eq!(config.presets.len(), 2);
+
+        s.apply(Action::NextPreset);
+        assert_eq!(s.app.length_input, "20");
+        assert!(s.app.use_special);
+        s.apply(Action::NextPreset);
+        assert_eq!(s.app.length_input, "12");
+        assert!(!s.app.use_special);
+    }
+
+    #[test]
+    fn reveal_by_char_steps_within_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::RevealByChar);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::RevealChar
+                }
+        );
+
+        let password = s.viewer.as_ref().unwrap().entries[0].password.clone();
+        let len = password.chars().count();
+        s.apply(Action::CharPrev);
+        for _ in 0..len + 3 {
+            s.apply(Action::CharNext);
+        }
+        let state = s.viewer.as_ref().unwrap();
+        let (masked, position) = state.char_reveal(0);
+        assert_eq!(position, len);
+        assert_eq!(masked.chars().last(), password.chars().last());
+        assert_eq!(masked.chars().filter(|&c| c == '•').count(), len - 1);
+        assert!(!state.is_revealed(0));
+
+        s.apply(Action::Cancel);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+    }
+
+    #[test]
+    fn edit_mask_only_changes_display() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::EditPassword);
+        s.apply(Action::ToggleEditMask);
+        assert!(s.viewer.as_ref().unwrap().mask_edit);
+        while !s.viewer.as_ref().unwrap().edit_buffer.is_empty() {
+            s.apply(Action::Backspace);
+        }
+        type_text(&mut s, "masked-secret");
+        s.apply(Action::Confirm);
+        let entries = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(entries[0].password, "masked-secret");
+    }
+
+    #[test]
+    fn duplicate_cleanup_keeps_chosen_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let store = s.storage.as_ref().unwrap();
+        let mut entries = store.load().unwrap();
+        for (name, password) in [
+            ("bank", "b1"),
+            ("Mail", "m2"),
+            ("BANK", "b2"),
+            ("bank", "b3"),
+        ] {
+            entries.push(PasswordEntry {
+                name: name.into(),
+                password: password.into(),
+                ..Default::default()
+            });
+        }
+        store.save_all(&entries).unwrap();
+
+        s.apply(Action::FindDuplicates);
+        assert!(s.phase == Phase::Duplicates { selected: 0 });
+        // Leave the two "mail" entries alone
+        s.apply(Action::SkipGroup);
+        // Keep the second "bank"
+        s.apply(Action::SelectNext);
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+
+        let left: Vec<_> = s.storage.as_ref().unwrap().load().unwrap();
+        let passwords: Vec<_> = left.iter().map(|e| e.password.as_str()).collect();
+        assert_eq!(left.len(), 3);
+        assert!(passwords.contains(&"m2") && passwords.contains(&"b2"));
+
+        s.apply(Action::FindDuplicates);
+        s.apply(Action::Cancel);
+        assert!(s.phase == Phase::Main);
+    }
+
+    #[test]
+    fn detail_view_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::OpenDetail);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(s.map_key(key('u')), Some(Action::CopyUsername));
+        assert_eq!(s.map_key(key('p')), Some(Action::CopySelected));
+        assert_eq!(s.map_key(key('l')), Some(Action::CopyUrl));
+
+        // The entry has no username, so nothing reaches the clipboard
+        s.apply(Action::CopyUsername);
+        let state = s.viewer.as_ref().unwrap();
+        assert_eq!(state.status().unwrap(), "Username is empty");
+
+        s.apply(Action::Cancel);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+    }
+
+    #[test]
+    fn navigation_wraps_only_when_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::PrevField);
+        assert!(s.app.active_field == InputField::Name);
+        s.apply(Action::OpenViewer);
+        s.apply(Action::SelectPrev);
+        assert_eq!(s.viewer.as_ref().unwrap().selected, 0);
+        s.apply(Action::CloseViewer);
+
+        s.config.wrap_navigation = true;
+        s.apply(Action::PrevField);
+        assert!(s.app.active_field == InputField::Generate);
+        s.apply(Action::NextField);
+        assert!(s.app.active_field == InputField::Name);
+    }
+
+    #[test]
+    fn online_breach_check_needs_opt_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::CheckBreachOnline);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+
+        // Even with the flag set, declining the prompt sends nothing
+        s.config.allow_network = true;
+        s.apply(Action::CheckBreachOnline);
+        assert!(s.contacts_network(Action::Confirm));
+        s.apply(Action::Cancel);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+        assert!(s.viewer.as_ref().unwrap().breach_report.is_none());
+    }
+
+    #[test]
+    fn rename_onto_existing_name_asks_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::DuplicateSelected);
+        // The copy is "mail (copy)"; rename it back onto "mail"
+        s.apply(Action::EditName);
+        s.viewer.as_mut().unwrap().edit_buffer.clear();
+        type_text(&mut s, "MAIL");
+        s.apply(Action::Confirm);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::ConfirmRename
+                }
+        );
+
+        // Declining returns to the edit with the typed name intact
+        s.apply(Action::Cancel);
+        assert_eq!(s.viewer.as_ref().unwrap().edit_buffer, "MAIL");
+        s.apply(Action::Confirm);
+        s.apply(Action::Confirm);
+        let names: Vec<_> = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(names[1].name, "MAIL");
+    }
+
+    #[test]
+    fn regenerate_reuses_entry_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let saved = s.storage.as_ref().unwrap().load().unwrap();
+        let length = saved[0].password.chars().count();
+        assert_eq!(saved[0].gen_options.as_ref().unwrap().length, length);
+
+        // Changing the form afterwards does not affect rotations
+        s.app.length_input = "40".into();
+        s.apply(Action::OpenViewer);
+        s.apply(Action::EditPassword);
+        s.apply(Action::RegeneratePassword);
+        assert_eq!(
+            s.viewer.as_ref().unwrap().edit_buffer.chars().count(),
+            length
+        );
+    }
+
+    #[test]
+    fn edit_entry_form_saves_all_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::EditEntry);
+        // Name, then Username
+        s.apply(Action::NextField);
+        type_text(&mut s, "me@example.com");
+        s.apply(Action::PrevField);
+        type_text(&mut s, "box");
+        s.apply(Action::NextField);
+        s.apply(Action::NextField);
+        s.apply(Action::NextField);
+        s.apply(Action::NextField);
+        type_text(&mut s, "recovery codes in the drawer");
+        s.apply(Action::Confirm);
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::Browse
+                }
+        );
+        let saved = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(saved[0].name, "mailbox");
+        assert_eq!(saved[0].username, "me@example.com");
+        assert_eq!(saved[0].notes, "recovery codes in the drawer");
+
+        // Esc leaves the vault untouched
+        s.apply(Action::EditEntry);
+        type_text(&mut s, "zzz");
+        s.apply(Action::Cancel);
+        assert!(s.storage.as_ref().unwrap().load().unwrap() == saved);
+    }
+
+    #[test]
+    fn expiry_is_set_in_form_and_reported_on_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::EditEntry);
+        // Expires is the last field, one step back from Name
+        s.apply(Action::PrevField);
+        type_text(&mut s, "2000-02-30");
+        s.apply(Action::Confirm);
+        let state = s.viewer.as_ref().unwrap();
+        assert!(state.status().unwrap().contains("Invalid expiry"));
+        assert!(
+            s.phase
+                == Phase::ViewPasswords {
+                    mode: ViewMode::EditEntry
+                }
+        );
+
+        for _ in 0..2 {
+            s.apply(Action::Backspace);
+        }
+        type_text(&mut s, "28");
+        s.apply(Action::Confirm);
+        let saved = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(saved[0].expires_at.as_deref(), Some("2000-02-28"));
+        assert!(is_expired(&saved[0]));
+
+        s.lock();
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        assert_eq!(s.app.status(), Some("⚠ 1 password has expired"));
+    }
+
+    #[test]
+    fn notes_editor_handles_lines_and_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::EditNotes);
+        type_text(&mut s, "codes:\n1111\n2222");
+        // Fix a typo on the middle line, then join the last line onto it
+        s.apply(Action::SelectPrev);
+        s.apply(Action::Backspace);
+        s.apply(Action::Input('9'));
+        s.apply(Action::SelectNext);
+        s.apply(Action::CharPrev);
+        s.apply(Action::CharPrev);
+        s.apply(Action::CharPrev);
+        s.apply(Action::CharPrev);
+        s.apply(Action::Backspace);
+        s.apply(Action::Input(' '));
+        let editor = s.viewer.as_ref().unwrap().notes.as_ref().unwrap();
+        assert_eq!(editor.text(), "codes:\n1119 2222");
+        assert_eq!(editor.cursor, (1, 5));
+
+        s.apply(Action::Confirm);
+        let saved = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(saved[0].notes, "codes:\n1119 2222");
+
+        // Esc throws the edit away
+        s.apply(Action::EditNotes);
+        type_text(&mut s, "\nmore");
+        s.apply(Action::Cancel);
+        assert_eq!(
+            s.storage.as_ref().unwrap().load().unwrap()[0].notes,
+            "codes:\n1119 2222"
+        );
+    }
+
+    #[test]
+    fn debounced_viewer_edits_flush_on_close_and_quit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.config.save_debounce_ms = 60_000;
+        s.lock();
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        let on_disk = || {
+            Storage::new_at("master", &dir.path().join("vault.enc"))
+                .unwrap()
+                .load()
+                .unwrap()
+        };
+
+        s.apply(Action::OpenViewer);
+        s.apply(Action::EditName);
+        type_text(&mut s, "2");
+        s.apply(Action::Confirm);
+        s.apply(Action::DuplicateSelected);
+        assert!(s.has_unsaved_changes());
+        assert_eq!(on_disk().len(), 1);
+        assert_eq!(on_disk()[0].name, "mail");
+
+        s.apply(Action::CloseViewer);
+        assert!(!s.has_unsaved_changes());
+        assert_eq!(on_disk().len(), 2);
+        assert_eq!(on_disk()[0].name, "mail2");
+
+        s.apply(Action::OpenViewer);
+        s.apply(Action::DeleteSelected);
+        s.apply(Action::Confirm);
+        assert_eq!(on_disk().len(), 2);
+        s.apply(Action::Quit);
+        assert!(s.quit);
+        assert_eq!(on_disk().len(), 1);
+    }
+
+    #[test]
+    fn master_password_change_keeps_debounced_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.config.save_debounce_ms = 60_000;
+        s.lock();
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        s.app.name_input = "bank".into();
+        s.apply(Action::GenerateAndSave);
+        assert!(s.has_unsaved_changes());
+
+        s.apply(Action::ChangeMasterPassword);
+        type_text(&mut s, "wrong");
+        s.apply(Action::Confirm);
+        assert!(s.app.error.is_some());
+        assert!(s.storage.as_ref().unwrap().load().is_ok());
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        type_text(&mut s, "changed");
+        s.apply(Action::Confirm);
+        type_text(&mut s, "changed");
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+
+        let on_disk = Storage::new_at("changed", &dir.path().join("vault.enc"))
+            .unwrap()
+            .load()
+            .unwrap();
+        let names: Vec<&str> = on_disk.iter().map(|e| e.name.as_str()).collect();
+        assert!(names.contains(&"mail") && names.contains(&"bank"));
+    }
+
+    #[test]
+    fn unsaved_changes_show_and_guard_quit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.config.save_debounce_ms = 60_000;
+        s.lock();
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+
+        s.apply(Action::OpenViewer);
+        s.apply(Action::DuplicateSelected);
+        assert!(s.app.dirty);
+        let key = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(s.map_key(key), Some(Action::SaveNow));
+        s.apply(Action::SaveNow);
+        assert!(!s.app.dirty);
+
+        // Another instance writes meanwhile, so the held change cannot be flushed
+        s.apply(Action::DuplicateSelected);
+        let other = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        other.load().unwrap();
+        other.save(PasswordEntry::default()).unwrap();
+        s.apply(Action::Quit);
+        assert!(!s.quit);
+        assert!(s.confirm.is_some());
+        s.apply(Action::Cancel);
+        assert!(!s.quit && s.app.dirty);
+        s.apply(Action::Quit);
+        s.apply(Action::Confirm);
+        assert!(s.quit);
+    }
+
+    #[test]
+    fn compact_reports_and_respects_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let key = KeyEvent::new(KeyCode::Char('k'), KeyModifiers::CONTROL);
+        assert_eq!(s.map_key(key), Some(Action::CompactVault));
+        s.apply(Action::CompactVault);
+        assert!(s.app.status().unwrap().starts_with("✓ Vault compacted"));
+
+        s.app.read_only = true;
+        s.apply(Action::CompactVault);
+        assert!(s.app.status().unwrap().starts_with("Read-only mode"));
+    }
+
+    #[test]
+    fn audit_screen_lists_entries_needing_attention() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let key = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+        assert_eq!(s.map_key(key), Some(Action::OpenAudit));
+        s.apply(Action::OpenAudit);
+        assert!(s.phase == Phase::Audit);
+        assert!(s.audit.is_some());
+        assert_eq!(s.map_key(key), Some(Action::Cancel));
+        s.apply(Action::Cancel);
+        assert!(s.phase == Phase::Main);
+        assert!(s.audit.is_none());
+    }
+
+    #[test]
+    fn seeded_generation_repeats_and_never_saves() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let ctrl_g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL);
+        assert_ne!(s.map_key(ctrl_g), Some(Action::EnterSeed));
+
+        s.app.seeded = true;
+        assert_eq!(s.map_key(ctrl_g), Some(Action::EnterSeed));
+        s.apply(Action::EnterSeed);
+        type_text(&mut s, "seven");
+        s.apply(Action::Confirm);
+        assert!(s.app.error.is_some());
+        s.prompt_input = "7".into();
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+
+        s.app.name_input = "bank".into();
+        s.apply(Action::GenerateAndSave);
+        let first = s.app.generated_password.clone();
+        s.apply(Action::GenerateAndSave);
+        assert!(first.is_some());
+        assert_eq!(s.app.generated_password, first);
+        assert_eq!(s.app.last_seed, Some(7));
+        assert_eq!(s.storage.as_ref().unwrap().load().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn target_bits_sets_the_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::SuggestLength);
+        assert!(s.phase == Phase::TargetBits);
+        type_text(&mut s, "lots");
+        s.apply(Action::Confirm);
+        assert!(s.app.error.is_some());
+        assert!(s.phase == Phase::TargetBits);
+
+        s.prompt_input.clear();
+        type_text(&mut s, "128");
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+        let opts = s.app.gen_options().unwrap();
+        assert!(opts.entropy_bits() >= 128.0);
+        let shorter = GenOptions {
+            length: opts.length - 1,
+            ..opts
+        };
+        assert!(shorter.entropy_bits() < 128.0);
+
+        s.app.use_letters = false;
+        s.app.use_numbers = false;
+        s.app.use_special = false;
+        s.app.use_extended = false;
+        s.app.trailing_digit = false;
+        s.app.trailing_special = false;
+        assert!(s.app.suggest_length(128.0).is_err());
+    }
+
+    #[test]
+    fn number_keys_jump_between_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        let digit = |c: char, modifiers| KeyEvent::new(KeyCode::Char(c), modifiers);
+        s.app.active_field = InputField::Name;
+        // Digits are text in the name and length fields, unless Alt is held
+        assert_eq!(
+            s.map_key(digit('4', KeyModifiers::NONE)),
+            Some(Action::Input('4'))
+        );
+        assert_eq!(
+            s.map_key(digit('4', KeyModifiers::ALT)),
+            Some(Action::JumpToField(InputField::ToggleLetters))
+        );
+        s.apply(Action::JumpToField(InputField::ToggleLetters));
+        assert_eq!(
+            s.map_key(digit('6', KeyModifiers::NONE)),
+            Some(Action::JumpToField(InputField::Generate))
+        );
+        assert_eq!(
+            s.map_key(digit('2', KeyModifiers::NONE)),
+            Some(Action::JumpToField(InputField::Length))
+        );
+        assert!(!matches!(
+            s.map_key(digit('9', KeyModifiers::ALT)),
+            Some(Action::JumpToField(_))
+        ));
+    }
+
+    #[test]
+    fn explicit_save_waits_for_ctrl_s() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = session(dir.path());
+        s.config.explicit_save = true;
+        s.app.explicit_save = true;
+        s.apply(Action::Confirm);
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        type_text(&mut s, "mail");
+        s.apply(Action::GenerateAndSave);
+        assert!(s.app.generated_password.is_some());
+        assert!(s.storage.as_ref().unwrap().load().unwrap().is_empty());
+
+        let key = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(s.map_key(key), Some(Action::SaveNow));
+        s.apply(Action::SaveNow);
+        let saved = s.storage.as_ref().unwrap().load().unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(
+            &saved[0].password,
+            s.app.generated_password.as_ref().unwrap()
+        );
+        // Saving again does not store a second copy
+        s.apply(Action::SaveNow);
+        assert_eq!(s.storage.as_ref().unwrap().load().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sorting_reorders_only_the_view() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.app.name_input = "bank".into();
+        s.apply(Action::GenerateAndSave);
+        s.apply(Action::OpenViewer);
+        let state = s.viewer.as_ref().unwrap();
+        assert_eq!(state.filtered_indices, vec![0, 1]);
+
+        s.apply(Action::CycleSort);
+        assert_eq!(s.viewer.as_ref().unwrap().filtered_indices, vec![1, 0]);
+        s.apply(Action::CycleSort);
+        assert_eq!(s.viewer.as_ref().unwrap().filtered_indices, vec![0, 1]);
+        // Navigation follows the displayed order
+        s.apply(Action::CycleSort);
+        s.apply(Action::CycleSort);
+        s.apply(Action::CycleSort);
+        s.apply(Action::CycleSort);
+        assert_eq!(s.viewer.as_ref().unwrap().sort, SortMode::Name);
+        assert_eq!(s.viewer.as_ref().unwrap().selected, 0);
+        s.apply(Action::SelectPrev);
+        assert_eq!(s.viewer.as_ref().unwrap().selected, 1);
+        assert_eq!(s.storage.as_ref().unwrap().load().unwrap()[0].name, "mail");
+    }
+
+    #[test]
+    fn transfer_moves_entry_to_other_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = dir.path().join("work.enc");
+        Storage::new_at("other", &work)
+            .unwrap()
+            .save_all(&[])
+            .unwrap();
+
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::TransferEntry);
+        type_text(&mut s, work.to_str().unwrap());
+        s.apply(Action::Confirm);
+        // A wrong password leaves both vaults alone
+        type_text(&mut s, "wrong");
+        s.apply(Action::Confirm);
+        assert_eq!(s.storage.as_ref().unwrap().load().unwrap().len(), 1);
+
+        s.apply(Action::TransferEntry);
+        type_text(&mut s, work.to_str().unwrap());
+        s.apply(Action::Confirm);
+        type_text(&mut s, "other");
+        s.apply(Action::Confirm);
+        assert!(s.storage.as_ref().unwrap().load().unwrap().is_empty());
+        assert!(s.viewer.as_ref().unwrap().entries.is_empty());
+        let moved = Storage::new_at("other", &work).unwrap().load().unwrap();
+        assert_eq!(moved[0].name, "mail");
+    }
+
+    #[test]
+    fn reveal_stamps_last_used_only_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        s.apply(Action::ToggleReveal);
+        assert!(
+            s.storage.as_ref().unwrap().load().unwrap()[0]
+                .last_used
+                .is_none()
+        );
+
+        s.config.track_last_used = true;
+        s.apply(Action::ToggleReveal);
+        // Hiding again is not a use
+        assert!(s.viewer.as_ref().unwrap().entries[0].last_used.is_none());
+        s.apply(Action::ToggleReveal);
+        let stamp = s.storage.as_ref().unwrap().load().unwrap()[0]
+            .last_used
+            .clone();
+        assert!(stamp.is_some());
+        assert!(s.viewer.as_ref().unwrap().entries[0].last_used == stamp);
+    }
+
+    #[test]
+    fn lock_forgets_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.config.allow_reveal_all = true;
+        s.apply(Action::OpenViewer);
+        s.apply(Action::RevealAll);
+        let ctrl_l = KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL);
+        assert_eq!(s.map_key(ctrl_l), Some(Action::Lock));
+
+        s.apply(Action::Lock);
+        assert!(s.phase == Phase::MasterPassword);
+        assert!(s.storage.is_none() && s.viewer.is_none());
+        assert!(s.app.recent.is_empty() && s.app.generated_password.is_none());
+        assert_eq!(s.map_key(ctrl_l), Some(Action::Input('l')));
+
+        type_text(&mut s, "master");
+        s.apply(Action::Confirm);
+        assert!(s.phase == Phase::Main);
+    }
+
+    #[test]
+    fn esc_clears_filter_before_closing() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "mail");
+        s.apply(Action::OpenViewer);
+        let esc = KeyEvent::from(KeyCode::Esc);
+        assert_eq!(s.map_key(esc), Some(Action::CloseViewer));
+
+        s.apply(Action::StartSearch);
+        type_text(&mut s, "zzz");
+        s.apply(Action::Confirm);
+        assert_eq!(s.map_key(esc), Some(Action::ClearSearch));
+    }
+
+    #[test]
+    fn template_mode_edits_the_pattern_in_the_length_field() {
+        use crate::passgen_core::generator::GenMode;
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = unlocked_with_entry(dir.path(), "first");
+        s.apply(Action::JumpToField(InputField::Mode));
+        while s.app.mode != GenMode::Template {
+            s.apply(Action::ToggleField);
+        }
+        s.apply(Action::GenerateAndSave);
+        assert!(s.app.error.is_some());
+
+        s.apply(Action::JumpToField(InputField::Length));
+        type_text(&mut s, "pin-####");
+        assert_eq!(s.app.length_input, "16");
+        s.app.name_input = "door".into();
+        s.apply(Action::GenerateAndSave);
+        let password = s.app.generated_password.clone().unwrap();
+        assert!(password.starts_with("pin-") && password[4..].bytes().all(|b| b.is_ascii_digit()));
+        let saved = s.storage.as_ref().unwrap().load().unwrap();
+        let entry = saved.iter().find(|e| e.name == "door").unwrap();
+        assert_eq!(entry.password, password);
+        assert!(s.app.suggest_length(64.0).is_err());
+    }
+}
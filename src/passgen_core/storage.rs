@@ -1,16 +1,122 @@
-use aes_gcm::{Aes256Gcm, KeyInit, Nonce, aead::Aead};
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use chacha20poly1305::ChaCha20Poly1305;
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use log::{debug, info, warn};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::ffi::OsString;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use unicode_normalization::UnicodeNormalization;
+
+use super::app::weak_passwords;
+use super::generator::GenOptions;
+use super::logging::{redact, remember_secrets};
 
 /// A single password entry
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct PasswordEntry {
     pub name: String,
     pub password: String,
     pub created_at: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When the password was last copied or revealed (Unix seconds), if tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<String>,
+    /// Rules the password was generated with, reused when rotating it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gen_options: Option<GenOptions>,
+    /// Date (YYYY-MM-DD, UTC) from which the password is due for rotation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+impl PasswordEntry {
+    /// First text field containing `query` (already lowercased), if any
+    pub fn matched_field(&self, query: &str) -> Option<&'static str> {
+        let contains = |field: &str| field.to_lowercase().contains(query);
+        if contains(&self.name) {
+            Some("name")
+        } else if contains(&self.username) {
+            Some("username")
+        } else if contains(&self.url) {
+            Some("url")
+        } else if contains(&self.notes) {
+            Some("notes")
+        } else if self.tags.iter().any(|t| contains(t)) {
+            Some("tags")
+        } else {
+            None
+        }
+    }
+
+    /// Fill `{name}`, `{username}`, `{url}` and `{password}` in `template`
+    pub fn format(&self, template: &str) -> String {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let field = rest.find('}').and_then(|end| {
+                let value = match &rest[1..end] {
+                    "name" => &self.name,
+                    "username" => &self.username,
+                    "url" => &self.url,
+                    "password" => &self.password,
+                    _ => return None,
+                };
+                Some((value, end))
+            });
+            match field {
+                Some((value, end)) => {
+                    out.push_str(value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// URL ready to hand to the browser, with `https://` added when no scheme is
+    /// given. Other schemes are refused: imported URLs could launch local handlers
+    pub fn launch_url(&self) -> Result<String, String> {
+        let url = self.url.trim();
+        if url.is_empty() {
+            return Err("No URL saved for this entry — press [u] to add one".into());
+        }
+        let Some((scheme, _)) = url.split_once("://") else {
+            return Ok(format!("https://{}", url));
+        };
+        if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") {
+            Ok(url.to_string())
+        } else {
+            Err(format!(
+                "Only http and https links are opened, not {}://",
+                scheme
+            ))
+        }
+    }
 }
 
 /// The encrypted file format
@@ -19,22 +125,703 @@ struct EncryptedStore {
     salt: String,       // Base64 encoded
     nonce: String,      // Base64 encoded
     ciphertext: String, // Base64 encoded
+    #[serde(default = "default_compression")]
+    compression: String, // Applied to the plaintext before encryption
+    #[serde(default)]
+    keyfile_required: bool, // Key derivation mixes in a keyfile digest
+    #[serde(default)]
+    challenge_response: bool, // Key derivation mixes in a YubiKey HMAC response
+    #[serde(default = "default_iterations")]
+    iterations: u32, // Key derivation rounds chosen when the vault was created
+    #[serde(default)]
+    algorithm: Cipher, // AEAD used for the ciphertext
+    #[serde(default)]
+    nonce_counter: u64, // Saves made under the current key, mixed into the nonce
+    #[serde(default)]
+    version: u32, // File format version; 0 for files written before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wrapped_dek: Option<String>, // Base64 nonce + data key sealed under the master key, version 2 on
+}
+
+/// Format version written by this build. From version 1 on, the ciphertext is
+/// authenticated together with the version and salt. From version 2 on, the
+/// entries are sealed under a random data key and only that key under the
+/// master key, so a new master password re-wraps 32 bytes instead of the vault.
+const FORMAT_VERSION: u32 = 2;
+
+/// Associated data binding a ciphertext to its file's version and salt
+fn associated_data(version: u32, salt: &[u8]) -> Vec<u8> {
+    if version == 0 {
+        return Vec::new();
+    }
+    version.to_be_bytes().iter().chain(salt).copied().collect()
+}
+
+/// Associated data for the entries. With a wrapped data key the salt is bound
+/// to the wrapped key instead, since re-wrapping changes it without touching them.
+fn entries_aad(version: u32, salt: &[u8]) -> Vec<u8> {
+    if version >= 2 {
+        associated_data(version, &[])
+    } else {
+        associated_data(version, salt)
+    }
+}
+
+/// Nonce for save number `counter`: 8 counter bytes then 4 random bytes.
+/// The counter half makes reuse under one key structurally impossible while
+/// the file's counter only grows; the random half covers a rolled-back file.
+fn make_nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(&counter.to_be_bytes());
+    rand::rng().fill_bytes(&mut nonce[8..]);
+    nonce
+}
+
+/// Zero-width, soft-hyphen and direction-override characters: invisible, yet
+/// they make names that look the same compare different
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+/// Whether `c` may appear in an entry name at all
+pub fn allowed_in_name(c: char) -> bool {
+    !c.is_control() && !is_invisible(c)
+}
+
+/// `name` without control or invisible characters and surrounding whitespace,
+/// in NFC form. Empty means the name is unusable
+pub fn sanitize_name(name: &str) -> String {
+    let kept: String = name.chars().filter(|&c| allowed_in_name(c)).collect();
+    kept.trim().nfc().collect()
+}
+
+/// Form of a name used to decide whether two entries clash
+fn name_key(name: &str) -> String {
+    sanitize_name(name).to_lowercase()
+}
+
+/// Whether an entry other than `except` already uses `name`
+pub fn name_exists(entries: &[PasswordEntry], name: &str, except: Option<usize>) -> bool {
+    let key = name_key(name);
+    entries
+        .iter()
+        .enumerate()
+        .any(|(i, e)| Some(i) != except && name_key(&e.name) == key)
+}
+
+/// Whether the entry has reached its expiry date
+pub fn is_expired(entry: &PasswordEntry) -> bool {
+    entry
+        .expires_at
+        .as_deref()
+        .is_some_and(|date| date <= today().as_str())
+}
+
+/// Today's date in UTC as YYYY-MM-DD
+pub fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Validate an expiry typed as YYYY-MM-DD; blank clears it
+pub fn parse_expiry(input: &str) -> Result<Option<String>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || format!("Invalid expiry date '{}': use YYYY-MM-DD", input);
+    let parts: Vec<&str> = input.split('-').collect();
+    let [y, m, d] = parts[..] else {
+        return Err(invalid());
+    };
+    if y.len() != 4 || m.len() != 2 || d.len() != 2 {
+        return Err(invalid());
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (y.parse::<u32>(), m.parse::<u32>(), d.parse::<u32>())
+    else {
+        return Err(invalid());
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return Err(invalid()),
+    };
+    if day == 0 || day > days_in_month {
+        return Err(invalid());
+    }
+    Ok(Some(input.to_string()))
+}
+
+/// Indices of entries sharing a name (ignoring case and surrounding space),
+/// one group per name with more than one entry, in vault order
+pub fn duplicate_groups(entries: &[PasswordEntry]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let key = name_key(&entry.name);
+        match groups.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .collect()
+}
+
+/// Indices of entries sharing the exact same password, one group per
+/// password used more than once, in vault order
+pub fn reused_passwords(entries: &[PasswordEntry]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        match groups.iter_mut().find(|(pw, _)| *pw == entry.password) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((&entry.password, vec![i])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .collect()
+}
+
+/// What `Storage::verify_integrity` found
+#[derive(Debug, Default)]
+pub struct VaultStats {
+    pub entries: usize,
+    /// One line per entry field that fails validation
+    pub problems: Vec<String>,
+    /// Set when the vault file is readable by other users
+    pub permissions: Option<String>,
+}
+
+impl VaultStats {
+    /// No problems and safe permissions
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty() && self.permissions.is_none()
+    }
+}
+
+impl fmt::Display for VaultStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} entries decrypted", self.entries)?;
+        for problem in &self.problems {
+            writeln!(f, "✗ {}", problem)?;
+        }
+        match &self.permissions {
+            Some(warning) => writeln!(f, "{}", warning)?,
+            None => writeln!(f, "✓ Permissions are owner-only")?,
+        }
+        if self.is_healthy() {
+            write!(f, "✓ Vault is healthy")
+        } else {
+            write!(
+                f,
+                "{} problem(s) found",
+                self.problems.len() + usize::from(self.permissions.is_some())
+            )
+        }
+    }
+}
+
+/// What `Storage::compact` did
+#[derive(Debug, Default, PartialEq)]
+pub struct CompactReport {
+    pub entries: usize,
+    /// Entries that had blank optional fields or tags removed
+    pub tidied: usize,
+    /// File size before and after the rewrite, in bytes
+    pub before: u64,
+    pub after: u64,
+}
+
+impl CompactReport {
+    pub fn bytes_saved(&self) -> u64 {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+impl fmt::Display for CompactReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Rewrote {} entries ({} tidied): {} → {} bytes, {} saved",
+            self.entries,
+            self.tidied,
+            self.before,
+            self.after,
+            self.bytes_saved()
+        )
+    }
+}
+
+/// Drop blank optional fields and blank or repeated tags; true if anything changed
+fn compact_entry(entry: &mut PasswordEntry) -> bool {
+    let before = entry.clone();
+    for field in [&mut entry.last_used, &mut entry.expires_at] {
+        if field.as_deref().is_some_and(|v| v.trim().is_empty()) {
+            *field = None;
+        }
+    }
+    let mut tags: Vec<String> = Vec::new();
+    for tag in entry.tags.drain(..) {
+        if !tag.trim().is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    entry.tags = tags;
+    *entry != before
+}
+
+/// What `Storage::audit` found, by entry name; passwords are never included
+#[derive(Debug, Default, PartialEq)]
+pub struct AuditReport {
+    pub entries: usize,
+    pub weak: Vec<String>,
+    /// One group per password shared by several entries
+    pub reused: Vec<Vec<String>>,
+    pub expired: Vec<String>,
+}
+
+impl AuditReport {
+    pub fn compute(entries: &[PasswordEntry]) -> Self {
+        let names = |indices: &[usize]| -> Vec<String> {
+            indices.iter().map(|&i| entries[i].name.clone()).collect()
+        };
+        Self {
+            entries: entries.len(),
+            weak: names(&weak_passwords(entries)),
+            reused: reused_passwords(entries)
+                .iter()
+                .map(|group| names(group))
+                .collect(),
+            expired: entries
+                .iter()
+                .filter(|e| is_expired(e))
+                .map(|e| e.name.clone())
+                .collect(),
+        }
+    }
+
+    /// Number of findings; an entry is counted once per reason
+    pub fn issues(&self) -> usize {
+        self.weak.len() + self.reused.iter().map(Vec::len).sum::<usize>() + self.expired.len()
+    }
+
+    /// One line per finding, grouped by reason
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        lines.extend(
+            self.weak
+                .iter()
+                .map(|name| format!("✗ {}: weak password", name)),
+        );
+        for group in &self.reused {
+            for (i, name) in group.iter().enumerate() {
+                let others: Vec<&str> = group
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, other)| other.as_str())
+                    .collect();
+                lines.push(format!(
+                    "✗ {}: password reused by {}",
+                    name,
+                    others.join(", ")
+                ));
+            }
+        }
+        lines.extend(
+            self.expired
+                .iter()
+                .map(|name| format!("✗ {}: expired", name)),
+        );
+        lines
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} entries audited", self.entries)?;
+        for line in self.lines() {
+            writeln!(f, "{}", line)?;
+        }
+        match self.issues() {
+            0 => write!(f, "✓ Nothing needs attention"),
+            n => write!(f, "{} issue(s) found", n),
+        }
+    }
+}
+
+/// Environment variable that overrides the default vault location
+pub const VAULT_PATH_ENV: &str = "PASSGEN_VAULT_PATH";
+
+/// File name of the vault inside the home (or fallback) directory
+const VAULT_FILE_NAME: &str = ".passgen_vault.enc";
+
+/// Order for `Storage::iter_sorted`
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum SortMode {
+    /// As stored in the vault
+    #[default]
+    Stored,
+    /// Case-insensitive by name
+    Name,
+    /// Oldest first
+    Created,
+}
+
+impl SortMode {
+    /// How `a` orders against `b` in this mode; `Stored` treats all as equal
+    pub fn compare(self, a: &PasswordEntry, b: &PasswordEntry) -> std::cmp::Ordering {
+        match self {
+            SortMode::Stored => std::cmp::Ordering::Equal,
+            SortMode::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            // Timestamps are Unix seconds; unparsable ones sort first
+            SortMode::Created => {
+                let created = |e: &PasswordEntry| e.created_at.parse::<u64>().unwrap_or(0);
+                created(a).cmp(&created(b))
+            }
+        }
+    }
+}
+
+/// AEAD cipher protecting the vault contents
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Cipher {
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    /// Fast in software, for machines without AES acceleration
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// Parse the name used in the file format and on the command line
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "aes-256-gcm" => Ok(Self::Aes256Gcm),
+            "chacha20-poly1305" => Ok(Self::ChaCha20Poly1305),
+            other => Err(format!(
+                "Unknown cipher '{}' (use aes-256-gcm or chacha20-poly1305)",
+                other
+            )),
+        }
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8; 32],
+        nonce: &[u8],
+        plaintext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let nonce = Nonce::from_slice(nonce);
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+        match self {
+            Self::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .map_err(|e| format!("Cipher init failed: {}", e))?
+                .encrypt(nonce, payload),
+            Self::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+                .map_err(|e| format!("Cipher init failed: {}", e))?
+                .encrypt(nonce, payload),
+        }
+        .map_err(|e| format!("Encryption failed: {}", e))
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8; 32],
+        nonce: &[u8],
+        ciphertext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        if nonce.len() != 12 {
+            return Err("Invalid nonce length".into());
+        }
+        let nonce = Nonce::from_slice(nonce);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        match self {
+            Self::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .map_err(|e| format!("Cipher init failed: {}", e))?
+                .decrypt(nonce, payload),
+            Self::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+                .map_err(|e| format!("Cipher init failed: {}", e))?
+                .decrypt(nonce, payload),
+        }
+        .map_err(|_| "Decryption failed - wrong master password?".to_string())
+    }
+}
+
+/// Key derivation rounds used by vaults that predate calibration
+const LEGACY_ITERATIONS: u32 = 10_000;
+
+/// Upper bound so a misbehaving clock can't make a vault unopenable
+const MAX_ITERATIONS: u32 = 50_000_000;
+
+/// Unlock time new vaults are calibrated for
+pub const TARGET_UNLOCK_TIME: Duration = Duration::from_millis(250);
+
+fn default_iterations() -> u32 {
+    LEGACY_ITERATIONS
+}
+
+/// Compression used for newly written vaults
+const COMPRESSION_DEFLATE: &str = "deflate";
+
+/// Files written before compression existed hold raw JSON
+fn default_compression() -> String {
+    "none".into()
+}
+
+/// Compress serialized entries ahead of encryption
+fn compress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .map_err(|e| format!("Compression failed: {}", e))?;
+    encoder
+        .finish()
+        .map_err(|e| format!("Compression failed: {}", e))
+}
+
+/// Undo `compress` according to the store's compression tag
+fn decompress(data: Vec<u8>, compression: &str) -> Result<Vec<u8>, String> {
+    match compression {
+        "none" => Ok(data),
+        COMPRESSION_DEFLATE => {
+            let mut out = Vec::new();
+            DeflateDecoder::new(data.as_slice())
+                .read_to_end(&mut out)
+                .map_err(|e| format!("Decompression failed: {}", e))?;
+            Ok(out)
+        }
+        other => Err(format!("Unsupported compression: {}", other)),
+    }
+}
+
+/// The non-secret part of an entry, as listed in the metadata manifest
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    name: &'a str,
+    url: &'a str,
+    tags: &'a [String],
+}
+
+fn write_manifest(entries: &[PasswordEntry], dest: &Path) -> Result<(), String> {
+    let mut listed: Vec<ManifestEntry> = entries
+        .iter()
+        .map(|e| ManifestEntry {
+            name: &e.name,
+            url: &e.url,
+            tags: &e.tags,
+        })
+        .collect();
+    listed.sort_by(|a, b| {
+        (a.name.to_lowercase(), a.name, a.url).cmp(&(b.name.to_lowercase(), b.name, b.url))
+    });
+    let mut json = serde_json::to_string_pretty(&listed)
+        .map_err(|e| format!("Serialization failed: {}", e))?;
+    json.push('\n');
+    write_private(dest, json.as_bytes()).map_err(|e| format!("Manifest not written: {}", e))
+}
+
+/// Write a file readable only by its owner (0o600 on Unix)
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        // New files never exist with looser permissions
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // Existing files keep their old mode on open, so tighten it before writing
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    }
+
+    file.write_all(contents)
+        .map_err(|e| format!("Failed to write file: {}", e))
+}
+
+/// Why a write to the vault failed
+#[derive(Debug, PartialEq)]
+pub enum StorageError {
+    /// The file changed on disk since this instance last loaded it
+    Conflict,
+    /// An entry is no longer at the index it was loaded from
+    Drifted,
+    Other(String),
+}
+
+/// Held changes still reach the file when a session ends on an error or a panic
+impl Drop for Storage {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("unsaved changes lost: {}", e);
+        }
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Conflict => write!(
+                f,
+                "The vault was changed by another instance; reload it before saving"
+            ),
+            Self::Drifted => write!(
+                f,
+                "The vault changed elsewhere and this entry moved; reload before editing"
+            ),
+            Self::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<String> for StorageError {
+    fn from(e: String) -> Self {
+        Self::Other(e)
+    }
+}
+
+impl From<StorageError> for String {
+    fn from(e: StorageError) -> Self {
+        e.to_string()
+    }
 }
 
 /// Password storage manager
 pub struct Storage {
     file_path: PathBuf,
+    /// SHA-256 of the vault file as we last loaded or wrote it
+    seen: Cell<Option<[u8; 32]>>,
+    /// Program and arguments to run after each save, see `Config::post_save_command`
+    post_save_command: Option<Vec<String>>,
+    /// Where to keep the plaintext metadata manifest in sync, if anywhere
+    manifest_path: Option<PathBuf>,
+    /// Why the last manifest write or post-save command failed, until taken
+    after_save_error: RefCell<Option<String>>,
+    /// How long saves are held in memory after the last change, if at all
+    debounce: Option<Duration>,
+    /// Entries saved while debouncing but not yet written, and when they changed
+    pending: RefCell<Option<(Vec<PasswordEntry>, Instant)>>,
     master_key: [u8; 32],
+    salt: Vec<u8>,
+    keyfile: Option<[u8; 32]>,
+    challenge_response: bool,
+    iterations: u32,
+    cipher: Cipher,
 }
 
 impl Storage {
     /// Create a new storage with a master password
     pub fn new(master_password: &str) -> Result<Self, String> {
-        let file_path = Self::default_path()?;
+        Self::new_at(master_password, &Self::default_path()?)
+    }
+
+    /// Create a storage for a vault at an explicit path
+    pub fn new_at(master_password: &str, path: &Path) -> Result<Self, String> {
+        Self::open(master_password, path, None, false, Cipher::default())
+    }
 
-        // Derive key from master password
-        // If file exists, use its salt; otherwise generate new
-        let (master_key, _salt) = if file_path.exists() {
+    /// Create a storage whose key also depends on the contents of a keyfile
+    pub fn new_with_keyfile(master_password: &str, keyfile: &Path) -> Result<Self, String> {
+        Self::new_with_factors(master_password, Some(keyfile), false)
+    }
+
+    /// Create a storage with any combination of second factors.
+    /// `challenge_response` only matters for new vaults; existing ones record it in the file.
+    pub fn new_with_factors(
+        master_password: &str,
+        keyfile: Option<&Path>,
+        challenge_response: bool,
+    ) -> Result<Self, String> {
+        Self::new_with_factors_at(
+            master_password,
+            &Self::default_path()?,
+            keyfile,
+            challenge_response,
+            Cipher::default(),
+        )
+    }
+
+    /// Like `new_with_factors`, for a vault at an explicit path.
+    /// `cipher` is likewise only used when the vault doesn't exist yet.
+    pub fn new_with_factors_at(
+        master_password: &str,
+        path: &Path,
+        keyfile: Option<&Path>,
+        challenge_response: bool,
+        cipher: Cipher,
+    ) -> Result<Self, String> {
+        let digest = keyfile.map(Self::keyfile_digest).transpose()?;
+        Self::open(master_password, path, digest, challenge_response, cipher)
+    }
+
+    /// Hash a keyfile's bytes into fixed-size key material
+    fn keyfile_digest(keyfile: &Path) -> Result<[u8; 32], String> {
+        let bytes = fs::read(keyfile).map_err(|e| format!("Failed to read keyfile: {}", e))?;
+        if bytes.is_empty() {
+            return Err("Keyfile is empty".into());
+        }
+        Ok(Sha256::digest(&bytes).into())
+    }
+
+    fn open(
+        master_password: &str,
+        path: &Path,
+        keyfile: Option<[u8; 32]>,
+        challenge_response: bool,
+        cipher: Cipher,
+    ) -> Result<Self, String> {
+        let file_path = path.to_path_buf();
+
+        // If file exists, use its salt, work factor, cipher and factor flags; otherwise generate new
+        let (salt, challenge_response, iterations, cipher) = if file_path.exists() {
             let content = fs::read_to_string(&file_path)
                 .map_err(|e| format!("Failed to read file: {}", e))?;
             let store: EncryptedStore = serde_json::from_str(&content)
@@ -42,27 +829,131 @@ impl Storage {
             let salt = BASE64
                 .decode(&store.salt)
                 .map_err(|e| format!("Invalid salt: {}", e))?;
-            (Self::derive_key(master_password, &salt), salt)
+            (
+                salt,
+                store.challenge_response,
+                store.iterations,
+                store.algorithm,
+            )
         } else {
             let mut salt = [0u8; 16];
             rand::rng().fill_bytes(&mut salt);
-            (Self::derive_key(master_password, &salt), salt.to_vec())
+            let iterations = Self::calibrate_iterations(TARGET_UNLOCK_TIME);
+            (salt.to_vec(), challenge_response, iterations, cipher)
+        };
+
+        info!(
+            "opening {} ({:?}, {} iterations, keyfile: {}, challenge-response: {})",
+            file_path.display(),
+            cipher,
+            iterations,
+            keyfile.is_some(),
+            challenge_response
+        );
+
+        // Derive key from master password plus any second factors
+        let response = if challenge_response {
+            Some(Self::hardware_response(&salt)?)
+        } else {
+            None
         };
+        let master_key = Self::derive_key(
+            master_password,
+            &salt,
+            &Self::factor_material(keyfile.as_ref(), response.as_ref()),
+            iterations,
+        );
 
         Ok(Self {
             file_path,
+            seen: Cell::new(None),
+            post_save_command: None,
+            manifest_path: None,
+            after_save_error: RefCell::new(None),
+            debounce: None,
+            pending: RefCell::new(None),
             master_key,
+            salt,
+            keyfile,
+            challenge_response,
+            iterations,
+            cipher,
         })
     }
 
-    /// Get default storage path
-    fn default_path() -> Result<PathBuf, String> {
-        let home = dirs::home_dir().ok_or_else(|| "Cannot find home directory".to_string())?;
-        Ok(home.join(".passgen_vault.enc"))
+    /// Concatenate second-factor digests into extra key derivation input
+    fn factor_material(keyfile: Option<&[u8; 32]>, response: Option<&[u8; 32]>) -> Vec<u8> {
+        keyfile
+            .into_iter()
+            .chain(response)
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Ask the hardware token to answer a challenge derived from the salt
+    #[cfg(feature = "yubikey")]
+    fn hardware_response(salt: &[u8]) -> Result<[u8; 32], String> {
+        let challenge = Sha256::digest(salt);
+        let response = super::yubikey::challenge_response(&challenge)?;
+        Ok(Sha256::digest(&response).into())
+    }
+
+    #[cfg(not(feature = "yubikey"))]
+    fn hardware_response(_salt: &[u8]) -> Result<[u8; 32], String> {
+        Err("This vault requires a YubiKey; rebuild with `--features yubikey`".into())
+    }
+
+    /// Get default storage path: `$PASSGEN_VAULT_PATH`, else the home directory,
+    /// else the working directory
+    pub fn default_path() -> Result<PathBuf, String> {
+        Self::default_path_from(std::env::var_os(VAULT_PATH_ENV), dirs::home_dir())
+    }
+
+    fn default_path_from(
+        env_override: Option<OsString>,
+        home: Option<PathBuf>,
+    ) -> Result<PathBuf, String> {
+        if let Some(path) = env_override.filter(|p| !p.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        let dir = match home {
+            Some(home) => home,
+            None => std::env::current_dir().map_err(|e| {
+                format!(
+                    "No home directory or working directory ({}); set {} to the vault file",
+                    e, VAULT_PATH_ENV
+                )
+            })?,
+        };
+        Ok(dir.join(VAULT_FILE_NAME))
+    }
+
+    /// Explanation to show when the vault fell back to the working directory
+    pub fn home_fallback_notice() -> Option<String> {
+        if std::env::var_os(VAULT_PATH_ENV).is_some_and(|p| !p.is_empty())
+            || dirs::home_dir().is_some()
+        {
+            return None;
+        }
+        Some(format!(
+            "No home directory: vault is in the working directory (set {} to choose)",
+            VAULT_PATH_ENV
+        ))
+    }
+
+    /// Pick a round count that makes `derive_key` take about `target` on this machine
+    pub fn calibrate_iterations(target: Duration) -> u32 {
+        const SAMPLE: u32 = 2_000;
+        let start = Instant::now();
+        Self::derive_key("calibration", &[0u8; 16], &[], SAMPLE);
+        let elapsed = start.elapsed().max(Duration::from_micros(1));
+        let scaled = SAMPLE as f64 * target.as_secs_f64() / elapsed.as_secs_f64();
+        (scaled as u32).clamp(LEGACY_ITERATIONS, MAX_ITERATIONS)
     }
 
     /// Simple key derivation (PBKDF2-like using multiple SHA256 rounds)
-    fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
+    pub fn derive_key(password: &str, salt: &[u8], factors: &[u8], iterations: u32) -> [u8; 32] {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
 
@@ -71,20 +962,21 @@ impl Storage {
             .as_bytes()
             .iter()
             .chain(salt.iter())
+            .chain(factors.iter())
             .copied()
             .collect();
 
         // Simple iterative hashing (not as secure as Argon2, but works)
-        for i in 0..32 {
+        for (i, byte) in key.iter_mut().enumerate() {
             let mut hasher = DefaultHasher::new();
             combined.hash(&mut hasher);
             (i as u64).hash(&mut hasher);
             let hash = hasher.finish();
-            key[i] = (hash & 0xFF) as u8;
+            *byte = (hash & 0xFF) as u8;
         }
 
         // Additional rounds for strengthening
-        for _ in 0..10000 {
+        for _ in 0..iterations {
             let mut hasher = DefaultHasher::new();
             key.hash(&mut hasher);
             salt.hash(&mut hasher);
@@ -99,15 +991,44 @@ impl Storage {
 
     /// Load all passwords from encrypted storage
     pub fn load(&self) -> Result<Vec<PasswordEntry>, String> {
+        let result = self.decrypt_entries().map_err(|e| redact(&e));
+        match &result {
+            Ok(entries) => {
+                remember_secrets(entries.iter().map(|e| e.password.as_str()));
+                // Unwritten changes were based on the stamp already recorded
+                if !self.has_unsaved_changes() {
+                    self.seen.set(self.disk_stamp());
+                }
+                debug!(
+                    "loaded {} entries from {}",
+                    entries.len(),
+                    self.file_path.display()
+                )
+            }
+            Err(e) => warn!("load from {} failed: {}", self.file_path.display(), e),
+        }
+        result
+    }
+
+    fn decrypt_entries(&self) -> Result<Vec<PasswordEntry>, String> {
+        if let Some((entries, _)) = &*self.pending.borrow() {
+            return Ok(entries.clone());
+        }
         if !self.file_path.exists() {
             return Ok(Vec::new());
         }
 
-        let content = fs::read_to_string(&self.file_path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let store = self.read_store()?;
 
-        let store: EncryptedStore =
-            serde_json::from_str(&content).map_err(|e| format!("Invalid file format: {}", e))?;
+        match (store.keyfile_required, self.keyfile.is_some()) {
+            (true, false) => {
+                return Err("This vault requires a keyfile (pass --keyfile <path>)".into());
+            }
+            (false, true) => {
+                return Err("This vault was not created with a keyfile".into());
+            }
+            _ => {}
+        }
 
         let nonce_bytes = BASE64
             .decode(&store.nonce)
@@ -116,145 +1037,1553 @@ impl Storage {
             .decode(&store.ciphertext)
             .map_err(|e| format!("Invalid ciphertext: {}", e))?;
 
-        let cipher = Aes256Gcm::new_from_slice(&self.master_key)
-            .map_err(|e| format!("Cipher init failed: {}", e))?;
+        let salt = BASE64
+            .decode(&store.salt)
+            .map_err(|e| format!("Invalid salt: {}", e))?;
+        let key = if store.version >= 2 {
+            self.unwrap_dek(&store)?
+        } else {
+            self.master_key
+        };
+        let plaintext = store.algorithm.decrypt(
+            &key,
+            &nonce_bytes,
+            &ciphertext,
+            &entries_aad(store.version, &salt),
+        )?;
 
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext.as_ref())
-            .map_err(|_| "Decryption failed - wrong master password?".to_string())?;
+        let plaintext = decompress(plaintext, &store.compression)?;
 
         let json = String::from_utf8(plaintext).map_err(|e| format!("Invalid UTF-8: {}", e))?;
 
         serde_json::from_str(&json).map_err(|e| format!("Invalid JSON: {}", e))
     }
 
+    /// The data key of a version 2 file, opened with this instance's master key
+    fn unwrap_dek(&self, store: &EncryptedStore) -> Result<[u8; 32], String> {
+        let wrapped = BASE64
+            .decode(store.wrapped_dek.as_deref().ok_or("Missing data key")?)
+            .map_err(|e| format!("Invalid data key: {}", e))?;
+        if wrapped.len() <= 12 {
+            return Err("Invalid data key length".into());
+        }
+        let salt = BASE64
+            .decode(&store.salt)
+            .map_err(|e| format!("Invalid salt: {}", e))?;
+        let (nonce, sealed) = wrapped.split_at(12);
+        let dek = store.algorithm.decrypt(
+            &self.master_key,
+            nonce,
+            sealed,
+            &associated_data(store.version, &salt),
+        )?;
+        dek.try_into()
+            .map_err(|_| "Invalid data key length".to_string())
+    }
+
+    /// Seal `dek` under `master_key` for a file with `salt`, as stored in `wrapped_dek`.
+    /// Each wrap draws a random nonce, so saves keep the stored wrap and only a
+    /// new salt or data key wraps again.
+    fn wrap_dek(
+        cipher: Cipher,
+        master_key: &[u8; 32],
+        salt: &[u8],
+        dek: &[u8; 32],
+    ) -> Result<String, String> {
+        let mut nonce = [0u8; 12];
+        rand::rng().fill_bytes(&mut nonce);
+        let sealed = cipher.encrypt(
+            master_key,
+            &nonce,
+            dek,
+            &associated_data(FORMAT_VERSION, salt),
+        )?;
+        Ok(BASE64.encode([&nonce[..], &sealed].concat()))
+    }
+
+    /// First entry whose name matches `name`, ignoring case
+    pub fn find_by_name(&self, name: &str) -> Result<Option<PasswordEntry>, String> {
+        let key = name_key(name);
+        Ok(self.load()?.into_iter().find(|e| name_key(&e.name) == key))
+    }
+
+    /// Vault index of the one entry named `name`, ignoring case; an error if
+    /// there is none or more than one
+    pub fn index_by_name(&self, name: &str) -> Result<usize, String> {
+        let key = name_key(name);
+        let matches: Vec<usize> = self
+            .load()?
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| name_key(&e.name) == key)
+            .map(|(i, _)| i)
+            .collect();
+        match matches[..] {
+            [index] => Ok(index),
+            [] => Err(format!("No entry named '{}'", name)),
+            _ => Err(format!("{} entries are named '{}'", matches.len(), name)),
+        }
+    }
+
+    /// Entries carrying `tag`, ignoring case
+    pub fn filter_by_tag(&self, tag: &str) -> Result<Vec<PasswordEntry>, String> {
+        let tag = tag.to_lowercase();
+        Ok(self
+            .load()?
+            .into_iter()
+            .filter(|e| e.tags.iter().any(|t| t.to_lowercase() == tag))
+            .collect())
+    }
+
+    /// All entries in the order given by `by`
+    pub fn iter_sorted(&self, by: SortMode) -> Result<impl Iterator<Item = PasswordEntry>, String> {
+        let mut entries = self.load()?;
+        entries.sort_by(|a, b| by.compare(a, b));
+        Ok(entries.into_iter())
+    }
+
+    /// Number of entries in the vault
+    pub fn count(&self) -> Result<usize, String> {
+        Ok(self.load()?.len())
+    }
+
+    /// Rewrite the vault with tidied entries under a fresh nonce. Fields left
+    /// by older versions are dropped too, since they don't survive the load.
+    /// Held changes are written as part of it.
+    pub fn compact(&self) -> Result<CompactReport, String> {
+        let size = || {
+            fs::metadata(&self.file_path)
+                .map(|m| m.len())
+                .map_err(|e| format!("Failed to read file: {}", e))
+        };
+        if !self.file_path.exists() {
+            return Err(format!("No vault at {}", self.file_path.display()));
+        }
+        let before = size()?;
+        let mut entries = self.load()?;
+        let tidied = entries
+            .iter_mut()
+            .map(compact_entry)
+            .filter(|&changed| changed)
+            .count();
+        self.write_all(&entries)?;
+        self.pending.replace(None);
+        info!("compacted {}", self.file_path.display());
+        Ok(CompactReport {
+            entries: entries.len(),
+            tidied,
+            before,
+            after: size()?,
+        })
+    }
+
+    /// Weak, reused and expired entries, for a one-shot "what to fix" view
+    pub fn audit(&self) -> Result<AuditReport, String> {
+        Ok(AuditReport::compute(&self.load()?))
+    }
+
+    /// Decrypt and check every entry without changing anything
+    pub fn verify_integrity(&self) -> Result<VaultStats, String> {
+        if !self.file_path.exists() {
+            return Err(format!("No vault at {}", self.file_path.display()));
+        }
+        let entries = self.load()?;
+        let mut problems = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let label = format!("Entry {} ('{}')", i + 1, entry.name);
+            if entry.name.trim().is_empty() {
+                problems.push(format!("{}: empty name", label));
+            }
+            if entry.password.is_empty() {
+                problems.push(format!("{}: empty password", label));
+            }
+            if entry.created_at.parse::<u64>().is_err() {
+                problems.push(format!(
+                    "{}: unparseable timestamp '{}'",
+                    label, entry.created_at
+                ));
+            }
+        }
+        Ok(VaultStats {
+            entries: entries.len(),
+            problems,
+            permissions: self.permissions_warning(),
+        })
+    }
+
+    /// Parse the vault file without decrypting it
+    fn read_store(&self) -> Result<EncryptedStore, String> {
+        let content = fs::read_to_string(&self.file_path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid file format: {}", e))
+    }
+
+    fn disk_stamp(&self) -> Option<[u8; 32]> {
+        let contents = fs::read(&self.file_path).ok()?;
+        Some(Sha256::digest(contents).into())
+    }
+
+    /// Hold an advisory lock on a `.lock` file beside the vault until dropped
+    fn lock(&self) -> Result<fs::File, String> {
+        let mut path = self.file_path.as_os_str().to_owned();
+        path.push(".lock");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(false);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(PathBuf::from(path))
+            .map_err(|e| format!("Failed to open lock file: {}", e))?;
+        match file.try_lock() {
+            Ok(()) => Ok(file),
+            Err(fs::TryLockError::WouldBlock) => {
+                Err("The vault is being saved by another instance; try again".into())
+            }
+            Err(fs::TryLockError::Error(e)) => Err(format!("Failed to lock vault: {}", e)),
+        }
+    }
+
+    /// Hold saves in memory until nothing has changed for `debounce`; `None`
+    /// writes every save at once. Callers must `flush` before dropping
+    pub fn set_save_debounce(&mut self, debounce: Option<Duration>) {
+        self.debounce = debounce;
+    }
+
+    /// Whether saved changes are still waiting to be written
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.pending.borrow().is_some()
+    }
+
+    /// Write any held changes now; on failure they stay held
+    pub fn flush(&self) -> Result<(), StorageError> {
+        let Some((entries, _)) = self.pending.take() else {
+            return Ok(());
+        };
+        let result = self.write_all(&entries);
+        if result.is_err() {
+            // Retry after another quiet period rather than on every check
+            self.pending.replace(Some((entries, Instant::now())));
+        }
+        result
+    }
+
+    /// Drop held changes without writing them
+    pub fn discard_pending(&self) {
+        self.pending.take();
+    }
+
+    /// Write held changes once the debounce period has passed without another
+    pub fn flush_if_idle(&self) -> Option<Result<(), StorageError>> {
+        let debounce = self.debounce?;
+        let idle = self
+            .pending
+            .borrow()
+            .as_ref()
+            .is_some_and(|(_, changed)| changed.elapsed() >= debounce);
+        idle.then(|| self.flush())
+    }
+
+    /// Run `command` (program then arguments) after every successful save
+    pub fn set_post_save_command(&mut self, command: Option<Vec<String>>) {
+        self.post_save_command = command.filter(|argv| !argv.is_empty());
+    }
+
+    /// Rewrite the metadata manifest at `path` after every successful save
+    pub fn set_metadata_manifest(&mut self, path: Option<PathBuf>) {
+        self.manifest_path = path;
+    }
+
+    /// Write a sidecar listing each entry's name, URL and tags, sorted by name.
+    /// Never includes passwords, usernames or notes, and the same entries always
+    /// produce the same bytes, so it diffs cleanly under version control.
+    pub fn write_metadata_manifest(&self, dest: &Path) -> Result<(), String> {
+        write_manifest(&self.load()?, dest)
+    }
+
+    /// Why the manifest write or post-save command last failed, if either did
+    /// since the last call
+    pub fn take_after_save_error(&self) -> Option<String> {
+        self.after_save_error.take()
+    }
+
+    fn run_post_save(&self) {
+        let Some((program, args)) = self
+            .post_save_command
+            .as_ref()
+            .and_then(|argv| argv.split_first())
+        else {
+            return;
+        };
+        // Output would draw over the TUI; keep only stderr for the report
+        let output = std::process::Command::new(program)
+            .args(args)
+            .arg(&self.file_path)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .output();
+        let error = match output {
+            Ok(out) if out.status.success() => None,
+            Ok(out) => {
+                let stderr = String::from_utf8_lossy(&out.stderr);
+                let detail = stderr.lines().next().unwrap_or("").trim();
+                Some(format!(
+                    "Post-save command failed ({}): {}",
+                    out.status, detail
+                ))
+            }
+            Err(e) => Some(format!("Post-save command failed to start: {}", e)),
+        };
+        match error {
+            Some(e) => {
+                warn!("{}", e);
+                self.after_save_error.replace(Some(e));
+            }
+            None => debug!("post-save command {} succeeded", program),
+        }
+    }
+
     /// Save a password entry (appends to existing)
-    pub fn save(&self, entry: PasswordEntry) -> Result<(), String> {
+    pub fn save(&self, entry: PasswordEntry) -> Result<(), StorageError> {
         let mut entries = self.load().unwrap_or_default();
         entries.push(entry);
         self.save_all(&entries)
     }
 
-    /// Save all entries
-    fn save_all(&self, entries: &[PasswordEntry]) -> Result<(), String> {
+    /// Replace the vault contents with `entries`.
+    ///
+    /// Invariant: a (key, nonce) pair is never used twice. Every save takes the
+    /// next counter after the one on disk, and a new key (new salt) starts over.
+    ///
+    /// Refuses to write if the file changed since this instance last loaded it,
+    /// so a second instance's edits are not silently overwritten.
+    /// Index-based edits read the file without refreshing that record, since
+    /// their indices refer to what was last loaded.
+    ///
+    /// With a debounce set, the entries are only kept in memory until `flush`.
+    pub fn save_all(&self, entries: &[PasswordEntry]) -> Result<(), StorageError> {
+        if self.debounce.is_some() {
+            self.pending
+                .replace(Some((entries.to_vec(), Instant::now())));
+            return Ok(());
+        }
+        self.write_all(entries)
+    }
+
+    fn write_all(&self, entries: &[PasswordEntry]) -> Result<(), StorageError> {
+        let result = self.lock().map_err(StorageError::from).and_then(|_lock| {
+            if let Some(seen) = self.seen.get()
+                && self.disk_stamp().is_some_and(|now| now != seen)
+            {
+                return Err(StorageError::Conflict);
+            }
+            self.encrypt_entries(entries)?;
+            self.seen.set(self.disk_stamp());
+            Ok(())
+        });
+        // Runs after the lock is released; its failure never undoes the save
+        if result.is_ok() {
+            if let Some(dest) = &self.manifest_path
+                && let Err(e) = write_manifest(entries, dest)
+            {
+                warn!("{}", e);
+                self.after_save_error.replace(Some(e));
+            }
+            self.run_post_save();
+        }
+        match &result {
+            Ok(()) => debug!(
+                "saved {} entries to {}",
+                entries.len(),
+                self.file_path.display()
+            ),
+            Err(e) => warn!("save to {} failed: {}", self.file_path.display(), e),
+        }
+        result
+    }
+
+    fn encrypt_entries(&self, entries: &[PasswordEntry]) -> Result<(), String> {
         let json =
             serde_json::to_string(entries).map_err(|e| format!("Serialization failed: {}", e))?;
 
-        // Generate new nonce for each save
-        let mut nonce_bytes = [0u8; 12];
-        rand::rng().fill_bytes(&mut nonce_bytes);
-
-        let cipher = Aes256Gcm::new_from_slice(&self.master_key)
-            .map_err(|e| format!("Cipher init failed: {}", e))?;
-
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        let ciphertext = cipher
-            .encrypt(nonce, json.as_bytes())
-            .map_err(|e| format!("Encryption failed: {}", e))?;
-
-        // Get or generate salt
-        let salt = if self.file_path.exists() {
-            let content = fs::read_to_string(&self.file_path).ok();
-            content
-                .and_then(|c| serde_json::from_str::<EncryptedStore>(&c).ok())
-                .map(|s| s.salt)
-                .unwrap_or_else(|| {
-                    let mut s = [0u8; 16];
-                    rand::rng().fill_bytes(&mut s);
-                    BASE64.encode(s)
-                })
-        } else {
-            let mut s = [0u8; 16];
-            rand::rng().fill_bytes(&mut s);
-            BASE64.encode(s)
+        let previous = self
+            .read_store()
+            .ok()
+            .filter(|s| s.salt == BASE64.encode(&self.salt));
+        // Keep the data key of a version 2 file; a new or older file gets a fresh one
+        let dek = match previous.as_ref().filter(|s| s.version >= 2) {
+            Some(store) => self.unwrap_dek(store)?,
+            None => {
+                let mut dek = [0u8; 32];
+                rand::rng().fill_bytes(&mut dek);
+                dek
+            }
         };
+        let wrapped_dek = match previous
+            .as_ref()
+            .filter(|s| s.version == FORMAT_VERSION && s.algorithm == self.cipher)
+        {
+            Some(store) => store.wrapped_dek.clone(),
+            None => Some(Self::wrap_dek(
+                self.cipher,
+                &self.master_key,
+                &self.salt,
+                &dek,
+            )?),
+        };
+        let nonce_counter = previous.as_ref().map_or(0, |s| s.nonce_counter + 1);
+        let nonce_bytes = make_nonce(nonce_counter);
+        debug_assert!(
+            previous.is_none_or(|s| s.nonce != BASE64.encode(nonce_bytes)),
+            "nonce reused under the same key"
+        );
+
+        let ciphertext = self.cipher.encrypt(
+            &dek,
+            &nonce_bytes,
+            &compress(json.as_bytes())?,
+            &entries_aad(FORMAT_VERSION, &self.salt),
+        )?;
 
+        // Always write the salt the key was derived from
         let store = EncryptedStore {
-            salt,
+            salt: BASE64.encode(&self.salt),
             nonce: BASE64.encode(nonce_bytes),
             ciphertext: BASE64.encode(ciphertext),
+            compression: COMPRESSION_DEFLATE.into(),
+            keyfile_required: self.keyfile.is_some(),
+            challenge_response: self.challenge_response,
+            iterations: self.iterations,
+            algorithm: self.cipher,
+            nonce_counter,
+            version: FORMAT_VERSION,
+            wrapped_dek,
         };
 
         let output = serde_json::to_string_pretty(&store)
             .map_err(|e| format!("Serialization failed: {}", e))?;
 
-        fs::write(&self.file_path, output).map_err(|e| format!("Failed to write file: {}", e))?;
+        write_private(&self.file_path, output.as_bytes())?;
 
         Ok(())
     }
 
+    /// Write a copy of the vault to `dest`, encrypted under `password` alone.
+    /// The copy has its own salt and no second factors, so plain `Storage::new_at` opens it.
+    pub fn export_encrypted(&self, dest: &Path, password: &str) -> Result<(), String> {
+        self.export_entries_encrypted(dest, password, &self.load()?)
+    }
+
+    /// Like `export_encrypted`, but writes only `entries`
+    pub fn export_entries_encrypted(
+        &self,
+        dest: &Path,
+        password: &str,
+        entries: &[PasswordEntry],
+    ) -> Result<(), String> {
+        if dest.exists() {
+            return Err(format!("{} already exists", dest.display()));
+        }
+        if password.is_empty() {
+            return Err("Export password cannot be empty".into());
+        }
+        let export = Self::open(password, dest, None, false, self.cipher)?;
+        Ok(export.save_all(entries)?)
+    }
+
+    /// Overwrite the vault file with random bytes, flush it to disk and unlink it.
+    ///
+    /// Best effort only: copy-on-write filesystems, SSD wear levelling and
+    /// snapshots can all keep the old ciphertext around regardless.
+    pub fn secure_delete(&self) -> Result<(), String> {
+        let _lock = self.lock()?;
+        self.pending.take();
+        if self.file_path.exists() {
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .open(&self.file_path)
+                .map_err(|e| format!("Failed to open vault: {}", e))?;
+            let len = file
+                .metadata()
+                .map_err(|e| format!("Failed to read vault size: {}", e))?
+                .len();
+            let mut chunk = [0u8; 4096];
+            let mut left = len;
+            while left > 0 {
+                let n = left.min(chunk.len() as u64) as usize;
+                rand::rng().fill_bytes(&mut chunk[..n]);
+                file.write_all(&chunk[..n])
+                    .map_err(|e| format!("Failed to overwrite vault: {}", e))?;
+                left -= n as u64;
+            }
+            file.sync_all()
+                .map_err(|e| format!("Failed to flush vault: {}", e))?;
+            drop(file);
+            fs::remove_file(&self.file_path)
+                .map_err(|e| format!("Vault overwritten, but not removed: {}", e))?;
+        }
+        let mut lock_path = self.file_path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let _ = fs::remove_file(PathBuf::from(lock_path));
+        self.seen.set(None);
+        info!("securely deleted vault at {}", self.file_path.display());
+        Ok(())
+    }
+
+    /// Relocate the vault, removing the original only once the copy decrypts
+    pub fn move_to(&self, new_path: &Path) -> Result<Storage, String> {
+        if new_path.exists() {
+            return Err(format!("{} already exists", new_path.display()));
+        }
+        self.flush()?;
+
+        let moved = Storage {
+            file_path: new_path.to_path_buf(),
+            seen: Cell::new(None),
+            post_save_command: self.post_save_command.clone(),
+            manifest_path: self.manifest_path.clone(),
+            after_save_error: RefCell::new(None),
+            debounce: self.debounce,
+            pending: RefCell::new(None),
+            master_key: self.master_key,
+            salt: self.salt.clone(),
+            keyfile: self.keyfile,
+            challenge_response: self.challenge_response,
+            iterations: self.iterations,
+            cipher: self.cipher,
+        };
+        if !self.file_path.exists() {
+            // Nothing saved yet; future saves simply go to the new place
+            return Ok(moved);
+        }
+
+        // Write beside the destination first so the final rename is atomic
+        let contents =
+            fs::read(&self.file_path).map_err(|e| format!("Failed to read vault: {}", e))?;
+        let mut tmp = new_path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        write_private(&tmp, &contents)?;
+        if let Err(e) = fs::rename(&tmp, new_path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(format!("Failed to move vault: {}", e));
+        }
+
+        if let Err(e) = moved.load() {
+            let _ = fs::remove_file(new_path);
+            return Err(format!("Copied vault failed to decrypt: {}", e));
+        }
+        fs::remove_file(&self.file_path)
+            .map_err(|e| format!("Vault copied, but the old file remains: {}", e))?;
+        info!(
+            "moved vault from {} to {}",
+            self.file_path.display(),
+            new_path.display()
+        );
+        Ok(moved)
+    }
+
     /// Get the storage file path for display
     pub fn path(&self) -> &PathBuf {
         &self.file_path
     }
 
+    /// Describe the vault's permissions if other users can access it
+    #[cfg(unix)]
+    pub fn permissions_warning(&self) -> Option<String> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(&self.file_path).ok()?.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            Some(format!(
+                "⚠ Vault is accessible by other users (mode {:o})",
+                mode
+            ))
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn permissions_warning(&self) -> Option<String> {
+        None
+    }
+
+    /// Restrict the vault file to its owner
+    #[cfg(unix)]
+    pub fn fix_permissions(&self) -> Result<(), String> {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&self.file_path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to set permissions: {}", e))
+    }
+
+    #[cfg(not(unix))]
+    pub fn fix_permissions(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Insert a password entry at `index`, shifting later entries down
+    pub fn insert_at(&self, index: usize, entry: PasswordEntry) -> Result<(), StorageError> {
+        let mut entries = self.decrypt_entries()?;
+        if index > entries.len() {
+            return Err(StorageError::Other("Invalid index".into()));
+        }
+        entries.insert(index, entry);
+        self.save_all(&entries)
+    }
+
+    /// Fail with `StorageError::Drifted` unless each index still holds an
+    /// entry with the given name, so indices from an earlier load can't hit
+    /// another entry after an outside change
+    pub fn check_entries(&self, expected: &[(usize, &str)]) -> Result<(), StorageError> {
+        let entries = self.decrypt_entries()?;
+        let matches = |&(index, name): &(usize, &str)| {
+            entries.get(index).is_some_and(|entry| entry.name == name)
+        };
+        if expected.iter().all(matches) {
+            Ok(())
+        } else {
+            Err(StorageError::Drifted)
+        }
+    }
+
     /// Delete a password entry by index
-    pub fn delete(&self, index: usize) -> Result<(), String> {
-        let mut entries = self.load()?;
+    pub fn delete(&self, index: usize) -> Result<(), StorageError> {
+        let mut entries = self.decrypt_entries()?;
         if index >= entries.len() {
-            return Err("Invalid index".into());
+            return Err(StorageError::Other("Invalid index".into()));
         }
         entries.remove(index);
         self.save_all(&entries)
     }
 
+    /// Delete several entries at once; indices refer to the vault before deletion
+    pub fn delete_many(&self, indices: &[usize]) -> Result<(), StorageError> {
+        let entries = self.decrypt_entries()?;
+        if indices.iter().any(|&i| i >= entries.len()) {
+            return Err(StorageError::Other("Invalid index".into()));
+        }
+        let kept: Vec<PasswordEntry> = entries
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !indices.contains(i))
+            .map(|(_, entry)| entry)
+            .collect();
+        self.save_all(&kept)
+    }
+
     /// Update a password entry by index
-    pub fn update(&self, index: usize, entry: PasswordEntry) -> Result<(), String> {
-        let mut entries = self.load()?;
+    pub fn update(&self, index: usize, entry: PasswordEntry) -> Result<(), StorageError> {
+        let mut entries = self.decrypt_entries()?;
         if index >= entries.len() {
-            return Err("Invalid index".into());
+            return Err(StorageError::Other("Invalid index".into()));
         }
         entries[index] = entry;
         self.save_all(&entries)
     }
 
+    /// Move the entry at `index` into `target`. It is added there and read back
+    /// before it is removed here, so a failure part way never loses it; at
+    /// worst it ends up in both vaults.
+    pub fn transfer_to(&self, index: usize, target: &Storage) -> Result<(), String> {
+        let same = match (
+            fs::canonicalize(&self.file_path),
+            fs::canonicalize(&target.file_path),
+        ) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => self.file_path == target.file_path,
+        };
+        if same {
+            return Err("Source and target are the same vault".into());
+        }
+        let entry = self
+            .decrypt_entries()?
+            .get(index)
+            .cloned()
+            .ok_or("Invalid index")?;
+
+        target.save(entry.clone())?;
+        if !target.load()?.contains(&entry) {
+            return Err("Entry did not verify in the target vault; nothing was removed".into());
+        }
+        self.delete(index)
+            .map_err(|e| format!("Copied to the target vault but not removed here: {}", e))?;
+        info!(
+            "moved an entry from {} to {}",
+            self.file_path.display(),
+            target.file_path.display()
+        );
+        Ok(())
+    }
+
     /// Change the master password
-    /// Returns a new Storage instance with the new key
+    /// Returns a new Storage instance with the new key. Only the data key is
+    /// re-wrapped; the entries keep their ciphertext. The new file is staged
+    /// beside the vault and checked before it replaces the original, so a
+    /// failure at any point leaves the old vault and password working
     pub fn change_master_password(&self, new_password: &str) -> Result<Storage, String> {
         // Load existing entries with current key
         let entries = self.load()?;
 
+        // Held changes, a missing file or an older format get written out
+        // first, so there is a data key to re-wrap
+        let current = self.read_store().ok().filter(|s| s.version >= 2);
+        if self.has_unsaved_changes() || current.is_none() {
+            self.write_all(&entries)?;
+            self.pending.take();
+        }
+        let store = self.read_store()?;
+        let dek = self.unwrap_dek(&store)?;
+
         // Generate new salt
         let mut new_salt = [0u8; 16];
         rand::rng().fill_bytes(&mut new_salt);
 
         // Derive new key
-        let new_key = Self::derive_key(new_password, &new_salt);
+        let response = if self.challenge_response {
+            Some(Self::hardware_response(&new_salt)?)
+        } else {
+            None
+        };
+        let new_key = Self::derive_key(
+            new_password,
+            &new_salt,
+            &Self::factor_material(self.keyfile.as_ref(), response.as_ref()),
+            self.iterations,
+        );
 
-        // Create new storage with new key
-        let new_storage = Storage {
-            file_path: self.file_path.clone(),
+        // Stage beside the vault; the original stays untouched until the copy
+        // is proven to open to the same data key
+        let mut tmp = self.file_path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut new_storage = Storage {
+            file_path: PathBuf::from(tmp),
+            seen: Cell::new(None),
+            // Set once the staged copy is in place, so it never sees the tmp file
+            post_save_command: None,
+            manifest_path: None,
+            after_save_error: RefCell::new(None),
+            debounce: None,
+            pending: RefCell::new(None),
             master_key: new_key,
+            salt: new_salt.to_vec(),
+            keyfile: self.keyfile,
+            challenge_response: self.challenge_response,
+            iterations: self.iterations,
+            cipher: self.cipher,
         };
 
-        // Encrypt and save with new key
-        // We need to write the new salt too, so we do it manually here
-        let json =
-            serde_json::to_string(&entries).map_err(|e| format!("Serialization failed: {}", e))?;
+        // Same data key, so the nonce count carries on
+        let staged = Self::wrap_dek(store.algorithm, &new_key, &new_salt, &dek)
+            .and_then(|wrapped| {
+                let rewrapped = EncryptedStore {
+                    salt: BASE64.encode(new_salt),
+                    wrapped_dek: Some(wrapped),
+                    version: FORMAT_VERSION,
+                    ..store
+                };
+                serde_json::to_string_pretty(&rewrapped)
+                    .map_err(|e| format!("Serialization failed: {}", e))
+            })
+            .and_then(|output| write_private(&new_storage.file_path, output.as_bytes()))
+            .and_then(|_| {
+                if new_storage.unwrap_dek(&new_storage.read_store()?)? == dek {
+                    Ok(())
+                } else {
+                    Err("Re-wrapped data key does not match the original".into())
+                }
+            });
+        if let Err(e) = staged {
+            let e = redact(&e);
+            warn!("master password change aborted: {}", e);
+            let _ = fs::remove_file(&new_storage.file_path);
+            return Err(e);
+        }
 
-        let mut nonce_bytes = [0u8; 12];
-        rand::rng().fill_bytes(&mut nonce_bytes);
+        if let Err(e) = fs::rename(&new_storage.file_path, &self.file_path) {
+            let _ = fs::remove_file(&new_storage.file_path);
+            return Err(format!("Failed to replace vault: {}", e));
+        }
+        new_storage.file_path = self.file_path.clone();
+        new_storage.post_save_command = self.post_save_command.clone();
+        new_storage.manifest_path = self.manifest_path.clone();
+        new_storage.debounce = self.debounce;
+        self.pending.take();
+        new_storage.run_post_save();
+        info!(
+            "master password changed, data key for {} entries re-wrapped",
+            entries.len()
+        );
+        Ok(new_storage)
+    }
+}
 
-        let cipher = Aes256Gcm::new_from_slice(&new_key)
-            .map_err(|e| format!("Cipher init failed: {}", e))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        let ciphertext = cipher
-            .encrypt(nonce, json.as_bytes())
-            .map_err(|e| format!("Encryption failed: {}", e))?;
+    fn entry(name: &str, password: &str) -> PasswordEntry {
+        PasswordEntry {
+            name: name.into(),
+            password: password.into(),
+            created_at: "0".into(),
+            ..Default::default()
+        }
+    }
 
-        let store = EncryptedStore {
-            salt: BASE64.encode(new_salt),
-            nonce: BASE64.encode(nonce_bytes),
-            ciphertext: BASE64.encode(ciphertext),
+    #[test]
+    fn format_fills_placeholders() {
+        let mut e = entry("mail", "hunter2");
+        e.username = "me".into();
+        assert_eq!(e.format("{name}: {password}"), "mail: hunter2");
+        assert_eq!(e.format("{username}@{name} {url}"), "me@mail ");
+        // Substituted values are never re-expanded
+        let tricky = entry("{password}", "x");
+        assert_eq!(tricky.format("{name}"), "{password}");
+        assert_eq!(e.format("{{name}} {other}"), "{mail} {other}");
+    }
+
+    #[test]
+    fn only_web_links_are_launched() {
+        let mut e = entry("mail", "a");
+        assert!(e.launch_url().is_err());
+        e.url = " example.com/login ".into();
+        assert_eq!(e.launch_url().unwrap(), "https://example.com/login");
+        e.url = "HTTP://example.com".into();
+        assert_eq!(e.launch_url().unwrap(), "HTTP://example.com");
+        for refused in ["file:///etc/passwd", "ssh://host", "custom-app://run?x=1"] {
+            e.url = refused.into();
+            assert!(e.launch_url().is_err(), "{}", refused);
+        }
+    }
+
+    #[test]
+    fn names_are_sanitized() {
+        assert_eq!(sanitize_name("\u{200B} Mail\u{0007}\n "), "Mail");
+        assert_eq!(sanitize_name("pay\u{202E}pal"), "paypal");
+        assert_eq!(sanitize_name("\u{FEFF}\t\u{2060}"), "");
+        assert_eq!(sanitize_name("café übung"), "café übung");
+        let entries = vec![entry("mail", "a")];
+        assert!(name_exists(&entries, "ma\u{200B}il", None));
+        assert!(!name_exists(&entries, "mai", None));
+
+        // A decomposed é is stored and compared as the composed one
+        assert_eq!(sanitize_name("cafe\u{301}"), "caf\u{E9}");
+        let entries = vec![entry("caf\u{E9}", "a")];
+        assert!(name_exists(&entries, "CAFE\u{301}", None));
+    }
+
+    #[test]
+    fn query_helpers() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        let mut mail = entry("Mail", "a");
+        mail.created_at = "20".into();
+        mail.tags = vec!["Work".into()];
+        let mut bank = entry("bank", "b");
+        bank.created_at = "10".into();
+        storage.save_all(&[mail, bank]).unwrap();
+
+        assert_eq!(storage.count().unwrap(), 2);
+        assert_eq!(storage.find_by_name("mail").unwrap().unwrap().password, "a");
+        assert!(storage.find_by_name("shop").unwrap().is_none());
+        assert!(storage.find_by_name(" ma\u{200B}il ").unwrap().is_some());
+        assert_eq!(storage.index_by_name("MAIL").unwrap(), 0);
+        assert!(storage.index_by_name("shop").is_err());
+        assert_eq!(storage.filter_by_tag("work").unwrap().len(), 1);
+        let names =
+            |by| -> Vec<String> { storage.iter_sorted(by).unwrap().map(|e| e.name).collect() };
+        assert_eq!(names(SortMode::Stored), ["Mail", "bank"]);
+        assert_eq!(names(SortMode::Name), ["bank", "Mail"]);
+        assert_eq!(names(SortMode::Created), ["bank", "Mail"]);
+    }
+
+    #[test]
+    fn default_path_without_home() {
+        let home = PathBuf::from("/home/me");
+        let from = Storage::default_path_from;
+        assert_eq!(
+            from(None, Some(home.clone())).unwrap(),
+            home.join(VAULT_FILE_NAME)
+        );
+        // The override wins, and covers environments with no $HOME at all
+        let custom = PathBuf::from("/data/vault.enc");
+        assert_eq!(from(Some(custom.clone().into()), None).unwrap(), custom);
+        assert_eq!(
+            from(Some(custom.clone().into()), Some(home)).unwrap(),
+            custom
+        );
+        assert_eq!(
+            from(Some(OsString::new()), None).unwrap(),
+            std::env::current_dir().unwrap().join(VAULT_FILE_NAME)
+        );
+    }
+
+    #[test]
+    fn secure_delete_removes_vault_and_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+        assert!(dir.path().join("vault.enc.lock").exists());
+
+        storage.secure_delete().unwrap();
+        assert!(!path.exists());
+        assert!(!dir.path().join("vault.enc.lock").exists());
+        // Nothing there is fine too
+        storage.secure_delete().unwrap();
+    }
+
+    #[test]
+    fn expiry_dates_are_validated() {
+        assert_eq!(parse_expiry("  "), Ok(None));
+        assert_eq!(parse_expiry("2024-02-29"), Ok(Some("2024-02-29".into())));
+        for bad in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-1-01",
+            "soon",
+            "2024-01-01-01",
+        ] {
+            assert!(parse_expiry(bad).is_err(), "{}", bad);
+        }
+
+        let today = today();
+        assert_eq!(today.len(), 10);
+        assert!(parse_expiry(&today).is_ok());
+        let mut e = entry("mail", "hunter2");
+        assert!(!is_expired(&e));
+        e.expires_at = Some("9999-12-31".into());
+        assert!(!is_expired(&e));
+        e.expires_at = Some(today);
+        assert!(is_expired(&e));
+    }
+
+    #[test]
+    fn debounced_saves_are_held_until_flushed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let mut storage = Storage::new_at("master", &path).unwrap();
+        storage.save(entry("mail", "a")).unwrap();
+        storage.set_save_debounce(Some(Duration::from_secs(60)));
+
+        storage.save(entry("bank", "b")).unwrap();
+        storage.delete(0).unwrap();
+        assert!(storage.has_unsaved_changes());
+        assert_eq!(storage.load().unwrap().len(), 1);
+        let on_disk = || Storage::new_at("master", &path).unwrap().load().unwrap();
+        assert_eq!(on_disk()[0].name, "mail");
+        assert!(storage.flush_if_idle().is_none());
+
+        storage.flush().unwrap();
+        assert!(!storage.has_unsaved_changes());
+        let names: Vec<_> = on_disk().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["bank"]);
+
+        storage.set_save_debounce(Some(Duration::ZERO));
+        storage.save(entry("shop", "c")).unwrap();
+        assert!(storage.flush_if_idle().unwrap().is_ok());
+        assert_eq!(on_disk().len(), 2);
+
+        // Dropping the store writes what it still holds; discarded changes stay out
+        storage.set_save_debounce(Some(Duration::from_secs(60)));
+        storage.save(entry("work", "d")).unwrap();
+        storage.save(entry("home", "e")).unwrap();
+        drop(storage);
+        assert_eq!(on_disk().len(), 4);
+        let mut storage = Storage::new_at("master", &path).unwrap();
+        storage.set_save_debounce(Some(Duration::from_secs(60)));
+        storage.delete(0).unwrap();
+        storage.discard_pending();
+        drop(storage);
+        assert_eq!(on_disk().len(), 4);
+    }
+
+    #[test]
+    fn reused_passwords_group_by_exact_password() {
+        let entries = [
+            entry("mail", "hunter2"),
+            entry("bank", "Hunter2"),
+            entry("shop", "hunter2"),
+            entry("work", "x"),
+        ];
+        assert_eq!(reused_passwords(&entries), [vec![0, 2]]);
+        assert!(reused_passwords(&entries[..2]).is_empty());
+    }
+
+    #[test]
+    fn duplicates_group_by_name() {
+        let entries = [
+            entry("Mail", "a"),
+            entry("bank", "b"),
+            entry("mail ", "c"),
+            entry("shop", "d"),
+            entry("MAIL", "e"),
+            entry("Bank", "f"),
+        ];
+        assert_eq!(duplicate_groups(&entries), [vec![0, 2, 4], vec![1, 5]]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        storage.save_all(&entries).unwrap();
+        storage.delete_many(&[2, 4]).unwrap();
+        let left: Vec<_> = storage
+            .load()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.password)
+            .collect();
+        assert_eq!(left, ["a", "b", "d", "f"]);
+        assert!(storage.delete_many(&[9]).is_err());
+    }
+
+    #[test]
+    fn compressed_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+
+        storage.save(entry("mail", "hunter2")).unwrap();
+        storage.save(entry("bank", "correct horse")).unwrap();
+
+        let raw: EncryptedStore =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw.compression, COMPRESSION_DEFLATE);
+        assert_eq!(raw.iterations, storage.iterations);
+        assert!(raw.iterations >= LEGACY_ITERATIONS);
+
+        let entries = Storage::new_at("master", &path).unwrap().load().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "mail");
+        assert_eq!(entries[1].password, "correct horse");
+    }
+
+    #[test]
+    fn uncompressed_legacy_file_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+
+        // Hand-write a vault the way older versions did: raw JSON, no compression tag
+        let salt = [7u8; 16];
+        let key = Storage::derive_key("master", &salt, &[], LEGACY_ITERATIONS);
+        let nonce_bytes = [3u8; 12];
+        let json = serde_json::to_string(&[entry("legacy", "old-secret")]).unwrap();
+        let ciphertext = Aes256Gcm::new_from_slice(&key)
+            .unwrap()
+            .encrypt(Nonce::from_slice(&nonce_bytes), json.as_bytes())
+            .unwrap();
+        let legacy = serde_json::json!({
+            "salt": BASE64.encode(salt),
+            "nonce": BASE64.encode(nonce_bytes),
+            "ciphertext": BASE64.encode(ciphertext),
+        });
+        fs::write(&path, legacy.to_string()).unwrap();
+
+        let storage = Storage::new_at("master", &path).unwrap();
+        let entries = storage.load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].password, "old-secret");
+
+        // The next save upgrades the file to the compressed format
+        storage.save(entry("new", "fresh")).unwrap();
+        let raw: EncryptedStore =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw.compression, COMPRESSION_DEFLATE);
+        assert_eq!(storage.load().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn keyfile_is_required_once_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let keyfile = dir.path().join("key.bin");
+        fs::write(&keyfile, b"some random key material").unwrap();
+        let digest = Storage::keyfile_digest(&keyfile).unwrap();
+
+        let storage =
+            Storage::open("master", &path, Some(digest), false, Cipher::default()).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        let reopened =
+            Storage::open("master", &path, Some(digest), false, Cipher::default()).unwrap();
+        assert_eq!(reopened.load().unwrap().len(), 1);
+
+        let Err(err) = Storage::new_at("master", &path).unwrap().load() else {
+            panic!("loading without the keyfile should fail");
         };
+        assert!(err.contains("requires a keyfile"));
+    }
 
-        let output = serde_json::to_string_pretty(&store)
-            .map_err(|e| format!("Serialization failed: {}", e))?;
+    #[test]
+    fn save_update_delete_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        assert!(storage.load().unwrap().is_empty());
 
-        fs::write(&self.file_path, output).map_err(|e| format!("Failed to write file: {}", e))?;
+        storage.save(entry("mail", "hunter2")).unwrap();
+        storage.save(entry("bank", "correct horse")).unwrap();
+        storage.save(entry("forum", "letmein")).unwrap();
+        let reopened = Storage::new_at("master", &path).unwrap();
+        let names: Vec<_> = reopened
+            .load()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["mail", "bank", "forum"]);
 
-        Ok(new_storage)
+        reopened.update(1, entry("bank", "battery staple")).unwrap();
+        assert_eq!(reopened.load().unwrap()[1].password, "battery staple");
+
+        reopened.delete(0).unwrap();
+        let entries = reopened.load().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "bank");
+        assert_eq!(entries[1].name, "forum");
+
+        assert!(reopened.update(5, entry("x", "y")).is_err());
+        assert!(reopened.delete(5).is_err());
+    }
+
+    #[test]
+    fn change_master_password_reencrypts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("old", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        let changed = storage.change_master_password("new").unwrap();
+        assert_eq!(changed.load().unwrap()[0].password, "hunter2");
+
+        let reopened = Storage::new_at("new", &path).unwrap();
+        assert_eq!(reopened.load().unwrap()[0].name, "mail");
+        assert!(Storage::new_at("old", &path).unwrap().load().is_err());
+    }
+
+    #[test]
+    fn password_change_only_rewraps_the_data_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("old", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+        let before = storage.read_store().unwrap();
+        assert_eq!(before.version, 2);
+        // Ordinary saves keep the wrapped key as it is
+        storage.save(entry("bank", "letmein")).unwrap();
+        storage.delete(1).unwrap();
+        let first_wrap = before.wrapped_dek;
+        let before = storage.read_store().unwrap();
+        assert_eq!(before.wrapped_dek, first_wrap);
+
+        let changed = storage.change_master_password("new").unwrap();
+        let after = changed.read_store().unwrap();
+        assert_eq!(after.ciphertext, before.ciphertext);
+        assert_ne!(after.salt, before.salt);
+        assert_ne!(after.wrapped_dek, before.wrapped_dek);
+
+        // Neither password opens the other's wrapped key
+        let mut swapped = before;
+        swapped.ciphertext = after.ciphertext.clone();
+        fs::write(&path, serde_json::to_string(&swapped).unwrap()).unwrap();
+        assert_eq!(
+            Storage::new_at("old", &path).unwrap().load().unwrap().len(),
+            1
+        );
+        swapped.wrapped_dek = after.wrapped_dek;
+        fs::write(&path, serde_json::to_string(&swapped).unwrap()).unwrap();
+        assert!(Storage::new_at("old", &path).unwrap().load().is_err());
+    }
+
+    #[test]
+    fn single_key_vault_upgrades_to_a_data_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+
+        // Version 1: entries sealed directly under the master key
+        let json = serde_json::to_string(&[entry("mail", "hunter2")]).unwrap();
+        let nonce = make_nonce(4);
+        let ciphertext = Cipher::default()
+            .encrypt(
+                &storage.master_key,
+                &nonce,
+                &compress(json.as_bytes()).unwrap(),
+                &associated_data(1, &storage.salt),
+            )
+            .unwrap();
+        let v1 = serde_json::json!({
+            "salt": BASE64.encode(&storage.salt),
+            "nonce": BASE64.encode(nonce),
+            "ciphertext": BASE64.encode(ciphertext),
+            "compression": COMPRESSION_DEFLATE,
+            "iterations": storage.iterations,
+            "nonce_counter": 4,
+            "version": 1,
+        });
+        fs::write(&path, v1.to_string()).unwrap();
+        assert_eq!(storage.load().unwrap()[0].password, "hunter2");
+
+        storage.save(entry("bank", "x")).unwrap();
+        let raw = storage.read_store().unwrap();
+        assert_eq!((raw.version, raw.nonce_counter), (2, 5));
+        assert!(raw.wrapped_dek.is_some());
+        assert_eq!(storage.load().unwrap().len(), 2);
+
+        // A version 1 file changes password through the same upgrade
+        fs::write(&path, v1.to_string()).unwrap();
+        let changed = storage.change_master_password("new").unwrap();
+        assert_eq!(changed.load().unwrap()[0].password, "hunter2");
+        assert_eq!(changed.read_store().unwrap().version, 2);
+    }
+
+    #[test]
+    fn interrupted_password_change_keeps_old_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("old", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        // A directory where the staging file goes makes the change fail midway
+        let tmp = dir.path().join("vault.enc.tmp");
+        fs::create_dir(&tmp).unwrap();
+        assert!(storage.change_master_password("new").is_err());
+        let reopened = Storage::new_at("old", &path).unwrap();
+        assert_eq!(reopened.load().unwrap()[0].password, "hunter2");
+
+        // A stale staging file from a crash doesn't get in the way
+        fs::remove_dir(&tmp).unwrap();
+        fs::write(&tmp, "garbage").unwrap();
+        storage.change_master_password("new").unwrap();
+        assert!(!tmp.exists());
+        let reopened = Storage::new_at("new", &path).unwrap();
+        assert_eq!(reopened.load().unwrap()[0].password, "hunter2");
+    }
+
+    #[test]
+    fn verify_reports_bad_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        assert!(storage.verify_integrity().is_err());
+
+        storage
+            .save_all(&[entry("mail", "hunter2"), entry("", ""), {
+                let mut e = entry("bank", "x");
+                e.created_at = "yesterday".into();
+                e
+            }])
+            .unwrap();
+        let before = fs::read(&path).unwrap();
+        let stats = storage.verify_integrity().unwrap();
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.problems.len(), 3);
+        assert!(!stats.is_healthy());
+        assert_eq!(fs::read(&path).unwrap(), before);
+
+        storage.save_all(&[entry("mail", "hunter2")]).unwrap();
+        assert!(storage.verify_integrity().unwrap().is_healthy());
+    }
+
+    #[test]
+    fn compact_tidies_entries_and_rewrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        assert!(storage.compact().is_err());
+
+        let mut messy = entry("mail", "hunter2");
+        messy.expires_at = Some(" ".into());
+        messy.tags = vec!["work".into(), "".into(), "work".into(), "home".into()];
+        storage.save_all(&[messy, entry("bank", "x")]).unwrap();
+        let nonce = storage.read_store().unwrap().nonce;
+
+        let report = storage.compact().unwrap();
+        assert_eq!((report.entries, report.tidied), (2, 1));
+        assert_eq!(report.after, fs::metadata(&path).unwrap().len());
+        assert_ne!(storage.read_store().unwrap().nonce, nonce);
+        let entries = Storage::new_at("master", &path).unwrap().load().unwrap();
+        assert_eq!(entries[0].expires_at, None);
+        assert_eq!(entries[0].tags, ["work", "home"]);
+        assert_eq!(storage.compact().unwrap().tidied, 0);
+    }
+
+    #[test]
+    fn audit_names_entries_without_passwords() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        let strong = "Xy7#Xy7#Xy7#Xy7#";
+        let mut old = entry("old", "Qz9!mK2@pL5$wN8%");
+        old.expires_at = Some("2000-01-01".into());
+        storage
+            .save_all(&[
+                entry("mail", strong),
+                entry("bank", "abc"),
+                entry("shop", strong),
+                old,
+            ])
+            .unwrap();
+
+        let report = storage.audit().unwrap();
+        assert_eq!(report.weak, ["bank"]);
+        assert_eq!(report.reused, [vec!["mail", "shop"]]);
+        assert_eq!(report.expired, ["old"]);
+        assert_eq!(report.issues(), 4);
+        let text = report.to_string();
+        assert!(text.contains("✗ mail: password reused by shop"));
+        assert!(text.ends_with("4 issue(s) found"));
+        assert!(!text.contains(strong) && !text.contains("abc"));
+
+        storage.save_all(&[entry("mail", strong)]).unwrap();
+        assert_eq!(storage.audit().unwrap().issues(), 0);
+    }
+
+    #[test]
+    fn stale_instance_cannot_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let first = Storage::new_at("master", &path).unwrap();
+        first.save(entry("mail", "hunter2")).unwrap();
+        let second = Storage::new_at("master", &path).unwrap();
+        second.save(entry("bank", "letmein")).unwrap();
+
+        // The first instance never saw "bank"
+        assert_eq!(first.delete(0), Err(StorageError::Conflict));
+        assert_eq!(first.load().unwrap().len(), 2);
+        first.delete(0).unwrap();
+        assert_eq!(second.load().unwrap()[0].name, "bank");
+
+        // A held lock blocks writers
+        let _lock = second.lock().unwrap();
+        assert!(first.save(entry("forum", "x")).is_err());
+    }
+
+    #[test]
+    fn external_rewrite_is_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+        let entries = storage.load().unwrap();
+
+        // Same entries, same size on disk, but a fresh nonce and ciphertext
+        let other = Storage::new_at("master", &path).unwrap();
+        other.save_all(&entries).unwrap();
+
+        let changed = entry("mail", "correct horse");
+        assert_eq!(
+            storage.update(0, changed.clone()),
+            Err(StorageError::Conflict)
+        );
+        storage.load().unwrap();
+        storage.update(0, changed).unwrap();
+    }
+
+    #[test]
+    fn transfer_moves_entry_between_vaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let personal = Storage::new_at("master", &dir.path().join("personal.enc")).unwrap();
+        let work = Storage::new_at("other", &dir.path().join("work.enc")).unwrap();
+        personal.save(entry("mail", "hunter2")).unwrap();
+        personal.save(entry("vpn", "letmein")).unwrap();
+        work.save(entry("wiki", "x")).unwrap();
+
+        personal.transfer_to(1, &work).unwrap();
+        assert_eq!(personal.load().unwrap().len(), 1);
+        let moved = work.load().unwrap();
+        assert_eq!(moved[1].name, "vpn");
+        assert_eq!(moved[1].password, "letmein");
+
+        assert!(personal.transfer_to(5, &work).is_err());
+        assert!(personal.transfer_to(0, &personal).is_err());
+        assert_eq!(personal.load().unwrap().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_save_command_runs_without_blocking_saves() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let mut storage = Storage::new_at("master", &path).unwrap();
+        storage.set_post_save_command(Some(vec![
+            "sh".into(),
+            "-c".into(),
+            "cp \"$0\" \"$0.bak\"".into(),
+        ]));
+        storage.save(entry("mail", "hunter2")).unwrap();
+        assert_eq!(
+            fs::read(&path).unwrap(),
+            fs::read(dir.path().join("vault.enc.bak")).unwrap()
+        );
+        assert_eq!(storage.take_after_save_error(), None);
+
+        storage.set_post_save_command(Some(vec!["false".into()]));
+        storage.save(entry("bank", "letmein")).unwrap();
+        assert!(storage.take_after_save_error().is_some());
+        assert_eq!(storage.load().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn metadata_manifest_is_sorted_and_secret_free() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("vault.manifest.json");
+        let mut storage = Storage::new_at("master", &dir.path().join("vault.enc")).unwrap();
+        storage.set_metadata_manifest(Some(manifest.clone()));
+        let mut mail = entry("mail", "hunter2");
+        mail.url = "https://mail.example".into();
+        mail.notes = "pin 1234".into();
+        storage.save(mail).unwrap();
+        storage.save(entry("Bank", "letmein")).unwrap();
+
+        let written = fs::read_to_string(&manifest).unwrap();
+        assert!(written.find("Bank").unwrap() < written.find("mail").unwrap());
+        assert!(!written.contains("hunter2") && !written.contains("pin 1234"));
+
+        // Re-saving the same entries leaves the file byte-for-byte unchanged
+        storage.save_all(&storage.load().unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&manifest).unwrap(), written);
+        let copy = dir.path().join("copy.json");
+        storage.write_metadata_manifest(&copy).unwrap();
+        assert_eq!(fs::read_to_string(&copy).unwrap(), written);
+    }
+
+    #[test]
+    fn wrong_password_fails_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        Storage::new_at("master", &path)
+            .unwrap()
+            .save(entry("mail", "hunter2"))
+            .unwrap();
+
+        let Err(err) = Storage::new_at("wrong", &path).unwrap().load() else {
+            panic!("loading with the wrong password should fail");
+        };
+        assert!(err.contains("wrong master password"), "{}", err);
+    }
+
+    #[test]
+    fn move_to_relocates_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        let taken = dir.path().join("taken.enc");
+        fs::write(&taken, "keep me").unwrap();
+        assert!(storage.move_to(&taken).is_err());
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "keep me");
+
+        let new_path = dir.path().join("moved.enc");
+        let moved = storage.move_to(&new_path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(moved.path(), &new_path);
+        let reopened = Storage::new_at("master", &new_path).unwrap();
+        assert_eq!(reopened.load().unwrap()[0].name, "mail");
+    }
+
+    #[test]
+    fn encrypted_export_opens_with_its_own_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let keyfile = dir.path().join("key.bin");
+        fs::write(&keyfile, b"some random key material").unwrap();
+        let digest = Storage::keyfile_digest(&keyfile).unwrap();
+        let storage = Storage::open(
+            "master",
+            &dir.path().join("vault.enc"),
+            Some(digest),
+            false,
+            Cipher::default(),
+        )
+        .unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        let dest = dir.path().join("backup.enc");
+        storage.export_encrypted(&dest, "transfer").unwrap();
+        assert!(storage.export_encrypted(&dest, "transfer").is_err());
+
+        let copy = Storage::new_at("transfer", &dest).unwrap();
+        assert_eq!(copy.load().unwrap()[0].password, "hunter2");
+        assert!(Storage::new_at("master", &dest).unwrap().load().is_err());
+    }
+
+    #[test]
+    fn swapped_salt_fails_authentication() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        // The key in memory is unchanged, so only the AAD can catch this
+        let mut raw = storage.read_store().unwrap();
+        assert_eq!(raw.version, FORMAT_VERSION);
+        raw.salt = BASE64.encode([0u8; 16]);
+        fs::write(&path, serde_json::to_string(&raw).unwrap()).unwrap();
+        assert!(storage.load().is_err());
+    }
+
+    #[test]
+    fn saves_never_repeat_a_nonce() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+
+        let mut seen = std::collections::HashSet::new();
+        for i in 0..20 {
+            storage.save(entry(&format!("e{}", i), "pw")).unwrap();
+            let raw = storage.read_store().unwrap();
+            assert_eq!(raw.nonce_counter, i);
+            assert!(seen.insert(raw.nonce), "nonce repeated on save {}", i);
+        }
+
+        // A new master password keeps the data key, so the count carries on
+        let changed = storage.change_master_password("new").unwrap();
+        assert_eq!(changed.read_store().unwrap().nonce_counter, 19);
+        changed.save(entry("after", "pw")).unwrap();
+        let raw = changed.read_store().unwrap();
+        assert_eq!(raw.nonce_counter, 20);
+        assert!(seen.insert(raw.nonce));
+    }
+
+    #[test]
+    fn chacha_vault_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage =
+            Storage::new_with_factors_at("master", &path, None, false, Cipher::ChaCha20Poly1305)
+                .unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        let raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["algorithm"], "chacha20-poly1305");
+
+        // The file, not the caller, decides the cipher of an existing vault
+        let reopened = Storage::new_at("master", &path).unwrap();
+        assert_eq!(reopened.cipher, Cipher::ChaCha20Poly1305);
+        assert_eq!(reopened.load().unwrap()[0].password, "hunter2");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn vault_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.enc");
+        let storage = Storage::new_at("master", &path).unwrap();
+        storage.save(entry("mail", "hunter2")).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(storage.permissions_warning().is_none());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(storage.permissions_warning().is_some());
+        storage.fix_permissions().unwrap();
+        assert!(storage.permissions_warning().is_none());
     }
 }
@@ -1,12 +1,57 @@
+use qrcode::QrCode;
 use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, Paragraph},
+    widgets::{Block, Borders, Clear, Padding, Paragraph, Wrap},
 };
+use std::collections::VecDeque;
+use std::time::SystemTime;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use super::app::{App, InputField};
+use super::app::{
+    App, EntryField, EntryStats, InputField, ViewerState, caps_lock_suspected, estimate_entropy,
+};
+use super::generator::{GenMode, strength_label};
+use super::import::MergeStrategy;
+use super::storage::{AuditReport, PasswordEntry, SortMode, is_expired};
+
+/// Smallest terminal the layouts are designed for
+pub const MIN_WIDTH: u16 = 40;
+pub const MIN_HEIGHT: u16 = 15;
+
+/// Whether the terminal is big enough to draw the normal screens
+pub fn ensure_min_size(f: &Frame) -> bool {
+    let size = f.area();
+    size.width >= MIN_WIDTH && size.height >= MIN_HEIGHT
+}
+
+/// Shown instead of the normal screens while the terminal is too small
+pub fn render_too_small(f: &mut Frame) {
+    let size = f.area();
+    let lines = vec![
+        Line::from(Span::styled(
+            "Terminal too small",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(format!("{}×{}", size.width, size.height)),
+        Line::from(format!("Resize to at least {}×{}", MIN_WIDTH, MIN_HEIGHT)),
+    ];
+    let top = size.height.saturating_sub(lines.len() as u16) / 2;
+    let area = Rect {
+        y: size.y + top,
+        height: size.height - top,
+        ..size
+    };
+    f.render_widget(Clear, size);
+    f.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        area,
+    );
+}
 
 /// Main render function
 pub fn render(
@@ -25,8 +70,15 @@ pub fn render(
 
     let main_area = centered_rect(60, 80, size);
 
+    let title = if app.read_only {
+        " 🔐 Password Generator [READ-ONLY] "
+    } else if app.dirty {
+        " ● 🔐 Password Generator "
+    } else {
+        " 🔐 Password Generator "
+    };
     let main_block = Block::default()
-        .title(" 🔐 Password Generator ")
+        .title(title)
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Cyan));
@@ -43,6 +95,7 @@ pub fn render(
             Constraint::Length(3), // Name input
             Constraint::Length(3), // Length input
             Constraint::Length(3), // Toggles row
+            Constraint::Length(3), // Must-match pattern
             Constraint::Length(3), // Generate button
             Constraint::Length(5), // Result
             Constraint::Length(2), // Status message
@@ -50,43 +103,279 @@ pub fn render(
         ])
         .split(inner);
 
-    // Name input
+    // Name input, with the anonymous toggle beside it
+    let name_row = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(75), Constraint::Percentage(25)])
+        .split(chunks[0]);
+    let name_label = if app.anonymous {
+        "1 Password Name (optional)"
+    } else {
+        "1 Password Name"
+    };
     render_text_input(
         f,
-        "Password Name",
+        name_label,
         &app.name_input,
         app.active_field == InputField::Name,
-        chunks[0],
+        name_row[0],
+    );
+    render_toggle(
+        f,
+        "Anonymous",
+        app.anonymous,
+        app.active_field == InputField::Anonymous,
+        name_row[1],
     );
 
-    // Length input
+    // Length input, with the generation mode beside it
+    let length_row = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[1]);
+    let length_label = match app.mode {
+        GenMode::Password => "2 Length",
+        GenMode::Passphrase => "2 Words",
+        GenMode::Pin => "2 Digits",
+        GenMode::Template => "2 Template (# ! A a)",
+    };
+    let length_text = match app.mode {
+        GenMode::Template => &app.template_input,
+        _ => &app.length_input,
+    };
     render_text_input(
         f,
-        "Length",
-        &app.length_input,
+        length_label,
+        length_text,
         app.active_field == InputField::Length,
-        chunks[1],
+        length_row[0],
+    );
+    render_choice(
+        f,
+        "Mode",
+        app.mode.label(),
+        app.active_field == InputField::Mode,
+        length_row[1],
     );
 
     // Toggles row
     render_toggles(f, app, chunks[2]);
 
+    // Must-match pattern, with the trailing-character rules beside it
+    let rules_row = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(50),
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+        ])
+        .split(chunks[3]);
+    render_text_input(
+        f,
+        "Must Match (regex, optional)",
+        &app.must_match_input,
+        app.active_field == InputField::MustMatch,
+        rules_row[0],
+    );
+    render_toggle(
+        f,
+        "Ends 0-9",
+        app.trailing_digit,
+        app.active_field == InputField::TrailingDigit,
+        rules_row[1],
+    );
+    render_toggle(
+        f,
+        "Ends !@#",
+        app.trailing_special,
+        app.active_field == InputField::TrailingSpecial,
+        rules_row[2],
+    );
+
     // Generate button
     render_button(
         f,
-        "[ Generate & Save ]",
+        if app.explicit_save {
+            "[ 6 Generate ]"
+        } else {
+            "[ 6 Generate & Save ]"
+        },
         app.active_field == InputField::Generate,
-        chunks[3],
+        app.charset_empty(),
+        chunks[4],
     );
 
     // Result
-    render_result(f, app, chunks[4]);
+    render_result(f, app, chunks[5]);
 
     // Status message
-    render_status(f, app, chunks[5]);
+    render_status(f, app, chunks[6]);
 
     // Help
-    render_help(f, chunks[6]);
+    render_help(f, app, chunks[7]);
+
+    if app.show_history {
+        render_history(f, app);
+    }
+
+    if app.show_error_log {
+        render_error_log(f, &app.error_log, app.error_log_scroll);
+    }
+}
+
+/// Popup listing recent errors, newest first, starting `scroll` entries down
+pub fn render_error_log(f: &mut Frame, entries: &VecDeque<(SystemTime, String)>, scroll: usize) {
+    let area = centered_rect(70, 60, f.area());
+
+    let block = Block::default()
+        .title(format!(" ⚠ Recent errors ({}) ", entries.len()))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red));
+
+    f.render_widget(Clear, area);
+    f.render_widget(block.clone(), area);
+
+    let inner = block.inner(area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+
+    let lines: Vec<Line> = if entries.is_empty() {
+        vec![Line::from(Span::styled(
+            "No errors this session",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        entries
+            .iter()
+            .skip(scroll)
+            .map(|(when, msg)| {
+                let age = age_label(when.elapsed().unwrap_or_default().as_secs());
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>8}  ", age),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw(msg.as_str()),
+                ])
+            })
+            .collect()
+    };
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), chunks[0]);
+
+    let help = key_hints(&[("[↑↓]", "Scroll"), ("[Esc]", "Close")]);
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), chunks[1]);
+}
+
+/// Popup listing passwords generated this session
+fn render_history(f: &mut Frame, app: &App) {
+    let area = centered_rect(50, 50, f.area());
+
+    let block = Block::default()
+        .title(" 🕘 Recent (this session) ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta));
+
+    f.render_widget(Clear, area);
+    f.render_widget(block.clone(), area);
+
+    let inner = block.inner(area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+
+    let lines: Vec<Line> = if app.recent.is_empty() {
+        vec![Line::from(Span::styled(
+            "Nothing generated yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.recent
+            .iter()
+            .enumerate()
+            .map(|(i, pwd)| {
+                if i == app.history_selected {
+                    Line::from(vec![
+                        Span::styled("▸ ", Style::default().fg(Color::Yellow)),
+                        Span::styled(
+                            pwd.as_str(),
+                            Style::default()
+                                .fg(Color::Yellow)
+                                .add_modifier(Modifier::BOLD),
+                        ),
+                    ])
+                } else {
+                    Line::from(format!("  {}", pwd))
+                }
+            })
+            .collect()
+    };
+    f.render_widget(Paragraph::new(lines), chunks[0]);
+
+    let help = key_hints(&[("[↑↓]", "Nav"), ("[Enter]", "Copy"), ("[Esc]", "Close")]);
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), chunks[1]);
+}
+
+/// First-run welcome screen
+pub fn render_onboarding(f: &mut Frame, vault_path: &str) {
+    let area = centered_rect(60, 60, f.area());
+
+    let block = Block::default()
+        .title(" 👋 Welcome to Password Generator ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    f.render_widget(Clear, area);
+    f.render_widget(block.clone(), area);
+
+    let inner = block.inner(area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+
+    let key = |k: &'static str| Span::styled(k, Style::default().fg(Color::Cyan));
+    let lines = vec![
+        Line::from("Generate strong passwords and keep them in an encrypted vault."),
+        Line::from(""),
+        Line::from("The master password you enter next creates your vault at:"),
+        Line::from(Span::styled(
+            vault_path.to_string(),
+            Style::default().fg(Color::Yellow),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            "⚠ The master password cannot be recovered.",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            "If you forget it, the saved passwords are lost for good.",
+            Style::default().fg(Color::Red),
+        )),
+        Line::from(""),
+        Line::from(vec![key("[Tab/↑↓]"), Span::raw(" move between fields")]),
+        Line::from(vec![key("[Space]"), Span::raw(" toggle character sets")]),
+        Line::from(vec![key("[Enter]"), Span::raw(" generate and save")]),
+        Line::from(vec![key("[v]"), Span::raw(" view saved passwords")]),
+        Line::from(vec![key("[q]"), Span::raw(" quit")]),
+    ];
+    let body = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(body, chunks[0]);
+
+    let help = Paragraph::new("[Enter] Continue  [Esc] Quit")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    f.render_widget(help, chunks[1]);
 }
 
 fn render_master_password_prompt(
@@ -133,12 +422,277 @@ fn render_master_password_prompt(
         .block(input_block);
     f.render_widget(input_para, chunks[1]);
 
-    let help = Paragraph::new("[Enter] Confirm  [Esc] Quit")
+    let mut lines = Vec::new();
+    if caps_lock_suspected(input) {
+        lines.push(Line::styled(
+            "⚠ Lots of uppercase — Caps Lock?",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    lines.push(Line::styled(
+        "[Enter] Confirm  [Esc] Quit",
+        Style::default().fg(Color::DarkGray),
+    ));
+    let help = Paragraph::new(lines).alignment(Alignment::Center);
+    f.render_widget(help, chunks[2]);
+}
+
+/// Prompt for a file path in a plain (unmasked) input box
+pub fn render_path_prompt(f: &mut Frame, title: &str, hint: &str, input: &str) {
+    let area = centered_rect(60, 30, f.area());
+
+    let block = Block::default()
+        .title(format!(" 📁 {} ", title))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    f.render_widget(Clear, area);
+    f.render_widget(block.clone(), area);
+
+    let inner = block.inner(area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(3),
+            Constraint::Min(1),
+        ])
+        .split(inner);
+
+    let hint = Paragraph::new(hint.to_string())
+        .style(Style::default().fg(Color::Gray))
+        .alignment(Alignment::Center);
+    f.render_widget(hint, chunks[0]);
+
+    let input_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    let input_para = Paragraph::new(format!("{}▌", input))
+        .style(Style::default().fg(Color::White))
+        .block(input_block);
+    f.render_widget(input_para, chunks[1]);
+
+    let help = Paragraph::new("[Enter] Confirm  [Esc] Cancel")
         .style(Style::default().fg(Color::DarkGray))
         .alignment(Alignment::Center);
     f.render_widget(help, chunks[2]);
 }
 
+/// Ask how imported entries that clash with existing names are handled
+pub fn render_merge_choice(f: &mut Frame, selected: usize) {
+    let area = fixed_rect(44, MergeStrategy::ALL.len() as u16 + 6, f.area());
+
+    let block = Block::default()
+        .title(" 📥 Duplicate names ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let mut lines: Vec<Line> = MergeStrategy::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, strategy)| {
+            if i == selected {
+                Line::from(Span::styled(
+                    format!("▶ {}", strategy.label()),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ))
+            } else {
+                Line::from(format!("  {}", strategy.label()))
+            }
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(key_hints(&[
+        ("[↑↓]", "Choose"),
+        ("[Enter]", "Import"),
+        ("[Esc]", "Cancel"),
+    ]));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(block.padding(Padding::uniform(1))),
+        area,
+    );
+}
+
+/// Pick which entry of a duplicate-name group survives
+pub fn render_duplicates(
+    f: &mut Frame,
+    group: &[(usize, PasswordEntry)],
+    selected: usize,
+    remaining: usize,
+) {
+    let area = fixed_rect(60, group.len() as u16 + 7, f.area());
+
+    let block = Block::default()
+        .title(format!(" 🧹 Duplicates ({} group(s) left) ", remaining))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let mut lines = vec![
+        Line::from("Keep which entry? The others are deleted."),
+        Line::from(""),
+    ];
+    lines.extend(group.iter().enumerate().map(|(i, (_, entry))| {
+        let mut details = format!("{}  created {}", entry.name, entry.created_at);
+        if !entry.username.is_empty() {
+            details.push_str(&format!("  {}", entry.username));
+        }
+        if !entry.url.is_empty() {
+            details.push_str(&format!("  {}", entry.url));
+        }
+        if i == selected {
+            Line::from(Span::styled(
+                format!("▶ {}", details),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ))
+        } else {
+            Line::from(format!("  {}", details))
+        }
+    }));
+    lines.push(Line::from(""));
+    lines.push(key_hints(&[
+        ("[↑↓]", "Choose"),
+        ("[Enter]", "Keep"),
+        ("[s]", "Skip"),
+        ("[Esc]", "Done"),
+    ]));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(block.padding(Padding::horizontal(1))),
+        area,
+    );
+}
+
+/// Render the vault statistics panel
+pub fn render_stats(f: &mut Frame, stats: &EntryStats) {
+    let shown_tags = stats.tags.len().min(5);
+    let area = fixed_rect(56, shown_tags as u16 + 13, f.area());
+
+    let block = Block::default()
+        .title(" 📊 Vault Statistics ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let label = |text: &str| Span::styled(text.to_string(), Style::default().fg(Color::Cyan));
+    let count = |n: usize, warn: bool| {
+        let color = if warn && n > 0 {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        Span::styled(n.to_string(), Style::default().fg(color))
+    };
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let dated = |entry: &Option<(String, u64)>| match entry {
+        Some((name, ts)) => format!("{} ({})", name, age_label(now.saturating_sub(*ts))),
+        None => "—".into(),
+    };
+
+    let mut lines = vec![
+        Line::from(vec![label("Entries          "), count(stats.total, false)]),
+        Line::from(vec![label("Weak passwords   "), count(stats.weak, true)]),
+        Line::from(vec![label("Reused passwords "), count(stats.reused, true)]),
+        Line::from(vec![
+            label("Average length   "),
+            Span::raw(format!("{:.1}", stats.average_length)),
+        ]),
+        Line::from(vec![
+            label("Oldest           "),
+            Span::raw(dated(&stats.oldest)),
+        ]),
+        Line::from(vec![
+            label("Newest           "),
+            Span::raw(dated(&stats.newest)),
+        ]),
+        Line::from(""),
+        Line::from(label("Tags")),
+    ];
+    if stats.tags.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "  none",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines.extend(
+        stats.tags[..shown_tags]
+            .iter()
+            .map(|(tag, n)| Line::from(format!("  {}  {}", pad_to_width(tag, 20), n))),
+    );
+    if stats.tags.len() > shown_tags {
+        lines.push(Line::from(Span::styled(
+            format!("  … {} more", stats.tags.len() - shown_tags),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(key_hints(&[("[Esc]", "Close")]));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(block.padding(Padding::horizontal(1))),
+        area,
+    );
+}
+
+pub fn render_audit(f: &mut Frame, report: &AuditReport) {
+    let findings = report.lines();
+    let shown = findings.len().min(12);
+    let area = fixed_rect(64, shown as u16 + 8, f.area());
+
+    let block = Block::default()
+        .title(" 🔎 Vault Audit ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let mut lines = vec![
+        Line::from(format!("{} entries audited", report.entries)),
+        Line::from(""),
+    ];
+    if findings.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "✓ Nothing needs attention",
+            Style::default().fg(Color::Green),
+        )));
+    }
+    lines.extend(
+        findings[..shown]
+            .iter()
+            .map(|line| Line::from(Span::styled(line.clone(), Style::default().fg(Color::Red)))),
+    );
+    if findings.len() > shown {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "… {} more; run `passgen_ui audit` for all",
+                findings.len() - shown
+            ),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(key_hints(&[("[Esc]", "Close")]));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(block.padding(Padding::horizontal(1))),
+        area,
+    );
+}
+
 fn render_text_input(f: &mut Frame, label: &str, value: &str, is_active: bool, area: Rect) {
     let style = if is_active {
         Style::default()
@@ -167,33 +721,41 @@ fn render_toggles(f: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Ratio(1, 3),
-            Constraint::Ratio(1, 3),
-            Constraint::Ratio(1, 3),
+            Constraint::Ratio(1, 4),
+            Constraint::Ratio(1, 4),
+            Constraint::Ratio(1, 4),
+            Constraint::Ratio(1, 4),
         ])
         .split(area);
 
     render_toggle(
         f,
-        "Special !@#",
+        "3 Special !@#",
         app.use_special,
         app.active_field == InputField::ToggleSpecial,
         chunks[0],
     );
     render_toggle(
         f,
-        "Letters A-z",
+        "4 Letters A-z",
         app.use_letters,
         app.active_field == InputField::ToggleLetters,
         chunks[1],
     );
     render_toggle(
         f,
-        "Numbers 0-9",
+        "5 Numbers 0-9",
         app.use_numbers,
         app.active_field == InputField::ToggleNumbers,
         chunks[2],
     );
+    render_toggle(
+        f,
+        "Extended §±€",
+        app.use_extended,
+        app.active_field == InputField::ToggleExtended,
+        chunks[3],
+    );
 }
 
 fn render_toggle(f: &mut Frame, label: &str, enabled: bool, is_active: bool, area: Rect) {
@@ -227,7 +789,53 @@ fn render_toggle(f: &mut Frame, label: &str, enabled: bool, is_active: bool, are
     f.render_widget(paragraph, area);
 }
 
-fn render_button(f: &mut Frame, label: &str, is_active: bool, area: Rect) {
+/// A bordered box showing the current value of a field that cycles on Space
+fn render_choice(f: &mut Frame, label: &str, value: &str, is_active: bool, area: Rect) {
+    let border_style = if is_active {
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Gray)
+    };
+
+    let block = Block::default()
+        .title(format!(" {} ", label))
+        .borders(Borders::ALL)
+        .border_style(border_style);
+
+    let text = Line::from(vec![
+        Span::styled(value.to_string(), Style::default().fg(Color::Cyan)),
+        Span::styled(" ⟳", Style::default().fg(Color::DarkGray)),
+    ]);
+
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .block(block);
+
+    f.render_widget(paragraph, area);
+}
+
+/// `disabled` grays the button out and says why underneath
+fn render_button(f: &mut Frame, label: &str, is_active: bool, disabled: bool, area: Rect) {
+    if disabled {
+        let style = if is_active {
+            Style::default().fg(Color::Black).bg(Color::DarkGray)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        let paragraph = Paragraph::new(vec![
+            Line::styled(label, style),
+            Line::styled(
+                "Enable at least one character type",
+                Style::default().fg(Color::Red),
+            ),
+        ])
+        .alignment(Alignment::Center);
+        f.render_widget(paragraph, area);
+        return;
+    }
+
     let style = if is_active {
         Style::default()
             .fg(Color::Black)
@@ -245,10 +853,37 @@ fn render_button(f: &mut Frame, label: &str, is_active: bool, area: Rect) {
 }
 
 fn render_result(f: &mut Frame, app: &App, area: Rect) {
-    let block = Block::default()
-        .title(" Generated Password ")
+    let title = match (&app.generated_password, app.gen_options()) {
+        (Some(_), Ok(opts)) => {
+            let bits = opts.entropy_bits();
+            format!(
+                " Generated Password (~{:.0} bits, {}) ",
+                bits,
+                strength_label(bits)
+            )
+        }
+        _ => " Generated Password ".to_string(),
+    };
+    let mut block = Block::default()
+        .title(title)
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Magenta));
+    if let Some(warning) = app.length_warning() {
+        block = block.title_bottom(Line::styled(
+            format!(" {} ", warning),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if app.seeded {
+        let seed = match app.last_seed {
+            Some(seed) => format!("seed {}", seed),
+            None => "[^G] set seed".to_string(),
+        };
+        block = block.title_bottom(Line::styled(
+            format!(" ⚠ Seeded, NOT secure — {} ", seed),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    }
 
     let content = if let Some(ref err) = app.error {
         Paragraph::new(err.as_str())
@@ -256,12 +891,8 @@ fn render_result(f: &mut Frame, app: &App, area: Rect) {
             .alignment(Alignment::Center)
             .block(block)
     } else if let Some(ref pwd) = app.generated_password {
-        // Truncate display if too long
-        let display = if pwd.len() > 40 {
-            format!("{}...", &pwd[..40])
-        } else {
-            pwd.clone()
-        };
+        // Fit inside the borders, cutting by display width, never mid-codepoint
+        let display = truncate_to_width(pwd, area.width.saturating_sub(2) as usize);
         Paragraph::new(display)
             .style(
                 Style::default()
@@ -281,111 +912,177 @@ fn render_result(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_status(f: &mut Frame, app: &App, area: Rect) {
-    if let Some(ref msg) = app.status_message {
-        let paragraph = Paragraph::new(msg.as_str())
+    if let Some(msg) = app.status() {
+        let paragraph = Paragraph::new(msg)
             .style(Style::default().fg(Color::Cyan))
             .alignment(Alignment::Center);
         f.render_widget(paragraph, area);
+    } else if app.dirty {
+        let paragraph = Paragraph::new(UNSAVED_HINT)
+            .style(Style::default().fg(Color::Yellow))
+            .alignment(Alignment::Center);
+        f.render_widget(paragraph, area);
     }
 }
 
-fn render_help(f: &mut Frame, area: Rect) {
-    let help = Line::from(vec![
+/// Shown while vault changes are held in memory
+const UNSAVED_HINT: &str = "Unsaved changes — press Ctrl+S to save";
+
+fn render_help(f: &mut Frame, app: &App, area: Rect) {
+    let mut spans = vec![
         Span::styled("[Tab/↑↓]", Style::default().fg(Color::Cyan)),
         Span::raw(" Nav  "),
+        Span::styled("[Alt+1-6]", Style::default().fg(Color::Cyan)),
+        Span::raw(" Jump  "),
         Span::styled("[Space]", Style::default().fg(Color::Cyan)),
         Span::raw(" Toggle  "),
         Span::styled("[Enter]", Style::default().fg(Color::Cyan)),
-        Span::raw(" Gen  "),
-        Span::styled("[v]", Style::default().fg(Color::Cyan)),
-        Span::raw(" View  "),
-        Span::styled("[c]", Style::default().fg(Color::Cyan)),
-        Span::raw(" ChgPwd  "),
-        Span::styled("[q]", Style::default().fg(Color::Cyan)),
-        Span::raw(" Quit"),
-    ]);
-    let paragraph = Paragraph::new(help).alignment(Alignment::Center);
+    ];
+    if app.explicit_save {
+        spans.extend([
+            Span::raw(" Gen  "),
+            Span::styled("[^S]", Style::default().fg(Color::Cyan)),
+            Span::raw(" Save  "),
+        ]);
+    } else {
+        spans.push(Span::raw(" Gen+Save  "));
+    }
+    let mut hints = vec![("[v]", "View")];
+    if !app.read_only {
+        hints.push(("[c]", "ChgPwd"));
+    }
+    let stats = if app.active_field.is_text() {
+        "[Alt+i]"
+    } else {
+        "[i]"
+    };
+    hints.extend([(stats, "Stats"), ("[^A]", "Audit")]);
+    if !app.read_only {
+        hints.extend([("[^K]", "Compact"), ("[^P]", "Move"), ("[^O]", "Import")]);
+    }
+    hints.extend([("[^E]", "Export"), ("[^R]", "Recent"), ("[^L]", "Lock")]);
+    if !app.read_only {
+        hints.push(("[^D]", "Dupes"));
+    }
+    hints.extend([("[^N/^W]", "Presets"), ("[^B]", "Bits")]);
+    if app.seeded {
+        hints.push(("[^G]", "Seed"));
+    }
+    if app.loose_permissions {
+        hints.push(("[^F]", "FixPerms"));
+    }
+    if !app.read_only {
+        hints.push(("[^X]", "Destroy"));
+    }
+    hints.extend([("[F2]", "Errors"), ("[q]", "Quit")]);
+    spans.extend(key_hints(&hints).spans);
+    let paragraph = Paragraph::new(Line::from(spans)).alignment(Alignment::Center);
     f.render_widget(paragraph, area);
 }
 
 /// Render the password list viewer
-pub fn render_password_list(
-    f: &mut Frame,
-    entries: &[super::storage::PasswordEntry],
-    selected: usize,
-    revealed: &std::collections::HashSet<usize>,
-    mode: &super::app::ViewMode,
-    status_message: Option<&str>,
-    edit_buffer: &str,
-) {
+pub fn render_password_list(f: &mut Frame, context: &ViewContext, unsaved: bool) {
+    let ViewContext {
+        state,
+        mode,
+        read_only,
+        ..
+    } = *context;
+    let entries = &state.entries;
+    let selected = state.selected;
+    let status_message = state.status();
+    let edit_buffer = state.edit_buffer.as_str();
+
     let size = f.area();
     let main_area = centered_rect(70, 80, size);
 
+    let title = if read_only {
+        " 📋 Saved Passwords [READ-ONLY] "
+    } else if unsaved {
+        " ● 📋 Saved Passwords "
+    } else {
+        " 📋 Saved Passwords "
+    };
     let main_block = Block::default()
-        .title(" 📋 Saved Passwords ")
+        .title(title)
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Cyan));
 
     f.render_widget(Clear, main_area);
-    f.render_widget(main_block.clone(), main_area);
+    f.render_widget(main_block, main_area);
 
-    let inner = main_block.inner(main_area);
-
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints([
-            Constraint::Min(3),    // Password list
-            Constraint::Length(2), // Status / edit area
-            Constraint::Length(2), // Help
-        ])
-        .split(inner);
+    let chunks = viewer_layout(size);
+    render_sort_header(f, state, chunks[1]);
 
     // Password list
-    if entries.is_empty() {
-        let empty = Paragraph::new("No passwords saved yet")
+    if state.filtered_indices.is_empty() {
+        let message = if entries.is_empty() {
+            "No passwords saved yet"
+        } else {
+            "No matches"
+        };
+        let empty = Paragraph::new(message)
             .style(Style::default().fg(Color::DarkGray))
             .alignment(Alignment::Center);
-        f.render_widget(empty, chunks[0]);
+        f.render_widget(empty, chunks[1]);
     } else {
-        let list_area = chunks[0];
+        let list_area = chunks[1];
         let visible_height = list_area.height as usize;
+        let query = state.search_query.to_lowercase();
 
         // Calculate scroll offset to keep selected item visible
-        let scroll_offset = if selected >= visible_height {
-            selected - visible_height + 1
+        let position = state
+            .filtered_indices
+            .iter()
+            .position(|&i| i == selected)
+            .unwrap_or(0);
+        let scroll_offset = if position >= visible_height {
+            position - visible_height + 1
         } else {
             0
         };
 
         let mut lines: Vec<Line> = Vec::new();
 
-        for (i, entry) in entries
+        for &i in state
+            .filtered_indices
             .iter()
-            .enumerate()
             .skip(scroll_offset)
             .take(visible_height)
         {
+            let entry = &entries[i];
             let is_selected = i == selected;
-            let is_revealed = revealed.contains(&i);
+            let is_revealed = state.is_revealed(i);
 
             let prefix = if is_selected { "▸ " } else { "  " };
+            let checkbox = if state.marked.contains(&i) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
 
             // Show edit buffer when editing
             let (name_display, password_display) = if is_selected {
                 match mode {
                     super::app::ViewMode::EditName => {
-                        (format!("{}▌", edit_buffer), "••••••••••••".to_string())
+                        (format!("{}▌", edit_buffer), mask(&entry.password))
                     }
+                    super::app::ViewMode::EditPassword if state.mask_edit => (
+                        entry.name.clone(),
+                        format!("{}▌", "•".repeat(edit_buffer.chars().count())),
+                    ),
                     super::app::ViewMode::EditPassword => {
                         (entry.name.clone(), format!("{}▌", edit_buffer))
                     }
+                    super::app::ViewMode::RevealChar => {
+                        (entry.name.clone(), state.char_reveal(i).0)
+                    }
                     _ => {
                         let pwd = if is_revealed {
                             entry.password.clone()
                         } else {
-                            "••••••••••••".to_string()
+                            mask(&entry.password)
                         };
                         (entry.name.clone(), pwd)
                     }
@@ -394,7 +1091,7 @@ pub fn render_password_list(
                 let pwd = if is_revealed {
                     entry.password.clone()
                 } else {
-                    "••••••••••••".to_string()
+                    mask(&entry.password)
                 };
                 (entry.name.clone(), pwd)
             };
@@ -409,6 +1106,8 @@ pub fn render_password_list(
                         .fg(Color::Yellow)
                         .add_modifier(Modifier::BOLD)
                 }
+            } else if is_expired(entry) {
+                Style::default().fg(Color::Red)
             } else {
                 Style::default().fg(Color::White)
             };
@@ -423,33 +1122,117 @@ pub fn render_password_list(
                 Style::default().fg(Color::DarkGray)
             };
 
-            let line = Line::from(vec![
+            let mut spans = vec![
                 Span::styled(prefix, Style::default().fg(Color::Yellow)),
-                Span::styled(format!("{:<20}", name_display), name_style),
+                Span::styled(checkbox, Style::default().fg(Color::Cyan)),
+                Span::styled(pad_to_width(&name_display, 20), name_style),
                 Span::raw(" → "),
                 Span::styled(password_display, pwd_style),
-            ]);
-            lines.push(line);
+            ];
+
+            // Countdown for timed reveals that aren't also pinned open
+            if !state.revealed.contains(&i)
+                && let Some(secs) = state.reveal_remaining(i)
+            {
+                spans.push(Span::styled(
+                    format!(" ⏱{}s", secs),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+
+            // URL (or its edit buffer) trails the password
+            if is_selected && *mode == super::app::ViewMode::EditUrl {
+                spans.push(Span::raw("  "));
+                spans.push(Span::styled(
+                    format!("{}▌", edit_buffer),
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            } else if !entry.url.is_empty() {
+                spans.push(Span::raw("  "));
+                spans.push(Span::styled(
+                    entry.url.as_str(),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+
+            // Point out matches that aren't visible in the row itself
+            if !query.is_empty()
+                && let Some(field) = entry.matched_field(&query)
+                && field != "name"
+            {
+                spans.push(Span::styled(
+                    format!("  [{}]", field),
+                    Style::default().fg(Color::Magenta),
+                ));
+            }
+
+            lines.push(Line::from(spans));
         }
 
         let list = Paragraph::new(lines);
-        f.render_widget(list, chunks[0]);
+        f.render_widget(list, chunks[1]);
     }
 
     // Status / confirm area
     let status_content = match mode {
-        super::app::ViewMode::ConfirmDelete => {
+        super::app::ViewMode::ConfirmQr => {
             let name = entries.get(selected).map(|e| e.name.as_str()).unwrap_or("");
             Line::from(vec![
-                Span::styled("Delete '", Style::default().fg(Color::Red)),
+                Span::styled("Show '", Style::default().fg(Color::Red)),
                 Span::styled(name, Style::default().fg(Color::Yellow)),
-                Span::styled("'? ", Style::default().fg(Color::Red)),
+                Span::styled(
+                    "' as QR? The secret will be visible on screen. ",
+                    Style::default().fg(Color::Red),
+                ),
                 Span::styled("[y]", Style::default().fg(Color::Green)),
                 Span::raw("es / "),
                 Span::styled("[n]", Style::default().fg(Color::Red)),
                 Span::raw("o"),
             ])
         }
+        super::app::ViewMode::ConfirmNetwork => Line::from(vec![
+            Span::styled(
+                "Send the first 5 hash digits to api.pwnedpasswords.com? ",
+                Style::default().fg(Color::Yellow),
+            ),
+            Span::styled("[y]", Style::default().fg(Color::Green)),
+            Span::raw("es / "),
+            Span::styled("[n]", Style::default().fg(Color::Red)),
+            Span::raw("o"),
+        ]),
+        super::app::ViewMode::BreachReport => Line::from(""),
+        super::app::ViewMode::ConfirmRename => Line::from(vec![
+            Span::styled("'", Style::default().fg(Color::Red)),
+            Span::styled(edit_buffer, Style::default().fg(Color::Yellow)),
+            Span::styled(
+                "' is already used by another entry. Rename anyway? ",
+                Style::default().fg(Color::Red),
+            ),
+            Span::styled("[y]", Style::default().fg(Color::Green)),
+            Span::raw("es / "),
+            Span::styled("[n]", Style::default().fg(Color::Red)),
+            Span::raw("o"),
+        ]),
+        super::app::ViewMode::ShowQr => Line::from(Span::styled(
+            "⚠ Secret displayed as QR code",
+            Style::default().fg(Color::Red),
+        )),
+        super::app::ViewMode::RevealChar => {
+            let total = entries
+                .get(selected)
+                .map_or(0, |e| e.password.chars().count());
+            Line::from(vec![
+                Span::styled("Character ", Style::default().fg(Color::Green)),
+                Span::styled(
+                    format!("{} of {}", state.char_reveal(selected).1, total),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            ])
+        }
         super::app::ViewMode::EditName => Line::from(vec![
             Span::styled("Editing name", Style::default().fg(Color::Green)),
             Span::raw(" — Press "),
@@ -460,48 +1243,673 @@ pub fn render_password_list(
         ]),
         super::app::ViewMode::EditPassword => Line::from(vec![
             Span::styled("Editing password", Style::default().fg(Color::Green)),
+            Span::styled(
+                format!(
+                    " ({} chars, ~{:.0} bits)",
+                    edit_buffer.chars().count(),
+                    estimate_entropy(edit_buffer)
+                ),
+                Style::default().fg(Color::Gray),
+            ),
+            Span::raw(" — Press "),
+            Span::styled("[Enter]", Style::default().fg(Color::Cyan)),
+            Span::raw(" to save, "),
+            Span::styled("[Esc]", Style::default().fg(Color::Cyan)),
+            Span::raw(" to cancel"),
+        ]),
+        super::app::ViewMode::EditUrl => Line::from(vec![
+            Span::styled("Editing URL", Style::default().fg(Color::Green)),
             Span::raw(" — Press "),
             Span::styled("[Enter]", Style::default().fg(Color::Cyan)),
             Span::raw(" to save, "),
             Span::styled("[Esc]", Style::default().fg(Color::Cyan)),
             Span::raw(" to cancel"),
         ]),
+        super::app::ViewMode::Search => Line::from(vec![
+            Span::styled("Search: ", Style::default().fg(Color::Green)),
+            Span::raw(format!("{}▌", state.search_query)),
+            Span::styled(
+                format!("  ({} of {})", state.filtered_indices.len(), entries.len()),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]),
+        super::app::ViewMode::TransferPath => Line::from(vec![
+            Span::styled("Move to vault: ", Style::default().fg(Color::Green)),
+            Span::raw(format!("{}▌", edit_buffer)),
+            Span::styled(
+                status_message
+                    .map(|msg| format!("  {}", msg))
+                    .unwrap_or_default(),
+                Style::default().fg(Color::Red),
+            ),
+        ]),
+        super::app::ViewMode::TransferPassword => Line::from(vec![
+            Span::styled(
+                "Master password of that vault: ",
+                Style::default().fg(Color::Green),
+            ),
+            Span::raw(format!("{}▌", mask(edit_buffer))),
+        ]),
+        super::app::ViewMode::EditEntry => match status_message {
+            Some(msg) => Line::from(Span::styled(msg, Style::default().fg(Color::Cyan))),
+            None => Line::from(Span::styled(
+                "Editing entry",
+                Style::default().fg(Color::Green),
+            )),
+        },
+        super::app::ViewMode::EditNotes => match status_message {
+            Some(msg) => Line::from(Span::styled(msg, Style::default().fg(Color::Cyan))),
+            None => Line::from(Span::styled(
+                "Editing notes",
+                Style::default().fg(Color::Green),
+            )),
+        },
+        super::app::ViewMode::Detail => match status_message {
+            Some(msg) => Line::from(Span::styled(msg, Style::default().fg(Color::Cyan))),
+            None => Line::from(""),
+        },
         super::app::ViewMode::Browse => {
-            if let Some(msg) = status_message {
+            if let Some((name, secs)) = state.undo_remaining() {
+                Line::from(vec![
+                    Span::styled(
+                        format!("Deleted '{}' — ", name),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::styled("[u]", Style::default().fg(Color::Cyan)),
+                    Span::styled(
+                        format!(" undo ({}s)", secs),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                ])
+            } else if let Some(msg) = status_message {
                 Line::from(Span::styled(msg, Style::default().fg(Color::Cyan)))
+            } else if !state.search_query.is_empty() {
+                Line::from(vec![
+                    Span::styled("Filter: ", Style::default().fg(Color::Green)),
+                    Span::raw(state.search_query.as_str()),
+                    Span::styled(
+                        format!("  ({} of {})", state.filtered_indices.len(), entries.len()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            } else if unsaved {
+                Line::from(Span::styled(
+                    UNSAVED_HINT,
+                    Style::default().fg(Color::Yellow),
+                ))
             } else {
                 Line::from("")
             }
         }
     };
     let status_para = Paragraph::new(status_content).alignment(Alignment::Center);
-    f.render_widget(status_para, chunks[1]);
+    f.render_widget(status_para, chunks[2]);
 
     // Help bar for viewer (context-sensitive)
-    let help = match mode {
-        super::app::ViewMode::Browse => Line::from(vec![
-            Span::styled("[↑↓]", Style::default().fg(Color::Cyan)),
-            Span::raw(" Nav "),
-            Span::styled("[Space]", Style::default().fg(Color::Cyan)),
-            Span::raw(" Reveal "),
-            Span::styled("[y]", Style::default().fg(Color::Cyan)),
-            Span::raw(" Copy "),
-            Span::styled("[e]", Style::default().fg(Color::Cyan)),
-            Span::raw(" EditName "),
-            Span::styled("[p]", Style::default().fg(Color::Cyan)),
-            Span::raw(" EditPwd "),
-            Span::styled("[d]", Style::default().fg(Color::Cyan)),
-            Span::raw(" Del "),
+    let help = help_line(context);
+    let help_para = Paragraph::new(help).alignment(Alignment::Center);
+    f.render_widget(help_para, chunks[3]);
+}
+
+/// What decides which keys the viewer's help bar offers
+pub struct ViewContext<'a> {
+    pub state: &'a ViewerState,
+    pub mode: &'a super::app::ViewMode,
+    pub read_only: bool,
+    /// `type_out` is enabled in the config
+    pub type_out: bool,
+    /// Online breach checks are allowed
+    pub allow_network: bool,
+    pub allow_reveal_all: bool,
+}
+
+/// The viewer's help bar, listing only keys that apply right now
+fn help_line(context: &ViewContext) -> Line<'static> {
+    match context.mode {
+        super::app::ViewMode::Browse => {
+            let state = context.state;
+            let entry = state
+                .has_selection()
+                .then(|| &state.entries[state.selected]);
+            let mut hints = vec![("[↑↓]", "Nav"), ("[/]", "Search")];
+            if state.pending_delete.is_some() {
+                hints.push(("[u]", "Undo"));
+            }
+            if !state.marked.is_empty() {
+                hints.push(("[X]", "ExportMarked"));
+            }
+            if let Some(entry) = entry {
+                hints.extend([
+                    ("[Enter]", "Reveal"),
+                    ("[Space]", "Mark"),
+                    ("[t]", "Peek"),
+                    ("[i]", "ByChar"),
+                    ("[y]", "Copy"),
+                    ("[Y]", "Copy&Close"),
+                ]);
+                if context.type_out {
+                    hints.push(("[T]", "TypeAtShell(visible; Enter runs it)"));
+                }
+                hints.extend([("[c]", "CopyFmt"), ("[v]", "Details")]);
+                if !context.read_only {
+                    hints.extend([("[e]", "EditName"), ("[p]", "EditPwd")]);
+                    if state.pending_delete.is_none() {
+                        hints.push(("[u]", "EditUrl"));
+                    }
+                    hints.extend([("[E]", "EditAll"), ("[n]", "Notes"), ("[M]", "Move")]);
+                }
+                if entry.launch_url().is_ok() {
+                    hints.push(("[O]", "Open"));
+                }
+                hints.push(("[Q]", "QR"));
+                hints.push(if context.allow_network {
+                    ("[B/N]", "Breach")
+                } else {
+                    ("[B]", "Breach")
+                });
+                if !context.read_only {
+                    hints.extend([("[D]", "Dup"), ("[d]", "Del")]);
+                }
+            }
+            if context.allow_reveal_all && !state.filtered_indices.is_empty() {
+                hints.push(("[r/H]", "RevealAll"));
+            }
+            hints.extend([("[R]", "Reload"), ("[^L]", "Lock"), ("[Esc]", "Back")]);
+            key_hints(&hints)
+        }
+        super::app::ViewMode::ShowQr => Line::from(vec![
             Span::styled("[Esc]", Style::default().fg(Color::Cyan)),
-            Span::raw(" Back"),
+            Span::raw(" Close"),
         ]),
+        super::app::ViewMode::EditPassword => {
+            let mask = if context.state.mask_edit {
+                "Unmask"
+            } else {
+                "Mask"
+            };
+            key_hints(&[
+                ("[Ctrl+G]", "Generate"),
+                ("[Ctrl+R]", mask),
+                ("[Esc]", "Cancel"),
+            ])
+        }
+        super::app::ViewMode::Search => key_hints(&[("[Enter]", "Apply"), ("[Esc]", "Clear")]),
+        super::app::ViewMode::EditEntry => key_hints(&[
+            ("[Tab/↑↓]", "Field"),
+            ("[Enter]", "Save"),
+            ("[Esc]", "Cancel"),
+        ]),
+        super::app::ViewMode::EditNotes => key_hints(&[
+            ("[Enter]", "Newline"),
+            ("[Ctrl+S]", "Save"),
+            ("[Esc]", "Cancel"),
+        ]),
+        super::app::ViewMode::RevealChar => key_hints(&[("[←→]", "Step"), ("[Esc]", "Done")]),
+        super::app::ViewMode::Detail => {
+            let entry = context.state.entries.get(context.state.selected);
+            let mut hints = Vec::new();
+            if entry.is_some_and(|e| !e.username.is_empty()) {
+                hints.push(("[u]", "Copy user"));
+            }
+            hints.push(("[p]", "Copy password"));
+            if entry.is_some_and(|e| !e.url.is_empty()) {
+                hints.push(("[l]", "Copy URL"));
+            }
+            hints.extend([("[Space]", "Reveal"), ("[Esc]", "Back")]);
+            key_hints(&hints)
+        }
         _ => Line::from(vec![
             Span::styled("[Esc]", Style::default().fg(Color::Cyan)),
             Span::raw(" Cancel"),
         ]),
+    }
+}
+
+/// Areas of the viewer: sort header, list, status, help
+fn viewer_layout(size: Rect) -> std::rc::Rc<[Rect]> {
+    let main_area = centered_rect(70, 80, size);
+    let inner = Block::default().borders(Borders::ALL).inner(main_area);
+    Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Sort header
+            Constraint::Min(3),    // Password list
+            Constraint::Length(2), // Status / edit area
+            Constraint::Length(2), // Help
+        ])
+        .split(inner)
+}
+
+/// Where the viewer's sort header is drawn on a terminal of `size`
+pub fn viewer_header_area(size: Rect) -> Rect {
+    viewer_layout(size)[0]
+}
+
+/// Column titles with the active sort column highlighted and its direction
+fn render_sort_header(f: &mut Frame, state: &ViewerState, area: Rect) {
+    let arrow = if state.sort_desc { " ▼" } else { " ▲" };
+    let column = |title: &str, mode: Option<SortMode>| {
+        if mode == Some(state.sort) {
+            Span::styled(
+                format!("{}{}", title, arrow),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Span::styled(title.to_string(), Style::default().fg(Color::DarkGray))
+        }
     };
-    let help_para = Paragraph::new(help).alignment(Alignment::Center);
-    f.render_widget(help_para, chunks[2]);
+    let separator = || Span::styled(" | ", Style::default().fg(Color::DarkGray));
+    let header = Line::from(vec![
+        Span::raw("  "),
+        column("Name", Some(SortMode::Name)),
+        separator(),
+        column("Password", None),
+        separator(),
+        column("Date", Some(SortMode::Created)),
+        Span::styled("   [s] sort", Style::default().fg(Color::DarkGray)),
+    ]);
+    f.render_widget(Paragraph::new(header), area);
+}
+
+/// Yes/no popup for a pending `Confirm`
+pub fn render_confirm(f: &mut Frame, prompt: &str) {
+    let width = (UnicodeWidthStr::width(prompt) as u16 + 6).max(30);
+    let area = fixed_rect(width, 6, f.area());
+
+    let block = Block::default()
+        .title(" Confirm ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red));
+    let lines = vec![
+        Line::from(Span::styled(prompt, Style::default().fg(Color::Yellow))),
+        Line::from(""),
+        key_hints(&[("[y]", "Yes"), ("[n]", "No")]),
+    ];
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .block(block.padding(Padding::horizontal(1))),
+        area,
+    );
+}
+
+/// Build a help line from `(key, label)` pairs
+fn key_hints(hints: &[(&str, &str)]) -> Line<'static> {
+    let mut spans = Vec::new();
+    for (i, (key, label)) in hints.iter().enumerate() {
+        let sep = if i + 1 < hints.len() { " " } else { "" };
+        spans.push(Span::styled(
+            key.to_string(),
+            Style::default().fg(Color::Cyan),
+        ));
+        spans.push(Span::raw(format!(" {}{}", label, sep)));
+    }
+    Line::from(spans)
+}
+
+/// Popup listing every field of `entry`; the password stays masked unless `revealed`
+pub fn render_entry_detail(f: &mut Frame, entry: &PasswordEntry, revealed: bool) {
+    let area = fixed_rect(60, 13, f.area());
+
+    let block = Block::default()
+        .title(format!(" {} ", entry.name))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let label = |text: &'static str| Span::styled(text, Style::default().fg(Color::Cyan));
+    let value = |text: &str| {
+        if text.is_empty() {
+            Span::styled("—", Style::default().fg(Color::DarkGray))
+        } else {
+            Span::raw(text.to_string())
+        }
+    };
+    let last_used = entry
+        .last_used
+        .as_deref()
+        .and_then(|ts| ts.parse::<u64>().ok())
+        .map(|ts| {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            age_label(now.saturating_sub(ts))
+        })
+        .unwrap_or_default();
+    let password = if revealed {
+        Span::styled(entry.password.clone(), Style::default().fg(Color::Green))
+    } else {
+        Span::styled(mask(&entry.password), Style::default().fg(Color::DarkGray))
+    };
+    let lines = vec![
+        Line::from(vec![label("[u] Username  "), value(&entry.username)]),
+        Line::from(vec![label("[p] Password  "), password]),
+        Line::from(vec![label("[l] URL       "), value(&entry.url)]),
+        Line::from(vec![label("    Tags      "), value(&entry.tags.join(", "))]),
+        Line::from(vec![label("    Created   "), value(&entry.created_at)]),
+        Line::from(vec![label("    Last used "), value(&last_used)]),
+        Line::from(vec![
+            label("    Expires   "),
+            match &entry.expires_at {
+                Some(date) if is_expired(entry) => Span::styled(
+                    format!("{} (expired)", date),
+                    Style::default().fg(Color::Red),
+                ),
+                Some(date) => Span::raw(date.clone()),
+                None => value(""),
+            },
+        ]),
+        Line::from(vec![label("    Notes     "), value(&entry.notes)]),
+    ];
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(block.padding(Padding::uniform(1))),
+        area,
+    );
+}
+
+/// Popup form with every text field of `draft`, `active` taking input
+pub fn render_entry_form(f: &mut Frame, draft: &PasswordEntry, expiry: &str, active: EntryField) {
+    let area = fixed_rect(60, 12, f.area());
+
+    let block = Block::default()
+        .title(" Edit Entry ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Green));
+
+    let lines: Vec<Line> = EntryField::ALL
+        .iter()
+        .map(|&field| {
+            let value = match field {
+                EntryField::Name => &draft.name,
+                EntryField::Username => &draft.username,
+                EntryField::Url => &draft.url,
+                EntryField::Password => &draft.password,
+                EntryField::Notes => &draft.notes,
+                EntryField::Expires => expiry,
+            };
+            let label = Span::styled(
+                format!("{:<10}", field.label()),
+                Style::default().fg(Color::Cyan),
+            );
+            if field == active {
+                Line::from(vec![
+                    label,
+                    Span::styled(
+                        format!("{}▌", value),
+                        Style::default()
+                            .fg(Color::Green)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                ])
+            } else if field == EntryField::Password {
+                // Only shown in clear while it is being typed
+                Line::from(vec![
+                    label,
+                    Span::styled(mask(value), Style::default().fg(Color::DarkGray)),
+                ])
+            } else {
+                Line::from(vec![label, Span::raw(value.to_string())])
+            }
+        })
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(block.padding(Padding::uniform(1))),
+        area,
+    );
+}
+
+/// Multi-line notes editor, word-wrapped and scrolled to keep `cursor` in view
+pub fn render_notes_editor(f: &mut Frame, lines: &[String], cursor: (usize, usize)) {
+    let area = centered_rect(70, 60, f.area());
+
+    let block = Block::default()
+        .title(" Notes ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Green));
+    let inner = block.inner(area).inner(ratatui::layout::Margin::new(1, 0));
+    let width = usize::from(inner.width).max(1);
+    let height = usize::from(inner.height).max(1);
+
+    // Split every line into screen rows and find the row holding the cursor
+    let mut rows: Vec<(usize, Vec<char>, usize)> = Vec::new();
+    let mut cursor_row = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let starts = wrap_starts(&chars, width);
+        for (j, &start) in starts.iter().enumerate() {
+            let end = starts.get(j + 1).copied().unwrap_or(chars.len());
+            let last = j + 1 == starts.len();
+            if i == cursor.0 && cursor.1 >= start && (cursor.1 < end || last) {
+                cursor_row = rows.len();
+            }
+            rows.push((i, chars[start..end].to_vec(), start));
+        }
+    }
+    let scroll = (cursor_row + 1).saturating_sub(height);
+
+    let text: Vec<Line> = rows
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(height)
+        .map(|(n, (_, chars, start))| {
+            if n != cursor_row {
+                return Line::from(chars.iter().collect::<String>());
+            }
+            let col = cursor.1 - start;
+            let before: String = chars[..col.min(chars.len())].iter().collect();
+            let at = chars.get(col).map_or(" ".to_string(), |c| c.to_string());
+            let after: String = chars.get(col + 1..).unwrap_or_default().iter().collect();
+            Line::from(vec![
+                Span::raw(before),
+                Span::styled(at, Style::default().add_modifier(Modifier::REVERSED)),
+                Span::raw(after),
+            ])
+        })
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text).block(block.padding(Padding::horizontal(1))),
+        area,
+    );
+}
+
+/// Character offsets where each screen row of `chars` starts, breaking after
+/// the last space that fits in `width`, or mid-word when there is none
+fn wrap_starts(chars: &[char], width: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut start = 0;
+    while chars.len() - start > width {
+        let next = chars[start..start + width]
+            .iter()
+            .rposition(|&c| c == ' ')
+            .filter(|&p| p > 0)
+            .map_or(start + width, |p| start + p + 1);
+        starts.push(next);
+        start = next;
+    }
+    starts
+}
+
+/// Popup with the outcome of the online breach check for `name`
+pub fn render_breach_report(f: &mut Frame, name: &str, report: &Result<u64, String>) {
+    let area = fixed_rect(56, 7, f.area());
+
+    let (text, color) = match report {
+        Ok(0) => ("✓ Not found in any known breach".to_string(), Color::Green),
+        Ok(n) => (
+            format!("⚠ Seen {} times in breaches — rotate it", n),
+            Color::Red,
+        ),
+        Err(e) => (format!("✗ {}", e), Color::Red),
+    };
+    let block = Block::default()
+        .title(format!(" Breach check: {} ", name))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color));
+    let lines = vec![
+        Line::from(Span::styled(text, Style::default().fg(color))),
+        Line::from(""),
+        key_hints(&[("[Esc]", "Close")]),
+    ];
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .block(block.padding(Padding::horizontal(1))),
+        area,
+    );
+}
+
+/// Render `data` as a QR code popup, two modules per cell using half blocks
+pub fn render_qr(f: &mut Frame, data: &str) {
+    let size = f.area();
+
+    let lines: Vec<Line> = match QrCode::new(data.as_bytes()) {
+        Ok(code) => {
+            // Keep a quiet zone around the symbol so scanners can lock on
+            const QUIET: usize = 2;
+            let width = code.width();
+            let colors = code.to_colors();
+            let full = width + QUIET * 2;
+            let is_dark = |x: usize, y: usize| {
+                x >= QUIET
+                    && y >= QUIET
+                    && x < width + QUIET
+                    && y < width + QUIET
+                    && colors[(y - QUIET) * width + (x - QUIET)] == qrcode::Color::Dark
+            };
+
+            (0..full)
+                .step_by(2)
+                .map(|y| {
+                    let row: String = (0..full)
+                        .map(|x| match (is_dark(x, y), is_dark(x, y + 1)) {
+                            (true, true) => '█',
+                            (true, false) => '▀',
+                            (false, true) => '▄',
+                            (false, false) => ' ',
+                        })
+                        .collect();
+                    Line::from(Span::styled(
+                        row,
+                        Style::default().fg(Color::Black).bg(Color::White),
+                    ))
+                })
+                .collect()
+        }
+        Err(e) => vec![Line::from(Span::styled(
+            format!("Cannot encode QR: {}", e),
+            Style::default().fg(Color::Red),
+        ))],
+    };
+
+    let height = lines.len() as u16 + 2;
+    let width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 2;
+    let area = fixed_rect(width, height, size);
+
+    let block = Block::default()
+        .title(" QR ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red));
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Overlay a one-line "please wait" box, drawn right before a blocking call
+pub fn render_busy(f: &mut Frame, message: &str) {
+    let text = format!("⏳ {}", message);
+    let width = UnicodeWidthStr::width(text.as_str()) as u16 + 6;
+    let area = fixed_rect(width, 3, f.area());
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::Yellow))
+            .block(block),
+        area,
+    );
+}
+
+/// A `width` x `height` rect centered in `r`, clipped to fit
+fn fixed_rect(width: u16, height: u16, r: Rect) -> Rect {
+    Rect {
+        x: r.x + r.width.saturating_sub(width) / 2,
+        y: r.y + r.height.saturating_sub(height) / 2,
+        width: width.min(r.width),
+        height: height.min(r.height),
+    }
+}
+
+/// One dot per character, so masked and revealed passwords line up
+fn mask(password: &str) -> String {
+    "•".repeat(password.chars().count())
+}
+
+/// Cut `s` to at most `width` terminal columns, marking the cut with "..."
+fn truncate_to_width(s: &str, width: usize) -> String {
+    if UnicodeWidthStr::width(s) <= width {
+        return s.to_string();
+    }
+    let budget = width.saturating_sub(3);
+    let mut used = 0;
+    let mut out = String::new();
+    for c in s.chars() {
+        let w = UnicodeWidthChar::width(c).unwrap_or(0);
+        if used + w > budget {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    out.push_str("...");
+    out
+}
+
+/// Rough age of something `secs` seconds old, e.g. "5m ago"
+fn age_label(secs: u64) -> String {
+    if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86_400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86_400)
+    }
+}
+
+/// Left-align `s` in `width` terminal columns, accounting for wide characters
+fn pad_to_width(s: &str, width: usize) -> String {
+    let used = UnicodeWidthStr::width(s);
+    format!("{}{}", s, " ".repeat(width.saturating_sub(used)))
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
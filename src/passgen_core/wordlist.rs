@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Smallest custom list accepted; fewer words make passphrases too guessable
+pub const MIN_CUSTOM_WORDS: usize = 1000;
+
+/// List loaded with `load_custom`, used instead of `WORDS` once set
+static CUSTOM: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Built-in list of short, distinct English words for passphrases
+pub const WORDS: &[&str] = &[
+    "able", "acid", "aged", "also", "area", "army", "away", "baby", "back", "ball", "band", "bank",
+    "base", "bath", "bear", "beat", "been", "beer", "bell", "belt", "best", "bike", "bird", "blow",
+    "blue", "boat", "body", "bold", "bone", "book", "boot", "born", "boss", "both", "bowl", "bulk",
+    "burn", "bush", "busy", "cafe", "cage", "cake", "calm", "came", "camp", "card", "care", "cart",
+    "case", "cash", "cast", "cell", "chat", "chef", "chip", "city", "clay", "clip", "club", "coal",
+    "coat", "code", "coin", "cold", "cook", "cool", "cope", "copy", "cord", "core", "corn", "cost",
+    "crew", "crop", "cube", "cure", "cute", "dark", "dash", "data", "date", "dawn", "deal", "dear",
+    "deck", "deep", "deer", "desk", "dial", "dice", "diet", "dish", "dive", "dock", "dome", "door",
+    "dose", "dove", "down", "draw", "drop", "drum", "duck", "dune", "dust", "duty", "each", "earn",
+    "east", "easy", "echo", "edge", "exit", "face", "fact", "fair", "fall", "farm", "fast", "fern",
+    "film", "find", "fine", "fire", "firm", "fish", "fist", "flag", "flat", "flow", "foam", "fold",
+    "folk", "food", "foot", "fork", "form", "fort", "frog", "fuel", "full", "fund", "gain", "game",
+    "gate", "gear", "gift", "girl", "glad", "glow", "glue", "goal", "goat", "gold", "golf", "good",
+    "gown", "grab", "gray", "grid", "grin", "grow", "gulf", "hair", "half", "hall", "hand", "harp",
+    "hawk", "head", "heat", "herb", "hero", "hill", "hint", "hive", "hold", "hole", "home", "hood",
+    "hook", "horn", "host", "hour", "huge", "hunt", "idea", "inch", "iron", "isle", "item", "jazz",
+    "jeep", "joke", "jump", "jury", "keen", "kept", "kick", "kind", "king", "kite", "knee", "knot",
+    "lace", "lake", "lamp", "land", "lane", "last", "lava", "lawn", "leaf", "lens", "lift", "lime",
+    "line", "link", "lion", "list", "loaf", "lock", "loft", "logo", "long", "loop", "lord", "luck",
+    "lung", "mail", "main", "male", "malt", "mask", "mass", "mast", "meal", "meat", "mild", "milk",
+    "mill", "mint", "mist", "moat", "mode", "mole", "moon", "moss", "moth", "mule", "myth", "nail",
+    "name", "neck", "nest", "news", "node", "noon", "nose", "note", "oath", "oven", "palm", "park",
+    "path", "peak", "pear", "pine", "pink", "pipe", "plan", "plum", "poem", "pond", "pony", "pool",
+    "port", "pure", "quiz", "race", "raft", "rail", "rain", "ramp", "rich", "ring", "road", "rock",
+    "roof", "room", "rope", "rose", "ruby", "rule", "safe", "sail", "salt", "sand", "seal", "seed",
+    "ship", "shoe", "silk", "sing", "sink", "snow", "soap", "sock", "sofa", "soil", "song", "soup",
+    "star", "stem", "swan", "tail", "tank", "tent", "tide", "tile", "toad", "tone", "tool", "town",
+    "tree", "tube", "tune", "vase", "vest", "vine", "wave", "wolf", "wood", "yard", "yarn", "zinc",
+    "zone",
+];
+
+/// Read a newline-separated wordlist, skipping blank lines and repeats
+pub fn read_wordlist(path: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read wordlist {}: {}", path.display(), e))?;
+    let mut seen = HashSet::new();
+    let words: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty() && seen.insert(*word))
+        .map(String::from)
+        .collect();
+    if words.len() < MIN_CUSTOM_WORDS {
+        return Err(format!(
+            "Wordlist {} has {} distinct words; at least {} are needed",
+            path.display(),
+            words.len(),
+            MIN_CUSTOM_WORDS
+        ));
+    }
+    Ok(words)
+}
+
+/// Draw passphrases from the list at `path` for the rest of the process.
+/// On error the built-in list stays in use
+pub fn load_custom(path: &Path) -> Result<usize, String> {
+    let words = read_wordlist(path)?;
+    let count = words.len();
+    CUSTOM
+        .set(words)
+        .map_err(|_| "A custom wordlist is already loaded".to_string())?;
+    Ok(count)
+}
+
+/// Number of words passphrases are drawn from
+pub fn word_count() -> usize {
+    CUSTOM.get().map_or(WORDS.len(), Vec::len)
+}
+
+/// Word `index` of the active list
+pub fn word(index: usize) -> &'static str {
+    match CUSTOM.get() {
+        Some(words) => &words[index],
+        None => WORDS[index],
+    }
+}
@@ -0,0 +1,33 @@
+use std::process::Command;
+
+/// HMAC-SHA1 challenge-response against slot 2 of an attached YubiKey.
+/// Shells out to `ykchalresp` from yubikey-personalization, which must be on `PATH`.
+pub fn challenge_response(challenge: &[u8]) -> Result<Vec<u8>, String> {
+    let hex: String = challenge.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let output = Command::new("ykchalresp")
+        .args(["-2", "-x", &hex])
+        .output()
+        .map_err(|e| format!("Failed to run ykchalresp: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("YubiKey challenge failed: {}", stderr.trim()));
+    }
+
+    let response = String::from_utf8_lossy(&output.stdout);
+    decode_hex(response.trim())
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.is_empty() || !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err("Unexpected YubiKey response".into());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| "Unexpected YubiKey response".to_string())
+        })
+        .collect()
+}